@@ -0,0 +1,40 @@
+use std::{env, fs, path::PathBuf};
+
+// The kernel symbol table is embedded as a fixed-size blob so that filling it in
+// doesn't move any code: build once, dump the symbols with `make symbols`, build again.
+const KSYMS_CAPACITY : usize = 256 * 1024;
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let symbols = env::var("TINIX_SYMBOLS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| manifest_dir.join("target").join("tinix.sym"));
+
+    println!("cargo:rerun-if-env-changed=TINIX_SYMBOLS");
+    println!("cargo:rerun-if-changed={}", symbols.display());
+
+    let mut table = fs::read(&symbols).unwrap_or_default();
+    if table.len() > KSYMS_CAPACITY {
+        println!("cargo:warning=Symbol table is {} bytes, truncating to {}", table.len(), KSYMS_CAPACITY);
+        table.truncate(KSYMS_CAPACITY);
+        // Drop the partial last line.
+        let end = table.iter().rposition(|&b| b == b'\n').map(|i| i + 1).unwrap_or(0);
+        table.truncate(end);
+    }
+    table.resize(KSYMS_CAPACITY, 0);
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("ksyms.bin"), table).expect("Unable To Write Symbol Table");
+
+    // The initramfs is a plain ustar archive, made by `make initramfs`. Without
+    // one the kernel boots with an empty root filesystem.
+    let initramfs = env::var("TINIX_INITRAMFS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| manifest_dir.join("target").join("initramfs.tar"));
+
+    println!("cargo:rerun-if-env-changed=TINIX_INITRAMFS");
+    println!("cargo:rerun-if-changed={}", initramfs.display());
+
+    let archive = fs::read(&initramfs).unwrap_or_default();
+    fs::write(out_dir.join("initramfs.tar"), archive).expect("Unable To Write Initramfs");
+}
@@ -1,11 +1,52 @@
 pub mod x64;
 
+use alloc::boxed::Box;
+
+pub use x64::irq::{HandlerId, IrqInfo, IrqStats};
+use x64::irq::IrqHandlerFn;
+
+/// Adds `f` to the handlers of `irq`. Lines are shared, so registering a second
+/// handler chains it after the first instead of replacing it.
 pub fn set_interrupt(irq : u8, f : fn(u8)) -> Result<(),&'static str> {
+    set_named_interrupt(irq, "<unnamed>", f).map(|_| ())
+}
+
+pub fn set_named_interrupt(irq : u8, name : &'static str, f : fn(u8)) -> Result<HandlerId, &'static str> {
+    add_interrupt_handler(irq, name, IrqHandlerFn::Plain(f))
+}
+
+/// Registers a handler that is passed `context` on every call, e.g. a pointer to driver state.
+pub fn set_interrupt_with_context(irq : u8, name : &'static str, f : fn(u8, usize), context : usize) -> Result<HandlerId, &'static str> {
+    add_interrupt_handler(irq, name, IrqHandlerFn::Context(f, context))
+}
+
+/// Registers a closure as a handler. Needs the heap, so it can't be used for boot-time IRQs.
+pub fn set_interrupt_closure<F : FnMut(u8) + Send + 'static>(irq : u8, name : &'static str, f : F) -> Result<HandlerId, &'static str> {
+    add_interrupt_handler(irq, name, IrqHandlerFn::Boxed(Box::new(f)))
+}
+
+fn add_interrupt_handler(irq : u8, name : &'static str, f : IrqHandlerFn) -> Result<HandlerId, &'static str> {
     disable_irq(irq);
-    x64::idt::set_handler(irq, f);
+    let result = x64::irq::register(irq, name, f);
     enable_irq(irq);
-    Ok(())
-} 
+    result
+}
+
+pub fn remove_interrupt(irq : u8, id : HandlerId) -> Result<(), &'static str> {
+    let result = x64::irq::unregister(irq, id);
+    if x64::irq::handler_count(irq) == 0 {
+        disable_irq(irq);
+    }
+    result
+}
+
+pub fn interrupt_table() -> alloc::vec::Vec<IrqInfo> {
+    x64::irq::table()
+}
+
+pub fn interrupt_stats(irq : u8) -> IrqStats {
+    x64::irq::stats(irq)
+}
 
 pub fn enable_irq(irq : u8) {
     x64::idt::clear_irq_mask(irq);
@@ -13,4 +54,4 @@ pub fn enable_irq(irq : u8) {
 
 pub fn disable_irq(irq : u8) {
     x64::idt::set_irq_mask(irq);
-}
\ No newline at end of file
+}
@@ -0,0 +1,78 @@
+use core::arch::asm;
+
+use crate::input::serial_println;
+use crate::kernel::symbols::{self, Symbolized};
+use crate::elog;
+
+pub const MAX_FRAMES : usize = 32;
+
+/// Reads the current frame pointer. The kernel is built with
+/// `-C force-frame-pointers=yes`, so every frame starts with the caller's rbp
+/// followed by the return address.
+#[inline(always)]
+pub fn frame_pointer() -> u64 {
+    let rbp : u64;
+    unsafe { asm!("mov {}, rbp", out(reg) rbp, options(nomem, nostack, preserves_flags)) };
+    rbp
+}
+
+/// Calls `f` with the depth and return address of each frame, starting at `rbp`.
+/// Stops at a null, misaligned or non-increasing frame pointer.
+pub fn walk<F : FnMut(usize, u64)>(mut rbp : u64, mut f : F) {
+    for depth in 0..MAX_FRAMES {
+        if rbp == 0 || rbp % 8 != 0 {
+            return;
+        }
+        let frame = rbp as *const u64;
+        let (next, return_address) = unsafe { (*frame, *frame.add(1)) };
+        if return_address == 0 {
+            return;
+        }
+        f(depth, return_address);
+        if next <= rbp {
+            return;
+        }
+        rbp = next;
+    }
+}
+
+/// Returns the frame pointer of the code an exception interrupted, given the
+/// frame pointer of the `x86-interrupt` handler that caught it.
+pub fn interrupted_frame_pointer(handler_rbp : u64) -> u64 {
+    if handler_rbp == 0 { 0 } else { unsafe { *(handler_rbp as *const u64) } }
+}
+
+fn frame_symbol(address : u64, is_return_address : bool) -> Symbolized {
+    // A return address points after the call, which may already be the next function.
+    Symbolized(if is_return_address { address - 1 } else { address })
+}
+
+pub fn print_serial(rip : u64, rbp : u64) {
+    serial_println!("Backtrace:");
+    serial_println!("  #0  {}", frame_symbol(rip, false));
+    walk(rbp, |depth, address| {
+        serial_println!("  #{:<2} {}", depth + 1, frame_symbol(address, true));
+    });
+}
+
+/// Prints a symbolized backtrace to the console, serial and the kernel log.
+pub fn print(rip : u64, rbp : u64) {
+    elog!("Backtrace:\n");
+    elog!("  #0  {}\n", frame_symbol(rip, false));
+    walk(rbp, |depth, address| {
+        elog!("  #{:<2} {}\n", depth + 1, frame_symbol(address, true));
+    });
+    if !symbols::is_loaded() {
+        elog!("  (no symbol table, build with `make build`)\n");
+    }
+}
+
+/// Prints a backtrace of the caller.
+#[inline(always)]
+pub fn print_here() {
+    let rbp = frame_pointer();
+    elog!("Backtrace:\n");
+    walk(rbp, |depth, address| {
+        elog!("  #{:<2} {}\n", depth, frame_symbol(address, true));
+    });
+}
@@ -0,0 +1,144 @@
+use core::fmt::Display;
+
+use x86_64::structures::idt::{InterruptStackFrame, PageFaultErrorCode};
+use x86_64::VirtAddr;
+
+use super::{backtrace, irq};
+use crate::kernel::symbols::Symbolized;
+use crate::{debug, error, warn};
+use crate::kernel::task;
+
+/// A task killed by a fault exits with `FAULT_EXIT_BASE + vector`, like a shell
+/// reports a process killed by a signal.
+pub const FAULT_EXIT_BASE : usize = 128;
+
+const RFLAGS_INTERRUPT_FLAG : u64 = 1 << 9;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Fault {
+    DivideError,
+    Breakpoint,
+    InvalidOpcode,
+    SegmentNotPresent(u64),
+    StackSegment(u64),
+    GeneralProtection(u64),
+    PageFault { address : u64, error : PageFaultErrorCode },
+}
+
+impl Fault {
+    pub fn vector(&self) -> u8 {
+        match self {
+            Fault::DivideError => 0,
+            Fault::Breakpoint => 3,
+            Fault::InvalidOpcode => 6,
+            Fault::SegmentNotPresent(_) => 11,
+            Fault::StackSegment(_) => 12,
+            Fault::GeneralProtection(_) => 13,
+            Fault::PageFault { .. } => 14,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fault::DivideError => "DIVIDE ERROR",
+            Fault::Breakpoint => "BREAKPOINT",
+            Fault::InvalidOpcode => "INVALID OPCODE",
+            Fault::SegmentNotPresent(_) => "SEGMENT NOT PRESENT",
+            Fault::StackSegment(_) => "STACK SEGMENT FAULT",
+            Fault::GeneralProtection(_) => "GENERAL PROTECTION FAULT",
+            Fault::PageFault { .. } => "PAGE FAULT",
+        }
+    }
+
+    /// Traps report the instruction after the one that caused them, so execution can just continue.
+    pub fn is_trap(&self) -> bool {
+        matches!(self, Fault::Breakpoint)
+    }
+
+    pub fn exit_status(&self) -> task::ExitStatus {
+        FAULT_EXIT_BASE + self.vector() as usize
+    }
+}
+
+fn write_selector_error(f : &mut core::fmt::Formatter<'_>, code : u64) -> core::fmt::Result {
+    if code == 0 {
+        return write!(f, "no selector");
+    }
+    let table = match (code >> 1) & 0b11 {
+        0 => "GDT",
+        2 => "LDT",
+        _ => "IDT",
+    };
+    write!(f, "{} selector {:#x}{}", table, code >> 3, if code & 1 != 0 {" (external)"} else {""})
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name())?;
+        match self {
+            Fault::SegmentNotPresent(code) | Fault::StackSegment(code) | Fault::GeneralProtection(code) => {
+                write!(f, " - ")?;
+                write_selector_error(f, *code)
+            },
+            Fault::PageFault { address, error } => {
+                write!(f, " - {} {} {:#x}",
+                    if error.contains(PageFaultErrorCode::PROTECTION_VIOLATION) {"protection violation on"} else {"non-present page on"},
+                    if error.contains(PageFaultErrorCode::INSTRUCTION_FETCH) {"fetch from"}
+                    else if error.contains(PageFaultErrorCode::CAUSED_BY_WRITE) {"write to"}
+                    else {"read from"},
+                    address)?;
+                if error.contains(PageFaultErrorCode::MALFORMED_TABLE) {
+                    write!(f, " (malformed page table)")?;
+                }
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Reports a CPU exception and decides what to do about it. Traps resume, faults
+/// in a `task::killable` section kill that task, and faults anywhere else panic.
+/// Even there, a fault with interrupts disabled or inside an IRQ handler panics:
+/// the task may hold a spinlock or owe the PIC an EOI that leaving would skip.
+/// `handler_rbp` is the frame pointer of the `x86-interrupt` handler.
+pub fn dispatch(fault : Fault, stack_frame : &mut InterruptStackFrame, handler_rbp : u64) {
+    let rip = stack_frame.instruction_pointer.as_u64();
+    let rbp = backtrace::interrupted_frame_pointer(handler_rbp);
+
+    if fault.is_trap() {
+        report_trap(fault, rip, rbp);
+        return;
+    }
+    error!("EXCEPTION: {} at {}", fault, Symbolized(rip));
+    debug!("{:#?}", stack_frame);
+    backtrace::print(rip, rbp);
+
+    let status = fault.exit_status();
+    let interruptible = stack_frame.cpu_flags & RFLAGS_INTERRUPT_FLAG != 0 && !irq::in_irq();
+    if interruptible && task::is_killable() && task::kill(status) {
+        warn!("Killing Task '{}' - Exit Status {}", task::current_name().unwrap_or_default(), status);
+        resume_at_exit(stack_frame);
+    } else {
+        panic!("Unrecoverable Kernel {} at {}", fault, Symbolized(rip));
+    }
+}
+
+/// Logs a trap at debug level; execution then carries on after the trapping instruction.
+pub fn report_trap(fault : Fault, rip : u64, rbp : u64) {
+    debug!("EXCEPTION: {} at {}", fault, Symbolized(rip));
+    backtrace::print_serial(rip, rbp);
+}
+
+/// Rewrites the interrupt frame so `iretq` lands in `task::exit_killed`, on the
+/// faulting stack but aligned as if it had just been called.
+fn resume_at_exit(stack_frame : &mut InterruptStackFrame) {
+    let exit = task::exit_killed as usize as u64;
+    unsafe {
+        stack_frame.as_mut().update(|frame| {
+            let rsp = (frame.stack_pointer.as_u64() & !0xF) - 8;
+            frame.instruction_pointer = VirtAddr::new(exit);
+            frame.stack_pointer = VirtAddr::new(rsp);
+        });
+    }
+}
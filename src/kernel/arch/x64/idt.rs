@@ -1,11 +1,11 @@
 use x86_64::instructions::port::Port;
 use x86_64::structures::idt::*;
+use x86_64::VirtAddr;
 use super::*;
 use crate::kernel::InitResult;
 
 
 use lazy_static::lazy_static;
-use spin::Mutex;
 const PIC1: u16 = 0x21;
 const PIC2: u16 = 0xA1;
 
@@ -14,21 +14,19 @@ pub fn init() -> InitResult<()> {
     Ok(())
 }
 
-fn default_handler(_irq : u8) {
-    //crate::input::serial_println!("Fired IRQ #{}", irq);
-}
-
 // Translate IRQ into system interrupt
 fn interrupt_index(irq: u8) -> u8 {
     (crate::kernel::hardware::pic::PIC_1_OFFSET + irq) as u8
 }
 
 lazy_static! {
-    pub static ref IRQ_HANDLERS: Mutex<[fn(u8); 16]> = Mutex::new([default_handler; 16]);
     static ref IDT : InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
 
-        idt.breakpoint.set_handler_fn(breakpoint_handler);
+        unsafe {
+            idt.breakpoint.set_handler_addr(VirtAddr::new(trap::tinix_breakpoint_entry as usize as u64));
+            idt.debug.set_handler_addr(VirtAddr::new(trap::tinix_debug_entry as usize as u64));
+        }
         unsafe {
             idt.double_fault
             .set_handler_fn(double_fault_handler)
@@ -41,6 +39,9 @@ lazy_static! {
         idt.non_maskable_interrupt.set_handler_fn(nmi);
 
         idt.divide_error.set_handler_fn(divide_fault);
+        idt.invalid_opcode.set_handler_fn(invalid_opcode);
+        idt.segment_not_present.set_handler_fn(segment_not_present);
+        idt.stack_segment_fault.set_handler_fn(stack_segment_fault);
 
         idt[interrupt_index(0) as usize].set_handler_fn(irq0);
         idt[interrupt_index(1) as usize].set_handler_fn(irq1);
@@ -69,10 +70,8 @@ lazy_static! {
 macro_rules! irq_handler {
     ($handler:ident, $irq:expr) => {
         extern "x86-interrupt" fn $handler(_stack_frame : InterruptStackFrame) {
-            let handlers = IRQ_HANDLERS.lock();
-            handlers[$irq]($irq);
-            crate::kernel::hardware::pic::notify_end_of_interrupt(interrupt_index($irq));
-        }        
+            irq::dispatch($irq);
+        }
     };
 }
 
@@ -93,10 +92,6 @@ irq_handler!(irq13, 13);
 irq_handler!(irq14, 14);
 irq_handler!(irq15, 15);
 
-pub fn set_handler(irq : u8, func : fn(u8)) {
-    IRQ_HANDLERS.lock()[irq as usize] = func;
-}
-
 
 pub fn set_irq_mask(irq: u8) {
     let mut port: Port<u8> = Port::new(if irq < 8 { PIC1 } else { PIC2 });
@@ -119,25 +114,48 @@ extern "x86-interrupt" fn double_fault_handler(stack_frame: InterruptStackFrame,
     panic!("EXCEPTION: DOUBLE FAULT\n\r{:#?}, Error: 0x{:x}", stack_frame, _error_code);
 }
 
-extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
-    crate::print!("EXCEPTION: BREAKPOINT\n\r{:#?}\n\r", stack_frame);
+/// Entered through `trap::tinix_breakpoint_entry`, so it sees (and can change) every register.
+pub(crate) extern "C" fn breakpoint_handler(frame: &mut trap::TrapFrame) {
+    if crate::kernel::gdb::is_enabled() {
+        crate::kernel::gdb::on_trap(frame, trap::BREAKPOINT_VECTOR);
+    } else {
+        fault::report_trap(fault::Fault::Breakpoint, frame.rip, frame.rbp);
+    }
 }
 
-extern "x86-interrupt" fn page_fault_handler(stack_frame: InterruptStackFrame, ec : PageFaultErrorCode) {
+pub(crate) extern "C" fn debug_handler(frame: &mut trap::TrapFrame) {
+    if crate::kernel::gdb::is_enabled() {
+        crate::kernel::gdb::on_trap(frame, trap::DEBUG_VECTOR);
+    } else {
+        frame.rflags &= !trap::TRAP_FLAG;
+    }
+}
+
+extern "x86-interrupt" fn page_fault_handler(mut stack_frame: InterruptStackFrame, ec : PageFaultErrorCode) {
     use x86_64::registers::control::Cr2;
-    use crate::println;
-    println!("EXCEPTION: PAGE FAULT");
-    println!("Accessed Address: {:?}", Cr2::read());
-    println!("Error Code: {:?}", ec);
-    println!("{:#?}", stack_frame);
-    loop {crate::time::sleep_ticks(10)}
+    let rbp = backtrace::frame_pointer();
+    let address = Cr2::read().as_u64();
+    fault::dispatch(fault::Fault::PageFault { address, error : ec }, &mut stack_frame, rbp);
+}
+
+extern "x86-interrupt" fn general_protection_fault(mut stack_frame: InterruptStackFrame, ec : u64) {
+    let rbp = backtrace::frame_pointer();
+    fault::dispatch(fault::Fault::GeneralProtection(ec), &mut stack_frame, rbp);
+}
+
+extern "x86-interrupt" fn segment_not_present(mut stack_frame: InterruptStackFrame, ec : u64) {
+    let rbp = backtrace::frame_pointer();
+    fault::dispatch(fault::Fault::SegmentNotPresent(ec), &mut stack_frame, rbp);
+}
+
+extern "x86-interrupt" fn stack_segment_fault(mut stack_frame: InterruptStackFrame, ec : u64) {
+    let rbp = backtrace::frame_pointer();
+    fault::dispatch(fault::Fault::StackSegment(ec), &mut stack_frame, rbp);
 }
 
-extern "x86-interrupt" fn general_protection_fault(stack_frame: InterruptStackFrame, ec : u64) {
-    use crate::println;
-    println!("GP Fault Error Code: {:?}", ec);
-    println!("{:#?}", stack_frame);
-    loop {crate::time::sleep_ticks(10)}
+extern "x86-interrupt" fn invalid_opcode(mut stack_frame: InterruptStackFrame) {
+    let rbp = backtrace::frame_pointer();
+    fault::dispatch(fault::Fault::InvalidOpcode, &mut stack_frame, rbp);
 }
 
 extern "x86-interrupt" fn nmi(_stack_frame: InterruptStackFrame) {
@@ -149,6 +167,7 @@ extern "x86-interrupt" fn swi_0(_stack_frame: InterruptStackFrame) {
     crate::input::serial_println!("SWI0 Fired...");
 }
 
-extern "x86-interrupt" fn divide_fault(stack_frame: InterruptStackFrame) {
-    crate::input::serial_println!("Divide Fault Fired... {:?}", stack_frame);
+extern "x86-interrupt" fn divide_fault(mut stack_frame: InterruptStackFrame) {
+    let rbp = backtrace::frame_pointer();
+    fault::dispatch(fault::Fault::DivideError, &mut stack_frame, rbp);
 }
\ No newline at end of file
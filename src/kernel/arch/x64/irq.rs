@@ -0,0 +1,340 @@
+use core::fmt::Display;
+
+use alloc::{boxed::Box, vec::Vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::kernel::hardware::pic;
+
+pub const IRQ_COUNT : usize = 16;
+/// How many handlers may share a single IRQ line.
+pub const MAX_SHARED_HANDLERS : usize = 4;
+
+/// Handlers unregistered while running, waiting to be dropped outside an IRQ.
+const RETIRED_COUNT : usize = IRQ_COUNT * MAX_SHARED_HANDLERS;
+
+const SPURIOUS_MASTER_IRQ : u8 = 7;
+const SPURIOUS_SLAVE_IRQ  : u8 = 15;
+
+pub type HandlerId = usize;
+
+/// The ways a handler can be attached to a line. Plain and context handlers
+/// don't allocate, so they can be registered before the heap is up (the PIT is).
+pub enum IrqHandlerFn {
+    Plain(fn(u8)),
+    Context(fn(u8, usize), usize),
+    Boxed(Box<dyn FnMut(u8) + Send>),
+}
+
+impl IrqHandlerFn {
+    fn call(&mut self, irq : u8) {
+        match self {
+            IrqHandlerFn::Plain(f) => f(irq),
+            IrqHandlerFn::Context(f, context) => f(irq, *context),
+            IrqHandlerFn::Boxed(f) => f(irq),
+        }
+    }
+}
+
+struct IrqHandler {
+    id   : HandlerId,
+    name : &'static str,
+    func : IrqHandlerFn,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IrqStats {
+    pub fired        : u64,
+    pub spurious     : u64,
+    pub unhandled    : u64,
+    pub total_cycles : u64,
+    pub max_cycles   : u64,
+    pub last_cycles  : u64,
+}
+
+impl IrqStats {
+    pub fn average_cycles(&self) -> u64 {
+        if self.fired == 0 { 0 } else { self.total_cycles / self.fired }
+    }
+}
+
+const EMPTY_HANDLER : Option<IrqHandler> = None;
+
+struct IrqLine {
+    handlers : [Option<IrqHandler>; MAX_SHARED_HANDLERS],
+    /// Handlers taken out of their slot while `dispatch` runs them, so they can
+    /// change the table. Unregistering one clears its entry here.
+    running  : [Option<HandlerId>; MAX_SHARED_HANDLERS],
+    stats    : IrqStats,
+}
+
+impl IrqLine {
+    const fn new() -> Self {
+        Self {
+            handlers : [EMPTY_HANDLER; MAX_SHARED_HANDLERS],
+            running : [None; MAX_SHARED_HANDLERS],
+            stats : IrqStats {
+                fired : 0,
+                spurious : 0,
+                unhandled : 0,
+                total_cycles : 0,
+                max_cycles : 0,
+                last_cycles : 0,
+            },
+        }
+    }
+
+    fn handler_count(&self) -> usize {
+        self.handlers.iter().filter(|h| h.is_some()).count() + self.running.iter().filter(|id| id.is_some()).count()
+    }
+}
+
+const EMPTY_LINE : IrqLine = IrqLine::new();
+
+pub struct IrqTable {
+    lines   : [IrqLine; IRQ_COUNT],
+    next_id : HandlerId,
+    /// Dropping a boxed handler frees heap memory, which an IRQ can't do in case
+    /// it interrupted the allocator, so `dispatch` parks them here instead.
+    retired : [Option<IrqHandler>; RETIRED_COUNT],
+}
+
+impl IrqTable {
+    const fn new() -> Self {
+        Self {
+            lines : [EMPTY_LINE; IRQ_COUNT],
+            next_id : 1,
+            retired : [EMPTY_HANDLER; RETIRED_COUNT],
+        }
+    }
+
+    /// Parks a handler for [take_retired]. If every place is taken the handler
+    /// is leaked, which only a handler re-registering itself on every IRQ can cause.
+    fn retire(&mut self, handler : IrqHandler) {
+        match self.retired.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(handler),
+            None => core::mem::forget(handler),
+        }
+    }
+
+    /// Takes the retired handlers, for the caller to drop once the table is released.
+    fn take_retired(&mut self) -> [Option<IrqHandler>; RETIRED_COUNT] {
+        core::mem::replace(&mut self.retired, [EMPTY_HANDLER; RETIRED_COUNT])
+    }
+
+    fn register(&mut self, irq : u8, name : &'static str, func : IrqHandlerFn) -> Result<HandlerId, &'static str> {
+        if irq as usize >= IRQ_COUNT {
+            return Err("IRQ Out Of Range");
+        }
+        let id = self.next_id;
+        let line = &mut self.lines[irq as usize];
+        for (slot, running) in line.handlers.iter_mut().zip(line.running.iter()) {
+            if slot.is_none() && running.is_none() {
+                *slot = Some(IrqHandler { id, name, func });
+                self.next_id += 1;
+                return Ok(id);
+            }
+        }
+        Err("IRQ Line Has No Free Handler Slots")
+    }
+
+    fn unregister(&mut self, irq : u8, id : HandlerId) -> Result<(), &'static str> {
+        if irq as usize >= IRQ_COUNT {
+            return Err("IRQ Out Of Range");
+        }
+        let line = &mut self.lines[irq as usize];
+        for slot in line.handlers.iter_mut() {
+            if slot.as_ref().map(|h| h.id) == Some(id) {
+                *slot = None;
+                return Ok(());
+            }
+        }
+        for running in line.running.iter_mut() {
+            if *running == Some(id) {
+                *running = None;
+                return Ok(());
+            }
+        }
+        Err("No Such Handler")
+    }
+}
+
+/// How many IRQs are being dispatched right now, on this (the only) CPU.
+static DEPTH : AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    static ref IRQ_TABLE : Mutex<IrqTable> = Mutex::new(IrqTable::new());
+}
+
+fn cycles() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+/// A spurious IRQ7/IRQ15 is raised by the PIC without setting its In-Service bit.
+fn is_spurious(irq : u8) -> bool {
+    (irq == SPURIOUS_MASTER_IRQ || irq == SPURIOUS_SLAVE_IRQ) && !pic::in_service(irq)
+}
+
+/// Called from the IDT stubs with interrupts disabled. Runs every handler on the line,
+/// updates the line's statistics and acknowledges the PIC. The table isn't locked
+/// while handlers run, so they may register and unregister handlers themselves.
+pub fn dispatch(irq : u8) {
+    DEPTH.fetch_add(1, Ordering::SeqCst);
+    dispatch_line(irq);
+    DEPTH.fetch_sub(1, Ordering::SeqCst);
+}
+
+/// Whether the CPU is inside an IRQ handler, so whatever it interrupted may hold locks.
+pub fn in_irq() -> bool {
+    DEPTH.load(Ordering::SeqCst) != 0
+}
+
+fn dispatch_line(irq : u8) {
+    let mut handlers = [EMPTY_HANDLER; MAX_SHARED_HANDLERS];
+    {
+        let mut table = IRQ_TABLE.lock();
+        let line = &mut table.lines[irq as usize];
+
+        if is_spurious(irq) {
+            line.stats.spurious += 1;
+            // The master still saw the cascade line fire for a spurious slave IRQ.
+            if irq == SPURIOUS_SLAVE_IRQ {
+                pic::notify_master_end_of_interrupt();
+            }
+            return;
+        }
+
+        for (index, slot) in line.handlers.iter_mut().enumerate() {
+            handlers[index] = slot.take();
+            line.running[index] = handlers[index].as_ref().map(|h| h.id);
+        }
+    }
+
+    let start = cycles();
+    let mut handled = false;
+    for handler in handlers.iter_mut().flatten() {
+        handler.func.call(irq);
+        handled = true;
+    }
+    let elapsed = cycles().wrapping_sub(start);
+
+    let mut table = IRQ_TABLE.lock();
+    let line = &mut table.lines[irq as usize];
+    for (index, handler) in handlers.iter_mut().enumerate() {
+        // Put back whatever wasn't unregistered while it ran.
+        if line.running[index].take().is_some() {
+            line.handlers[index] = handler.take();
+        }
+    }
+
+    line.stats.fired += 1;
+    line.stats.last_cycles = elapsed;
+    line.stats.total_cycles += elapsed;
+    if elapsed > line.stats.max_cycles {
+        line.stats.max_cycles = elapsed;
+    }
+    if !handled {
+        line.stats.unhandled += 1;
+    }
+    // Handlers unregistered while running are dropped later, by `register` or `unregister`.
+    for handler in handlers.iter_mut().filter_map(Option::take) {
+        table.retire(handler);
+    }
+    drop(table);
+
+    pic::notify_end_of_interrupt(pic::PIC_1_OFFSET + irq);
+}
+
+pub fn register(irq : u8, name : &'static str, func : IrqHandlerFn) -> Result<HandlerId, &'static str> {
+    let mut result = Err("");
+    without_interrupts(|| {
+        let mut table = IRQ_TABLE.lock();
+        result = table.register(irq, name, func);
+        drop_retired(table);
+    });
+    result
+}
+
+pub fn unregister(irq : u8, id : HandlerId) -> Result<(), &'static str> {
+    let mut result = Err("");
+    without_interrupts(|| {
+        let mut table = IRQ_TABLE.lock();
+        result = table.unregister(irq, id);
+        drop_retired(table);
+    });
+    result
+}
+
+/// Releases the table, then drops the retired handlers unless called from an IRQ.
+fn drop_retired(mut table : spin::MutexGuard<IrqTable>) {
+    if in_irq() {
+        return;
+    }
+    let retired = table.take_retired();
+    drop(table);
+    drop(retired);
+}
+
+pub fn stats(irq : u8) -> IrqStats {
+    let mut stats = IrqStats::default();
+    without_interrupts(|| {
+        stats = IRQ_TABLE.lock().lines[irq as usize].stats;
+    });
+    stats
+}
+
+pub fn reset_stats(irq : u8) {
+    without_interrupts(|| {
+        IRQ_TABLE.lock().lines[irq as usize].stats = IrqStats::default();
+    });
+}
+
+/// A snapshot of one IRQ line, as returned by [table].
+#[derive(Debug, Clone)]
+pub struct IrqInfo {
+    pub irq      : u8,
+    pub handlers : Vec<(HandlerId, &'static str)>,
+    pub stats    : IrqStats,
+}
+
+pub fn table() -> Vec<IrqInfo> {
+    let mut info = Vec::with_capacity(IRQ_COUNT);
+    without_interrupts(|| {
+        let table = IRQ_TABLE.lock();
+        for (irq, line) in table.lines.iter().enumerate() {
+            info.push(IrqInfo {
+                irq : irq as u8,
+                handlers : line.handlers.iter().flatten().map(|h| (h.id, h.name)).collect(),
+                stats : line.stats,
+            });
+        }
+    });
+    info
+}
+
+pub fn handler_count(irq : u8) -> usize {
+    let mut count = 0;
+    without_interrupts(|| {
+        count = IRQ_TABLE.lock().lines[irq as usize].handler_count();
+    });
+    count
+}
+
+impl Display for IrqInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "IRQ{:<2} {:>10} fired {:>6} spurious {:>6} unhandled  avg {:>8} max {:>8} cycles ",
+            self.irq,
+            self.stats.fired,
+            self.stats.spurious,
+            self.stats.unhandled,
+            self.stats.average_cycles(),
+            self.stats.max_cycles)?;
+        for (index, (_, name)) in self.handlers.iter().enumerate() {
+            if index > 0 { write!(f, ", ")?; }
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
@@ -1,15 +1,54 @@
 pub mod allocator;
 pub mod heap;
 use crate::kernel::InitResult;
-use x86_64::{VirtAddr, structures::paging::{OffsetPageTable, PageTable}};
+use core::sync::atomic::{AtomicU64, Ordering};
+use x86_64::{PhysAddr, VirtAddr, structures::paging::{OffsetPageTable, PageTable, Translate}};
 use crate::input::{serial_print, serial_println};
 
 
+static PHYSICAL_MEMORY_OFFSET : AtomicU64 = AtomicU64::new(0);
+
 pub unsafe fn init(physical_memory_offset: VirtAddr) -> InitResult<OffsetPageTable<'static>> {
+    PHYSICAL_MEMORY_OFFSET.store(physical_memory_offset.as_u64(), Ordering::SeqCst);
     let level_4_table = active_level_4_table(physical_memory_offset);
     Ok(OffsetPageTable::new(level_4_table, physical_memory_offset))
 }
 
+/// Walks the active page tables to find where `addr` is mapped, if anywhere.
+/// Returns None before [init] has run.
+pub fn translate(addr: VirtAddr) -> Option<PhysAddr> {
+    let offset = PHYSICAL_MEMORY_OFFSET.load(Ordering::SeqCst);
+    if offset == 0 {
+        return None;
+    }
+    let offset = VirtAddr::new(offset);
+    let table = unsafe { OffsetPageTable::new(active_level_4_table(offset), offset) };
+    table.translate_addr(addr)
+}
+
+/// Checks that every page in `addr..addr + len` is mapped.
+pub fn is_mapped(addr: u64, len: u64) -> bool {
+    if len == 0 {
+        return true;
+    }
+    let end = match addr.checked_add(len - 1) {
+        Some(end) => end,
+        None => return false,
+    };
+    let mut page = addr & !0xFFF;
+    while page <= end {
+        match VirtAddr::try_new(page) {
+            Ok(virt) if translate(virt).is_some() => {},
+            _ => return false,
+        }
+        page = match page.checked_add(0x1000) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    true
+}
+
 /// Returns a mutable reference to the active level 4 table.
 ///
 /// This function is unsafe because the caller must guarantee that the
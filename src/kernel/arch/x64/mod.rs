@@ -1,4 +1,8 @@
 pub mod idt;
+pub mod fault;
+pub mod backtrace;
+pub mod trap;
+pub mod irq;
 pub mod gdt;
 pub mod mem;
 
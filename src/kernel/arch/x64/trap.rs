@@ -0,0 +1,93 @@
+use core::arch::global_asm;
+
+pub const DEBUG_VECTOR : u8 = 1;
+pub const BREAKPOINT_VECTOR : u8 = 3;
+
+/// RFLAGS.TF, raises a debug exception after the next instruction.
+pub const TRAP_FLAG : u64 = 1 << 8;
+
+/// Every general purpose register of the interrupted code, as pushed by the
+/// entry stubs below, followed by the frame the CPU pushed.
+/// Unlike `InterruptStackFrame`, all of it can be read and changed by the handler.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TrapFrame {
+    pub r15 : u64,
+    pub r14 : u64,
+    pub r13 : u64,
+    pub r12 : u64,
+    pub r11 : u64,
+    pub r10 : u64,
+    pub r9  : u64,
+    pub r8  : u64,
+    pub rbp : u64,
+    pub rdi : u64,
+    pub rsi : u64,
+    pub rdx : u64,
+    pub rcx : u64,
+    pub rbx : u64,
+    pub rax : u64,
+
+    pub error_code : u64,
+
+    pub rip    : u64,
+    pub cs     : u64,
+    pub rflags : u64,
+    pub rsp    : u64,
+    pub ss     : u64,
+}
+
+macro_rules! trap_entry {
+    ($entry : literal, $handler : path) => {
+        global_asm!(
+            concat!(".global ", $entry, "\n", $entry, ":"),
+            "push 0",
+            "push rax",
+            "push rbx",
+            "push rcx",
+            "push rdx",
+            "push rsi",
+            "push rdi",
+            "push rbp",
+            "push r8",
+            "push r9",
+            "push r10",
+            "push r11",
+            "push r12",
+            "push r13",
+            "push r14",
+            "push r15",
+            "mov rdi, rsp",
+            "mov rbp, rsp",
+            "and rsp, -16",
+            "call {handler}",
+            "mov rsp, rbp",
+            "pop r15",
+            "pop r14",
+            "pop r13",
+            "pop r12",
+            "pop r11",
+            "pop r10",
+            "pop r9",
+            "pop r8",
+            "pop rbp",
+            "pop rdi",
+            "pop rsi",
+            "pop rdx",
+            "pop rcx",
+            "pop rbx",
+            "pop rax",
+            "add rsp, 8",
+            "iretq",
+            handler = sym $handler,
+        );
+    };
+}
+
+trap_entry!("tinix_debug_entry", super::idt::debug_handler);
+trap_entry!("tinix_breakpoint_entry", super::idt::breakpoint_handler);
+
+extern "C" {
+    pub fn tinix_debug_entry();
+    pub fn tinix_breakpoint_entry();
+}
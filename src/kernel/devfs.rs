@@ -0,0 +1,390 @@
+use alloc::{boxed::Box, collections::BTreeMap, format, string::String, vec::Vec};
+use core::arch::x86_64::_rdtsc;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::input;
+use crate::io::{IoReader, devices::{console, KeyBoard, Serial}};
+use crate::kernel::{InitResult, hardware::{ata, uart::{self, ComPort, COM_PORTS}}};
+use crate::kernel::vfs::{self, DirEntry, FileSystem, Metadata, NodeKind};
+use crate::kernel::task::{Gid, Uid};
+use crate::log;
+
+pub const MOUNT_POINT : &str = "/dev";
+
+const SECTOR_SIZE : usize = 512;
+
+/// Something a node in `/dev` reads from and writes to. Reads must not
+/// block, since they run with the filesystem locked and interrupts off.
+pub trait Device : Send {
+    fn read(&mut self, offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str>;
+    fn write(&mut self, offset : usize, buffer : &[u8]) -> Result<usize, &'static str>;
+
+    /// The size in bytes of a block device, character devices have none.
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn is_block(&self) -> bool {
+        false
+    }
+}
+
+struct Node {
+    device : Box<dyn Device>,
+    major  : u32,
+    minor  : u32,
+    mode   : u32,
+    uid    : Uid,
+    gid    : Gid,
+}
+
+lazy_static! {
+    static ref DEVICES : Mutex<BTreeMap<String, Node>> = Mutex::new(BTreeMap::new());
+}
+
+/// Adds `/dev/<name>`, readable and writable by everyone unless changed with chmod.
+pub fn register(name : &str, major : u32, minor : u32, device : Box<dyn Device>) -> Result<(), &'static str> {
+    without_interrupts(|| {
+        let mut devices = DEVICES.lock();
+        if devices.contains_key(name) {
+            return Err("File Exists");
+        }
+        let mode = if device.is_block() { 0o660 } else { 0o666 };
+        devices.insert(String::from(name), Node { device, major, minor, mode, uid : 0, gid : 0 });
+        Ok(())
+    })
+}
+
+pub fn unregister(name : &str) -> Result<(), &'static str> {
+    without_interrupts(|| {
+        DEVICES.lock().remove(name).map(|_| ()).ok_or("No Such Device")
+    })
+}
+
+fn with_device<T, F : FnOnce(&mut dyn Device) -> Result<T, &'static str>>(device : (u32, u32), f : F) -> Result<T, &'static str> {
+    without_interrupts(|| {
+        let mut devices = DEVICES.lock();
+        let node = devices.values_mut().find(|node| (node.major, node.minor) == device).ok_or("No Such Device")?;
+        f(node.device.as_mut())
+    })
+}
+
+/// Reads the device numbered `device`, wherever the node naming it lives.
+pub fn read_device(device : (u32, u32), offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+    with_device(device, |device| device.read(offset, buffer))
+}
+
+pub fn write_device(device : (u32, u32), offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+    with_device(device, |device| device.write(offset, buffer))
+}
+
+pub struct Null;
+
+impl Device for Null {
+    fn read(&mut self, _offset : usize, _buffer : &mut [u8]) -> Result<usize, &'static str> {
+        Ok(0)
+    }
+
+    fn write(&mut self, _offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+        Ok(buffer.len())
+    }
+}
+
+pub struct Zero;
+
+impl Device for Zero {
+    fn read(&mut self, _offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        buffer.fill(0);
+        Ok(buffer.len())
+    }
+
+    fn write(&mut self, _offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+        Ok(buffer.len())
+    }
+}
+
+/// xorshift64* seeded from the timestamp counter. Not fit for keys, but
+/// enough for shuffling and test data. Writes stir the state.
+pub struct Random {
+    state : u64,
+}
+
+impl Random {
+    pub fn new() -> Self {
+        let seed = unsafe { _rdtsc() };
+        Self { state : seed | 1 }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Device for Random {
+    fn read(&mut self, _offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        for chunk in buffer.chunks_mut(8) {
+            let bytes = self.next().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        Ok(buffer.len())
+    }
+
+    fn write(&mut self, _offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+        for byte in buffer {
+            self.state = (self.state ^ *byte as u64).rotate_left(8) | 1;
+        }
+        self.state ^= unsafe { _rdtsc() };
+        Ok(buffer.len())
+    }
+}
+
+/// Reads as many keys as have been typed, each as UTF-8, stopping at the
+/// first one that doesn't fit and leaving it for the next read.
+fn read_keys(buffer : &mut [u8]) -> usize {
+    let mut keyboard = KeyBoard;
+    let mut count = 0;
+    while let Some(key) = input::peek_key() {
+        if count + key.len_utf8() > buffer.len() {
+            break;
+        }
+        match keyboard.read() {
+            Some(key) => count += key.encode_utf8(&mut buffer[count..]).len(),
+            None => break,
+        }
+    }
+    count
+}
+
+/// The text console. Reading it gives what was typed on the keyboard.
+pub struct Console;
+
+impl Device for Console {
+    fn read(&mut self, _offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        Ok(read_keys(buffer))
+    }
+
+    fn write(&mut self, _offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+        console::_print(format_args!("{}", String::from_utf8_lossy(buffer)));
+        Ok(buffer.len())
+    }
+}
+
+pub struct Keyboard;
+
+impl Device for Keyboard {
+    fn read(&mut self, _offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        Ok(read_keys(buffer))
+    }
+
+    fn write(&mut self, _offset : usize, _buffer : &[u8]) -> Result<usize, &'static str> {
+        Err("Read Only Device")
+    }
+}
+
+/// A serial port, raw: no line ending translation in either direction.
+pub struct SerialPort(Serial);
+
+impl Device for SerialPort {
+    fn read(&mut self, _offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        let mut count = 0;
+        while count < buffer.len() {
+            match self.0.read() {
+                Some(byte) => { buffer[count] = byte; count += 1; },
+                None => break,
+            }
+        }
+        Ok(count)
+    }
+
+    fn write(&mut self, _offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+        for byte in buffer {
+            uart::send(self.0.port(), *byte);
+        }
+        Ok(buffer.len())
+    }
+}
+
+/// A whole ATA disk, read and written at any byte offset.
+pub struct AtaDisk {
+    bus   : u8,
+    drive : u8,
+    size  : usize,
+}
+
+impl AtaDisk {
+    pub fn new(bus : u8, drive : u8, size : usize) -> Self {
+        Self { bus, drive, size }
+    }
+}
+
+impl Device for AtaDisk {
+    fn read(&mut self, offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        let end = (offset + buffer.len()).min(self.size);
+        let mut position = offset;
+        let mut sector = [0; SECTOR_SIZE];
+        while position < end {
+            let start = position % SECTOR_SIZE;
+            let count = (SECTOR_SIZE - start).min(end - position);
+            ata::read_raw(self.bus, self.drive, (position / SECTOR_SIZE) as u32, &mut sector);
+            buffer[position - offset..position - offset + count].copy_from_slice(&sector[start..start + count]);
+            position += count;
+        }
+        Ok(end.saturating_sub(offset))
+    }
+
+    fn write(&mut self, offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+        if offset >= self.size && !buffer.is_empty() {
+            return Err("No Space Left On Device");
+        }
+        let end = (offset + buffer.len()).min(self.size);
+        let mut position = offset;
+        let mut sector = [0; SECTOR_SIZE];
+        while position < end {
+            let start = position % SECTOR_SIZE;
+            let count = (SECTOR_SIZE - start).min(end - position);
+            let block = (position / SECTOR_SIZE) as u32;
+            // Partial sectors keep the bytes around the written range.
+            if count < SECTOR_SIZE {
+                ata::read_raw(self.bus, self.drive, block, &mut sector);
+            }
+            sector[start..start + count].copy_from_slice(&buffer[position - offset..position - offset + count]);
+            ata::write_raw(self.bus, self.drive, block, &sector);
+            position += count;
+        }
+        Ok(end - offset)
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn is_block(&self) -> bool {
+        true
+    }
+}
+
+/// The filesystem at `/dev`, one node per registered device.
+pub struct DevFs;
+
+impl DevFs {
+    fn name_of(path : &str) -> Result<&str, &'static str> {
+        let name = path.trim_start_matches('/');
+        if name.contains('/') { Err("No Such File Or Directory") } else { Ok(name) }
+    }
+
+    fn with_node<T, F : FnOnce(&mut Node) -> Result<T, &'static str>>(path : &str, f : F) -> Result<T, &'static str> {
+        let name = Self::name_of(path)?;
+        without_interrupts(|| {
+            DEVICES.lock().get_mut(name).ok_or("No Such Device").and_then(f)
+        })
+    }
+}
+
+fn metadata(node : &Node) -> Metadata {
+    let block = node.device.is_block();
+    let mut meta = Metadata::new(if block { NodeKind::BlockDevice } else { NodeKind::CharDevice });
+    meta.size = node.device.size();
+    meta.mode = node.mode;
+    meta.uid = node.uid;
+    meta.gid = node.gid;
+    meta.device = (node.major, node.minor);
+    meta
+}
+
+impl FileSystem for DevFs {
+    fn name(&self) -> &'static str {
+        "devfs"
+    }
+
+    fn metadata(&self, path : &str) -> Result<Metadata, &'static str> {
+        if path == "/" {
+            return Ok(Metadata::new(NodeKind::Directory));
+        }
+        Self::with_node(path, |node| Ok(metadata(node)))
+    }
+
+    fn read_dir(&self, path : &str) -> Result<Vec<DirEntry>, &'static str> {
+        if path != "/" {
+            return Err("Not A Directory");
+        }
+        Ok(without_interrupts(|| {
+            DEVICES.lock().iter().map(|(name, node)| {
+                let meta = metadata(node);
+                DirEntry { name : name.clone(), kind : meta.kind, size : meta.size }
+            }).collect()
+        }))
+    }
+
+    fn read(&mut self, path : &str, offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        Self::with_node(path, |node| node.device.read(offset, buffer))
+    }
+
+    fn write(&mut self, path : &str, offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+        Self::with_node(path, |node| node.device.write(offset, buffer))
+    }
+
+    fn create(&mut self, _path : &str, _kind : NodeKind) -> Result<(), &'static str> {
+        Err("Operation Not Supported")
+    }
+
+    fn remove(&mut self, _path : &str) -> Result<(), &'static str> {
+        Err("Operation Not Supported")
+    }
+
+    // Devices can't be resized, but truncating is part of opening one for writing.
+    fn truncate(&mut self, path : &str, _size : usize) -> Result<(), &'static str> {
+        Self::with_node(path, |_| Ok(()))
+    }
+
+    fn set_mode(&mut self, path : &str, mode : u32) -> Result<(), &'static str> {
+        Self::with_node(path, |node| { node.mode = mode; Ok(()) })
+    }
+
+    fn set_owner(&mut self, path : &str, uid : Uid, gid : Gid) -> Result<(), &'static str> {
+        Self::with_node(path, |node| { node.uid = uid; node.gid = gid; Ok(()) })
+    }
+}
+
+/// Registers the built-in devices and mounts them at `/dev`. Major and
+/// minor numbers follow Linux, so archives with device nodes line up.
+pub fn init() -> InitResult<()> {
+    let _ = register("null", 1, 3, Box::new(Null));
+    let _ = register("zero", 1, 5, Box::new(Zero));
+    let _ = register("random", 1, 8, Box::new(Random::new()));
+    let _ = register("console", 5, 1, Box::new(Console));
+    let _ = register("keyboard", 13, 0, Box::new(Keyboard));
+
+    for (index, port) in COM_PORTS.iter().enumerate() {
+        if uart::is_present(*port) && !(cfg!(feature = "gdbstub") && *port == ComPort::Com2) {
+            let _ = register(&format!("ttyS{}", index), 4, 64 + index as u32, Box::new(SerialPort(Serial::new(*port))));
+        }
+    }
+
+    let disks = without_interrupts(|| {
+        ata::DISKS.lock().iter().map(|disk| (disk.bus, disk.drive, disk.size)).collect::<Vec<_>>()
+    });
+    for (bus, drive, size) in disks {
+        let index = bus * 2 + drive;
+        let name = format!("hd{}", (b'a' + index) as char);
+        let major = if bus == 0 { 3 } else { 22 };
+        let _ = register(&name, major, drive as u32 * 64, Box::new(AtaDisk::new(bus, drive, size)));
+    }
+
+    let _ = vfs::create_dir(MOUNT_POINT);
+    match vfs::mount(MOUNT_POINT, Box::new(DevFs)) {
+        Ok(()) => log!("{} Devices - ", without_interrupts(|| DEVICES.lock().len())),
+        Err(error) => log!("{} - ", error),
+    }
+    Ok(())
+}
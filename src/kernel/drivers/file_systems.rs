@@ -421,6 +421,48 @@ impl Block {
     pub fn from_disk_b(index : BlockIndex) -> Block {
         Block::load(0, 1, index)
     }
+
+    pub fn read_raw(bus : u8, drive : u8, index : BlockIndex) -> Block {
+        let mut block = Block::default();
+        ata::read_raw(bus, drive, index, &mut block.0);
+        block
+    }
+
+    pub fn write_raw(&self, bus : u8, drive : u8, index : BlockIndex) {
+        ata::write_raw(bus, drive, index, &self.0);
+    }
+
+    pub fn data(&self) -> &[u8; BLOCK_SIZE] {
+        &self.0
+    }
+
+    pub fn read_u16(&self, offset : usize) -> u16 {
+        u16::from_le_bytes([self[offset], self[offset + 1]])
+    }
+
+    pub fn read_u32(&self, offset : usize) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&self.0[offset..offset + 4]);
+        u32::from_le_bytes(bytes)
+    }
+
+    pub fn read_u64(&self, offset : usize) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&self.0[offset..offset + 8]);
+        u64::from_le_bytes(bytes)
+    }
+
+    pub fn write_u16(&mut self, offset : usize, value : u16) {
+        self.0[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, offset : usize, value : u32) {
+        self.0[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, offset : usize, value : u64) {
+        self.0[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
 }
 
 impl Default for Block {
@@ -468,7 +510,7 @@ pub fn open_file(name : &str) -> usize {
     let mut file_handle : usize = 0;
     without_interrupts(|| {
         file_handle = if let Some(file) = ROOT.lock().search(name) {
-            file.table_index() as usize
+            file.index() as usize
         } else {
             0
         }
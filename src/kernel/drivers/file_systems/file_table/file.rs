@@ -1,166 +1,232 @@
-use alloc::vec::Vec;
-
 use crate::kernel::drivers::file_systems::*;
-use crate::size_of;
+
+use super::FileType;
+
+pub const INDEXES_PER_NODE : usize = 126;
+
+/// Read/write/delete/execute bits, as described in the TinixFS reference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilePermissions(u8);
+
+impl FilePermissions {
+    const READABLE   : u8 = 1 << 0;
+    const WRITABLE   : u8 = 1 << 1;
+    const DELETABLE  : u8 = 1 << 2;
+    const EXECUTABLE : u8 = 1 << 3;
+
+    pub fn new() -> FilePermissions {
+        FilePermissions(Self::READABLE | Self::WRITABLE | Self::DELETABLE)
+    }
+
+    pub fn from_bits(bits : u8) -> FilePermissions {
+        FilePermissions(bits)
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+
+    fn set(&mut self, bit : u8, value : bool) {
+        if value { self.0 |= bit } else { self.0 &= !bit }
+    }
+
+    pub fn set_readable(&mut self, value : bool) { self.set(Self::READABLE, value) }
+    pub fn set_writable(&mut self, value : bool) { self.set(Self::WRITABLE, value) }
+    pub fn set_deletable(&mut self, value : bool) { self.set(Self::DELETABLE, value) }
+    pub fn set_executable(&mut self, value : bool) { self.set(Self::EXECUTABLE, value) }
+
+    pub fn is_readable(&self) -> bool { self.0 & Self::READABLE != 0 }
+    pub fn is_writable(&self) -> bool { self.0 & Self::WRITABLE != 0 }
+    pub fn is_deletable(&self) -> bool { self.0 & Self::DELETABLE != 0 }
+    pub fn is_executable(&self) -> bool { self.0 & Self::EXECUTABLE != 0 }
+}
+
+impl Default for FilePermissions {
+    fn default() -> Self {
+        FilePermissions::new()
+    }
+}
+
+// FileInfo block layout:
+// 0..4   => sector_num
+// 4..8   => index_node_lead, the first IndexNode holding the file's data blocks
+// 8..16  => size in bytes
+// 16     => file type
+// 17     => permissions
+// 18     => owner id
+// 19     => 1 for block devices
+// 20..24 => table, the first FileTable of a directory
+// 24..26 => device major
+// 26..28 => device minor
+// 28..30 => POSIX mode bits, 0 on disks written before modes were stored
+// 30     => group id
 #[repr(C, align(512))]
 #[derive(Debug, Clone, Copy)]
 pub struct FileInfo {
-    index_node_lead : BlockIndex
+    sector_num      : BlockIndex,
+    index_node_lead : BlockIndex,
+    size            : u64,
+    filetype        : u8,
+    permissions     : FilePermissions,
+    owner_id        : u8,
+    block_device    : bool,
+    table           : BlockIndex,
+    device          : (u16, u16),
+    mode            : u16,
+    group_id        : u8,
 }
 
 impl FileInfo {
-    pub fn new(_sectors : Vec<Sector>) -> Self {
-        let index_node_lead = 0;
+    pub fn new(sector_num : BlockIndex, filetype : FileType, permissions : FilePermissions, owner_id : u8) -> Self {
         Self {
-            index_node_lead,
+            sector_num,
+            index_node_lead : 0,
+            size : 0,
+            filetype : filetype as u8,
+            permissions,
+            owner_id,
+            block_device : false,
+            table : 0,
+            device : (0, 0),
+            mode : 0,
+            group_id : 0,
         }
     }
 
-    pub fn index_head(&self) -> IndexNode {
-        let head = IndexNode::from_index(self.index_node_lead);
-        head
+    pub fn from_block(block : &Block, sector_num : BlockIndex) -> Self {
+        Self {
+            sector_num,
+            index_node_lead : block.read_u32(4),
+            size : block.read_u64(8),
+            filetype : block[16],
+            permissions : FilePermissions::from_bits(block[17]),
+            owner_id : block[18],
+            block_device : block[19] != 0,
+            table : block.read_u32(20),
+            device : (block.read_u16(24), block.read_u16(26)),
+            mode : block.read_u16(28),
+            group_id : block[30],
+        }
     }
 
-    pub fn get_data(&self) -> Vec<u8> {
-        self.index_head().get_data()
+    pub fn to_block(&self) -> Block {
+        let mut block = Block::default();
+        block.write_u32(0, self.sector_num);
+        block.write_u32(4, self.index_node_lead);
+        block.write_u64(8, self.size);
+        block[16] = self.filetype;
+        block[17] = self.permissions.bits();
+        block[18] = self.owner_id;
+        block[19] = self.block_device as u8;
+        block.write_u32(20, self.table);
+        block.write_u16(24, self.device.0);
+        block.write_u16(26, self.device.1);
+        block.write_u16(28, self.mode);
+        block[30] = self.group_id;
+        block
+    }
+
+    pub fn sector_num(&self) -> BlockIndex { self.sector_num }
+    pub fn index_node_lead(&self) -> BlockIndex { self.index_node_lead }
+    pub fn size(&self) -> usize { self.size as usize }
+    pub fn permissions(&self) -> FilePermissions { self.permissions }
+    pub fn owner_id(&self) -> u8 { self.owner_id }
+    pub fn table(&self) -> BlockIndex { self.table }
+    pub fn device(&self) -> (u16, u16) { self.device }
+    pub fn is_block_device(&self) -> bool { self.block_device }
+    pub fn group_id(&self) -> u8 { self.group_id }
+
+    /// The POSIX mode, derived from the permission bits when none was stored.
+    pub fn mode(&self) -> u16 {
+        if self.mode != 0 {
+            return self.mode;
+        }
+        let permissions = self.permissions;
+        let directory = self.filetype == FileType::Directory as u8;
+        if permissions.is_readable() { 0o444 } else { 0 }
+            | if permissions.is_writable() { 0o200 } else { 0 }
+            | if permissions.is_executable() || directory { 0o111 } else { 0 }
     }
 
-    pub fn set_data(&mut self, data : Vec<u8>) {
-        let mut list = SectorList::new();
-        for (index, byte) in data.iter().enumerate() {
-            list.write_u8(index, *byte);
-        }
+    /// Sets the mode and keeps the read/write/execute permission bits in step with it.
+    pub fn set_mode(&mut self, mode : u16) {
+        self.mode = mode;
+        self.permissions.set_readable(mode & 0o444 != 0);
+        self.permissions.set_writable(mode & 0o222 != 0);
+        self.permissions.set_executable(mode & 0o111 != 0);
     }
 
-    pub fn get_mut_sectorlist(&mut self) -> SectorList {
-        let mut sectors = SectorList::new();
-        sectors
+    pub fn filetype(&self) -> FileType {
+        FileType::from_u8(self.filetype)
     }
 
-    pub fn set_sectors(&mut self, sectors : &SectorList) {
-        let mut head = self.index_head();
-        head.clear_list();
+    pub fn set_index_node_lead(&mut self, index : BlockIndex) { self.index_node_lead = index }
+    pub fn set_size(&mut self, size : usize) { self.size = size as u64 }
+    pub fn set_permissions(&mut self, permissions : FilePermissions) { self.permissions = permissions }
+    pub fn set_owner_id(&mut self, owner_id : u8) { self.owner_id = owner_id }
+    pub fn set_group_id(&mut self, group_id : u8) { self.group_id = group_id }
+    pub fn set_table(&mut self, table : BlockIndex) { self.table = table }
 
-        for sector in sectors.sectors() {
-            head.add_data_sector(sector);
-        }
+    pub fn set_device(&mut self, major : u16, minor : u16, block_device : bool) {
+        self.device = (major, minor);
+        self.block_device = block_device;
     }
 }
+
+// IndexNode block layout:
+// 0..4   => current
+// 4..8   => next, 0 if this is the last node
+// 8..512 => data block indexes, 0 for blocks that were never written
 #[repr(C, align(512))]
 #[derive(Debug, Clone, Copy)]
 pub struct IndexNode {
     current : BlockIndex,
     next : BlockIndex,
 
-    next_free : BlockIndex,
-    next_entry : BlockIndex,
-
-    data_blocks : [BlockIndex; 32],
+    data_blocks : [BlockIndex; INDEXES_PER_NODE],
 }
 
-
 impl IndexNode {
     pub fn create(index : BlockIndex) -> Self {
         Self {
             current : index,
             next : 0,
-            data_blocks : [0; 32],
-            next_free : (index + 1),
-            next_entry : 0,
-        }
-    }
-
-
-    /// Returns A Vector Of [Sector]s in the node (and all the following nodes) paired with the parent
-    /// [IndexNode].
-    pub fn sectors(&self) -> Vec<(IndexNode, Sector)> {
-        let mut list : Vec<(IndexNode, Sector)> = Vec::new();
-        for index in self.data_blocks {
-            list.push((*self, Sector::new(index)));
-        }
-        let next = self.get_next();
-        if next.is_some() {
-            let node = next.unwrap();
-            list.append(&mut node.sectors());
-        }
-
-        list
-    }
-
-    pub fn get_data(&self) -> Vec<u8> {
-        let mut v : Vec<u8> = Vec::new();
-        for (_, sector) in self.sectors() {
-            for byte in sector.data() {
-                v.push(byte);
-            }
-        }
-
-        if let Some(next) = self.get_next() {
-            v.append(&mut next.get_data());
-        }
-
-        v
-    }
-
-    pub fn add_data_sector(&mut self, sector : &Sector) {
-        if self.next_free > 33 {
-            self.add_index_node().add_data_sector(sector);
-        } else {
-            self.data_blocks[self.next_free as usize] = sector.index;
+            data_blocks : [0; INDEXES_PER_NODE],
         }
     }
 
-    pub fn add_block(&mut self, sector : &Block, index : BlockIndex) {
-        if self.next_free > 33 {
-            self.add_index_node().add_block(sector, index);
-        } else {
-            self.data_blocks[self.next_free as usize] = index;
+    pub fn from_block(block : &Block, current : BlockIndex) -> IndexNode {
+        let mut node = IndexNode::create(current);
+        node.next = block.read_u32(4);
+        for (slot, index) in node.data_blocks.iter_mut().enumerate() {
+            *index = block.read_u32(8 + slot * 4);
         }
+        node
     }
 
-    fn add_index_node(&mut self) -> IndexNode {
-        let disk = &get_disk(0, 1).expect("Couldn't Find Drive B...");
-        for sec_num in 0..get_disk_sector_count(0, 1) {
-            if is_range_empty(disk, (sec_num as u32)..(sec_num as u32 + 33 as u32)) {
-                self.next = sec_num as u32;
-                return self.get_next().unwrap();
-            }
+    pub fn to_block(&self) -> Block {
+        let mut block = Block::default();
+        block.write_u32(0, self.current);
+        block.write_u32(4, self.next);
+        for (slot, index) in self.data_blocks.iter().enumerate() {
+            block.write_u32(8 + slot * 4, *index);
         }
-        panic!("Unable To Allocate New Index Node, Disk Is Full.",)
+        block
     }
 
-    pub fn get_next(&self) -> Option<IndexNode> {
-        if self.next == 0 {return None} else {
-
-            let ptr = ConstPointer::from(&get_sector(&get_disk(0, 1).expect("Couldn't Find Drive B..."),self.next, ), size_of!(Sector));
-            Some(*ptr.cast::<IndexNode>())
-        }
-    }
+    pub fn current(&self) -> BlockIndex { self.current }
+    pub fn next(&self) -> BlockIndex { self.next }
+    pub fn set_next(&mut self, next : BlockIndex) { self.next = next }
 
-    pub fn as_const_ptr(&self) -> ConstPointer<IndexNode> {
-        ConstPointer::from(self, size_of!(IndexNode))
+    pub fn data_block(&self, slot : usize) -> BlockIndex {
+        self.data_blocks[slot]
     }
 
-    pub fn from_block(data : &Block) -> IndexNode {
-        let ptr = ConstPointer::from(data, size_of!(Block));
-        *ptr.cast::<IndexNode>()
+    pub fn set_data_block(&mut self, slot : usize, index : BlockIndex) {
+        self.data_blocks[slot] = index;
     }
 
-    pub fn from_index(index : BlockIndex) -> IndexNode {
-        let ptr = ConstPointer::from(&Block::load(0, 1, index), size_of!(Block));
-        *ptr.cast::<IndexNode>()
+    pub fn data_blocks(&self) -> &[BlockIndex; INDEXES_PER_NODE] {
+        &self.data_blocks
     }
-
-    pub fn clear_list(&mut self) {
-        self.data_blocks = [0; 32];
-        self.next_free = self.current + 1;
-        self.next_entry = 0;
-        if let Some(mut next) = self.get_next() {
-            next.clear_list();
-        }
-    }
-
-    
 }
-
-
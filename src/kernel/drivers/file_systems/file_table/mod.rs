@@ -1,10 +1,13 @@
-
 use core::ops::{Index, IndexMut};
 
-use alloc::boxed::Box;
+use alloc::format;
+
+use crate::kernel::{InitResult, vfs::{self, NodeKind}};
 
 use super::*;
 
+use self::file::{FileInfo, FilePermissions, IndexNode, INDEXES_PER_NODE};
+
 pub mod file;
 
 
@@ -13,6 +16,13 @@ pub const TABLE_SIZE : usize = 480;
 pub const FT_ENTRY_ALIGN : usize = 32;
 
 pub const ENTRIES_PER_TABLE : usize = TABLE_SIZE / FT_ENTRY_ALIGN;
+pub const TABLE_HEADER_SIZE : usize = BLOCK_SIZE - TABLE_SIZE;
+
+pub const MAGIC : [u8; 8] = *b"TINIXFS\0";
+pub const VERSION : u32 = 1;
+pub const BITS_PER_BLOCK : usize = BLOCK_SIZE * 8;
+
+pub type DiskID = (u8, u8);
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -21,15 +31,23 @@ pub enum FileType {
     File = 0x01,
     Directory = 0x02,
     Device = 0x03,
+    Pipe = 0x04,
 }
 
-pub const FILE_TYPE : [FileType; 4] = [
+pub const FILE_TYPE : [FileType; 5] = [
     FileType::Null,
     FileType::File,
     FileType::Directory,
-    FileType::Device
+    FileType::Device,
+    FileType::Pipe,
 ];
 
+impl FileType {
+    pub fn from_u8(value : u8) -> FileType {
+        FILE_TYPE.get(value as usize).copied().unwrap_or(FileType::Null)
+    }
+}
+
 impl Display for FileType {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
@@ -37,15 +55,21 @@ impl Display for FileType {
             FileType::File => write!(f, "File"),
             FileType::Directory => write!(f, "Dir"),
             FileType::Device => write!(f, "Device"),
+            FileType::Pipe => write!(f, "Pipe"),
         }
     }
 }
 
+// FileTable block layout:
+// 0..4    => sector_index
+// 4..8    => next_table, 0 if this is the last table in the chain
+// 32..512 => ENTRIES_PER_TABLE entries of FT_ENTRY_ALIGN bytes
 #[repr(C, align(512))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FileTable {
-    disk : (u8, u8),
+    disk : DiskID,
     sector_index : BlockIndex,
+    next_table : BlockIndex,
     entries : [FileTableEntry; ENTRIES_PER_TABLE]
 }
 
@@ -60,10 +84,25 @@ impl FileTable {
         FileTable {
             disk : (0,1),
             sector_index : sector,
+            next_table : 0,
             entries : [FileTableEntry::empty(); ENTRIES_PER_TABLE]
         }
     }
 
+    pub fn load(disk : DiskID, sector : BlockIndex) -> FileTable {
+        let block = Block::read_raw(disk.0, disk.1, sector);
+        let mut table = FileTable::new(sector);
+        table.disk = disk;
+        table.next_table = block.read_u32(4);
+        for (slot, entry) in table.entries.iter_mut().enumerate() {
+            let start = TABLE_HEADER_SIZE + slot * FT_ENTRY_ALIGN;
+            let mut raw = [0; FT_ENTRY_ALIGN];
+            raw.copy_from_slice(&block.data()[start..start + FT_ENTRY_ALIGN]);
+            *entry = raw.into();
+        }
+        table
+    }
+
     pub fn entries(&self) -> [FileTableEntry; ENTRIES_PER_TABLE] {
         self.entries
     }
@@ -72,77 +111,114 @@ impl FileTable {
         self.sector_index
     }
 
+    pub fn next_table(&self) -> BlockIndex {
+        self.next_table
+    }
+
+    pub fn set_next_table(&mut self, next : BlockIndex) {
+        self.next_table = next;
+    }
+
     pub unsafe fn raw_entries(&self) -> (*const FileTableEntry, usize) {
         (self.entries.as_ptr(), self.entries.len())
-    } 
+    }
 
+    /// Entries in this table only, not the ones chained after it.
     pub fn list(&self) -> impl Iterator<Item = &FileTableEntry> {
         self.entries.iter().filter(|entry| -> bool {
-            return entry.file_type != FileType::Null;
+            return entry.filetype() != FileType::Null;
         })
     }
 
+    /// Searches this table and every table chained after it.
     pub fn search(&self, name : &str) -> Option<FileTableEntry> {
+        self.locate(name).map(|(_, _, entry)| entry)
+    }
+
+    /// Like `search`, but also returns the table sector and slot the entry is in.
+    pub fn locate(&self, name : &str) -> Option<(BlockIndex, usize, FileTableEntry)> {
+        if name.len() > SMALL_STR_LEN {
+            return None;
+        }
         let cached_name : SmallString = SmallString::from_str(name);
-        for entry in self.list() {
-            if cached_name == entry.name() {
-                return Some(*entry);
+        let mut table = *self;
+        loop {
+            for (slot, entry) in table.entries.iter().enumerate() {
+                if entry.filetype() != FileType::Null && cached_name == entry.name() {
+                    return Some((table.sector_index, slot, *entry));
+                }
+            }
+            if table.next_table == 0 {
+                return None;
             }
+            table = FileTable::load(self.disk, table.next_table);
         }
-        None
     }
 
-    pub fn create(&mut self, name : &str) -> Option<FileTableEntry> {
-        let result = if let Some(mut entry) = self.get_first_mut_empty_ref() {
-            entry.1.set_filetype(FileType::File);
-            entry.1.set_filename(name);
-            entry.1.set_table_index(entry.0);
-            Some(entry.1)
-        } else {
-            panic!("Unable To File Empty File Slot, '{}'",name);
-        };
-        result
+    /// Puts `entry` in the first empty slot of this table, if there is one.
+    pub fn insert(&mut self, entry : FileTableEntry) -> Option<usize> {
+        let slot = self.entries.iter().position(|entry| entry.filetype() == FileType::Null)?;
+        self.entries[slot] = entry;
+        Some(slot)
     }
 
-    fn get_first_mut_empty_ref(&mut self) -> Option<(usize, FileTableEntry)> {
-        for (index, entry) in self.entries.iter().enumerate() {
-            if entry.filetype() == FileType::Null {
-                return Some((index, *entry));
-            };
-        }
-        None
+    pub fn is_empty(&self) -> bool {
+        self.list().next().is_none()
     }
 
-    pub fn update_on_disk(&self) {
-        let ptr = ConstPointer::from(self, size_of!(FileTable));
-        (*ptr.cast::<Block>()).save(self.disk.0, self.disk.1, self.sector_index)
+    pub fn to_block(&self) -> Block {
+        let mut block = Block::default();
+        block.write_u32(0, self.sector_index);
+        block.write_u32(4, self.next_table);
+        for (slot, entry) in self.entries.iter().enumerate() {
+            let raw : [u8; FT_ENTRY_ALIGN] = (*entry).into();
+            let start = TABLE_HEADER_SIZE + slot * FT_ENTRY_ALIGN;
+            for i in 0..FT_ENTRY_ALIGN {
+                block[start + i] = raw[i];
+            }
+        }
+        block
     }
 
-    pub fn load_root(disk : (u8, u8)) -> FileTable {
-        let ptr = ConstPointer::from(&Block::load(disk.0, disk.1, 0), size_of!(FileTable));
-        let mut table = *ptr.cast::<FileTable>();
-        table.set_disk(disk);
-        table
+    pub fn update_on_disk(&self) {
+        self.to_block().write_raw(self.disk.0, self.disk.1, self.sector_index)
+    }
+
+    /// Loads the root table of the TinixFS on `disk`, or an empty one if it isn't formatted.
+    pub fn load_root(disk : DiskID) -> FileTable {
+        match SuperBlock::load(disk) {
+            Some(superblock) => FileTable::load(disk, superblock.root_table),
+            None => {
+                let mut table = FileTable::new(0);
+                table.set_disk(disk);
+                table
+            },
+        }
     }
 
-    pub fn set_disk(&mut self, disk : (u8, u8)) {
+    pub fn set_disk(&mut self, disk : DiskID) {
         self.disk = disk;
     }
 
 }
 
+// FileTableEntry layout:
+// 0..4  => metanode_index, the FileInfo block
+// 4     => file type
+// 5     => permissions
+// 6     => owner id
+// 8..32 => name
 #[repr(C, align(32))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FileTableEntry {
-    file_type : FileType,
     metanode_index : BlockIndex,
-    bus : u8,
-    disk : u8,
-    table_index : usize,
+    file_type : u8,
+    permissions : u8,
+    owner_id : u8,
     name : SmallString,
 }
 
-impl FileTableEntry {   
+impl FileTableEntry {
 
     pub fn name(&self) -> SmallString {
         self.name
@@ -150,44 +226,46 @@ impl FileTableEntry {
 
     pub fn empty() -> Self {
         Self {
-            table_index : 0,
             metanode_index : 0,
-            bus : 0,
-            disk : 1,
-            file_type : FileType::Null,
+            file_type : FileType::Null as u8,
+            permissions : 0,
+            owner_id : 0,
             name : SmallString::from_str(""),
         }
     }
 
-    pub fn new_file<'a>(table_index : usize, name : &'a str, disk : &Disk, index : BlockIndex) -> Self {
+    pub fn new(name : &str, filetype : FileType, index : BlockIndex, permissions : FilePermissions, owner : u8) -> Self {
         Self {
             metanode_index : index,
-            table_index,
-            file_type : FileType::File,
-            bus : disk.bus,
-            disk : disk.drive,
+            file_type : filetype as u8,
+            permissions : permissions.bits(),
+            owner_id : owner,
             name : SmallString::from_str(name),
         }
     }
 
-    pub fn index(&self) -> BlockIndex {
-        self.metanode_index
+    pub fn new_file<'a>(name : &'a str, index : BlockIndex) -> Self {
+        FileTableEntry::new(name, FileType::File, index, FilePermissions::new(), 0)
     }
 
-    pub fn table_index(&self) -> usize {
-        self.table_index
+    pub fn clear(&mut self) {
+        *self = FileTableEntry::empty();
+    }
+
+    pub fn index(&self) -> BlockIndex {
+        self.metanode_index
     }
 
     pub fn is_file(&self) -> bool {
-        self.file_type == FileType::File
+        self.filetype() == FileType::File
     }
 
     pub fn is_device(&self) -> bool {
-        self.file_type == FileType::Device
+        self.filetype() == FileType::Device
     }
 
     pub fn is_dir(&self) -> bool {
-        self.file_type == FileType::Directory
+        self.filetype() == FileType::Directory
     }
 
 
@@ -196,74 +274,60 @@ impl FileTableEntry {
     }
 
     pub fn set_filetype(&mut self, filetype : FileType) {
-        self.file_type = filetype;
+        self.file_type = filetype as u8;
     }
 
     pub fn set_filename(&mut self, name : &str) {
         self.name = SmallString::from_str(name);
     }
 
-    pub fn set_table_index(&mut self, index : usize) {
-        self.table_index = index;
+    pub fn set_permissions(&mut self, permissions : FilePermissions) {
+        self.permissions = permissions.bits();
     }
 
-
-
     pub fn filetype(&self) -> FileType {
-        self.file_type
+        FileType::from_u8(self.file_type)
     }
 
-    pub fn disk_id(&self) -> u8 {
-        self.disk
+    pub fn permissions(&self) -> FilePermissions {
+        FilePermissions::from_bits(self.permissions)
     }
 
-    pub fn bus_id(&self) -> u8 {
-        self.bus
+    pub fn owner_id(&self) -> u8 {
+        self.owner_id
     }
 
-    pub fn get_fileinfo(&self) -> FileInfo {
-        let mut ptr = ConstPointer::from(&Block::from_disk_b(self.metanode_index), size_of!(Block));
-        *ptr.cast::<FileInfo>()
+    pub fn set_owner_id(&mut self, owner_id : u8) {
+        self.owner_id = owner_id;
     }
 
-    
-
-}
-
-impl Into<FileTable> for FileTableEntry {
-    fn into(self) -> FileTable {
-        let disk = get_disk(self.bus,self.disk).unwrap();
-        let sector = get_sector(&disk, self.metanode_index);
-        sector.into()
-    }
-}
-
-impl<> Into<FileTable> for Sector {
-    fn into(self) -> FileTable {
-       FileTable::new(0)
-    }
-}
-
-impl Into<Sector> for FileTable {
-    fn into(self) -> Sector {
-        let sector = Sector::new(self.sector_index());
-        
-        sector
+    pub fn file_info(&self, disk : DiskID) -> FileInfo {
+        FileInfo::from_block(&Block::read_raw(disk.0, disk.1, self.metanode_index), self.metanode_index)
     }
 }
 
 impl Into<FileTableEntry> for [u8; FT_ENTRY_ALIGN] {
     fn into(self) -> FileTableEntry {
-        let ptr = ConstPointer::from(&self, FT_ENTRY_ALIGN);
-        *ptr.cast::<FileTableEntry>()
+        let mut name = [0; SMALL_STR_LEN];
+        name.copy_from_slice(&self[8..8 + SMALL_STR_LEN]);
+        FileTableEntry {
+            metanode_index : u32::from_le_bytes([self[0], self[1], self[2], self[3]]),
+            file_type : self[4],
+            permissions : self[5],
+            owner_id : self[6],
+            name : SmallString { data : name },
+        }
     }
 }
 
 impl Into<[u8; FT_ENTRY_ALIGN]> for FileTableEntry {
     fn into(self) -> [u8; FT_ENTRY_ALIGN] {
-        let ptr = ConstPointer::from(&self, FT_ENTRY_ALIGN);
         let mut buffer = [0; FT_ENTRY_ALIGN];
-        ptr.copy_bytes(&mut buffer);
+        buffer[0..4].copy_from_slice(&self.metanode_index.to_le_bytes());
+        buffer[4] = self.file_type;
+        buffer[5] = self.permissions;
+        buffer[6] = self.owner_id;
+        buffer[8..8 + SMALL_STR_LEN].copy_from_slice(&self.name.data);
         buffer
     }
 }
@@ -281,7 +345,7 @@ impl IndexMut<usize> for FileTable {
     }
 }
 
-const SMALL_STR_LEN : usize = 8;
+pub const SMALL_STR_LEN : usize = 24;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct SmallString {
     data : [u8; SMALL_STR_LEN]
@@ -315,4 +379,604 @@ impl Display for SmallString {
         self.to_string(&mut s);
         write!(f, "{}", s)
     }
-}
\ No newline at end of file
+}
+
+// SuperBlock layout, always block 0:
+// 0..8   => MAGIC
+// 8..12  => VERSION
+// 12..16 => block_count
+// 16..20 => bitmap_start, the first block of the allocation bitmap
+// 20..24 => bitmap_blocks
+// 24..28 => root_table
+#[derive(Debug, Clone, Copy)]
+pub struct SuperBlock {
+    pub block_count   : BlockIndex,
+    pub bitmap_start  : BlockIndex,
+    pub bitmap_blocks : BlockIndex,
+    pub root_table    : BlockIndex,
+}
+
+impl SuperBlock {
+    pub fn load(disk : DiskID) -> Option<SuperBlock> {
+        let block = Block::read_raw(disk.0, disk.1, 0);
+        if block.data()[0..8] != MAGIC || block.read_u32(8) != VERSION {
+            return None;
+        }
+        Some(SuperBlock {
+            block_count : block.read_u32(12),
+            bitmap_start : block.read_u32(16),
+            bitmap_blocks : block.read_u32(20),
+            root_table : block.read_u32(24),
+        })
+    }
+
+    pub fn save(&self, disk : DiskID) {
+        let mut block = Block::default();
+        block.set_data(&MAGIC);
+        block.write_u32(8, VERSION);
+        block.write_u32(12, self.block_count);
+        block.write_u32(16, self.bitmap_start);
+        block.write_u32(20, self.bitmap_blocks);
+        block.write_u32(24, self.root_table);
+        block.write_raw(disk.0, disk.1, 0);
+    }
+}
+
+/// Where a file's entry lives: the table sector and slot within it.
+/// The root directory has no entry.
+pub type Location = Option<(BlockIndex, usize)>;
+
+/// A TinixFS formatted disk: a superblock, an allocation bitmap, and a tree of
+/// chained `FileTable`s whose entries point at `FileInfo` blocks.
+#[derive(Debug, Clone)]
+pub struct TinixFs {
+    disk       : DiskID,
+    superblock : SuperBlock,
+    next_free  : BlockIndex, // Where the allocator starts looking.
+}
+
+/// Formats `disk` with an empty TinixFS.
+pub fn mkfs(disk : DiskID) -> Result<TinixFs, &'static str> {
+    let block_count = get_disk_sector_count(disk.0, disk.1);
+    if block_count < 16 {
+        return Err("Disk Too Small");
+    }
+    let bitmap_blocks = (block_count + BITS_PER_BLOCK - 1) / BITS_PER_BLOCK;
+    let superblock = SuperBlock {
+        block_count : block_count as BlockIndex,
+        bitmap_start : 1,
+        bitmap_blocks : bitmap_blocks as BlockIndex,
+        root_table : 1 + bitmap_blocks as BlockIndex,
+    };
+
+    for index in 0..bitmap_blocks {
+        Block::default().write_raw(disk.0, disk.1, superblock.bitmap_start + index as BlockIndex);
+    }
+    let mut root = FileTable::new(superblock.root_table);
+    root.set_disk(disk);
+    root.update_on_disk();
+    superblock.save(disk);
+
+    let mut fs = TinixFs { disk, superblock, next_free : 0 };
+    for index in 0..=superblock.root_table {
+        fs.set_allocated(index, true);
+    }
+    Ok(fs)
+}
+
+impl TinixFs {
+    pub fn mount(disk : DiskID) -> Result<TinixFs, &'static str> {
+        let superblock = SuperBlock::load(disk).ok_or("Not A TinixFS Disk")?;
+        Ok(TinixFs { disk, superblock, next_free : superblock.root_table + 1 })
+    }
+
+    pub fn disk(&self) -> DiskID {
+        self.disk
+    }
+
+    pub fn root(&self) -> FileTable {
+        FileTable::load(self.disk, self.superblock.root_table)
+    }
+
+    fn read_block(&self, index : BlockIndex) -> Block {
+        Block::read_raw(self.disk.0, self.disk.1, index)
+    }
+
+    fn write_block(&self, index : BlockIndex, block : &Block) {
+        block.write_raw(self.disk.0, self.disk.1, index)
+    }
+
+    fn bitmap_position(&self, index : BlockIndex) -> (BlockIndex, usize) {
+        let bit = index as usize;
+        (self.superblock.bitmap_start + (bit / BITS_PER_BLOCK) as BlockIndex, bit % BITS_PER_BLOCK)
+    }
+
+    fn is_allocated(&self, index : BlockIndex) -> bool {
+        let (block, bit) = self.bitmap_position(index);
+        self.read_block(block)[bit / 8] & (1 << (bit % 8)) != 0
+    }
+
+    fn set_allocated(&mut self, index : BlockIndex, value : bool) {
+        let (block_index, bit) = self.bitmap_position(index);
+        let mut block = self.read_block(block_index);
+        if value {
+            block[bit / 8] |= 1 << (bit % 8);
+        } else {
+            block[bit / 8] &= !(1 << (bit % 8));
+        }
+        self.write_block(block_index, &block);
+    }
+
+    /// Returns a zeroed block that's now marked as in use.
+    fn alloc_block(&mut self) -> Result<BlockIndex, &'static str> {
+        let count = self.superblock.block_count;
+        let first = self.superblock.root_table + 1;
+        let start = self.next_free.max(first);
+        for offset in 0..count - first {
+            let index = first + (start - first + offset) % (count - first);
+            if !self.is_allocated(index) {
+                self.set_allocated(index, true);
+                self.write_block(index, &Block::default());
+                self.next_free = index + 1;
+                return Ok(index);
+            }
+        }
+        Err("Disk Is Full")
+    }
+
+    fn free_block(&mut self, index : BlockIndex) {
+        if index > self.superblock.root_table {
+            self.set_allocated(index, false);
+        }
+    }
+
+    /// Number of unallocated blocks, counted one bitmap block at a time.
+    pub fn free_blocks(&self) -> usize {
+        let count = self.superblock.block_count as usize;
+        let mut used = 0;
+        for first in (0..count).step_by(BITS_PER_BLOCK) {
+            let bitmap = self.read_block(self.bitmap_position(first as BlockIndex).0);
+            let bits = BITS_PER_BLOCK.min(count - first);
+            for byte in 0..(bits + 7) / 8 {
+                let mut value = bitmap[byte];
+                // Bits past the last block are padding.
+                if bits - byte * 8 < 8 {
+                    value &= (1 << (bits - byte * 8)) - 1;
+                }
+                used += value.count_ones() as usize;
+            }
+        }
+        count - used
+    }
+
+    fn read_info(&self, index : BlockIndex) -> FileInfo {
+        FileInfo::from_block(&self.read_block(index), index)
+    }
+
+    fn write_info(&self, info : &FileInfo) {
+        self.write_block(info.sector_num(), &info.to_block())
+    }
+
+    fn read_index_node(&self, index : BlockIndex) -> IndexNode {
+        IndexNode::from_block(&self.read_block(index), index)
+    }
+
+    fn write_index_node(&self, node : &IndexNode) {
+        self.write_block(node.current(), &node.to_block())
+    }
+
+    fn root_info(&self) -> FileInfo {
+        let mut info = FileInfo::new(0, FileType::Directory, FilePermissions::new(), 0);
+        info.set_table(self.superblock.root_table);
+        info
+    }
+
+    /// Walks `path` from the root directory.
+    pub fn lookup(&self, path : &str) -> Result<(FileInfo, Location), &'static str> {
+        let mut info = self.root_info();
+        let mut location = None;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            if info.filetype() != FileType::Directory {
+                return Err("Not A Directory");
+            }
+            let (table, slot, entry) = FileTable::load(self.disk, info.table())
+                .locate(name)
+                .ok_or("No Such File Or Directory")?;
+            info = self.read_info(entry.index());
+            location = Some((table, slot));
+        }
+        Ok((info, location))
+    }
+
+    /// Adds `entry` to the directory starting at `head`, chaining a new table if they're all full.
+    fn insert_entry(&mut self, head : BlockIndex, entry : FileTableEntry) -> Result<(), &'static str> {
+        let mut table = FileTable::load(self.disk, head);
+        loop {
+            if table.insert(entry).is_some() {
+                table.update_on_disk();
+                return Ok(());
+            }
+            if table.next_table() == 0 {
+                let next = self.alloc_block()?;
+                let mut chained = FileTable::new(next);
+                chained.set_disk(self.disk);
+                chained.update_on_disk();
+                table.set_next_table(next);
+                table.update_on_disk();
+            }
+            table = FileTable::load(self.disk, table.next_table());
+        }
+    }
+
+    pub fn create(&mut self, path : &str, filetype : FileType) -> Result<FileInfo, &'static str> {
+        let path = vfs::normalize(path);
+        let name = vfs::filename(&path);
+        if name.is_empty() {
+            return Err("File Exists");
+        }
+        if name.len() > SMALL_STR_LEN {
+            return Err("File Name Too Long");
+        }
+        if filetype == FileType::Null {
+            return Err("Invalid File Type");
+        }
+        let (parent, _) = self.lookup(vfs::dirname(&path))?;
+        if parent.filetype() != FileType::Directory {
+            return Err("Not A Directory");
+        }
+        if FileTable::load(self.disk, parent.table()).search(name).is_some() {
+            return Err("File Exists");
+        }
+
+        let mut info = FileInfo::new(self.alloc_block()?, filetype, FilePermissions::new(), 0);
+        if filetype == FileType::Directory {
+            let table = self.alloc_block()?;
+            let mut dir = FileTable::new(table);
+            dir.set_disk(self.disk);
+            dir.update_on_disk();
+            info.set_table(table);
+        }
+        self.write_info(&info);
+
+        let entry = FileTableEntry::new(name, filetype, info.sector_num(), info.permissions(), info.owner_id());
+        self.insert_entry(parent.table(), entry)?;
+        Ok(info)
+    }
+
+    /// Finds the block holding byte `logical * BLOCK_SIZE` of the file, allocating it if asked to.
+    fn data_block(&mut self, info : &mut FileInfo, logical : usize, allocate : bool) -> Result<Option<BlockIndex>, &'static str> {
+        if info.index_node_lead() == 0 {
+            if !allocate {
+                return Ok(None);
+            }
+            let lead = self.alloc_block()?;
+            self.write_index_node(&IndexNode::create(lead));
+            info.set_index_node_lead(lead);
+            self.write_info(info);
+        }
+
+        let mut node = self.read_index_node(info.index_node_lead());
+        for _ in 0..logical / INDEXES_PER_NODE {
+            if node.next() == 0 {
+                if !allocate {
+                    return Ok(None);
+                }
+                let next = self.alloc_block()?;
+                self.write_index_node(&IndexNode::create(next));
+                node.set_next(next);
+                self.write_index_node(&node);
+            }
+            node = self.read_index_node(node.next());
+        }
+
+        let slot = logical % INDEXES_PER_NODE;
+        if node.data_block(slot) == 0 {
+            if !allocate {
+                return Ok(None);
+            }
+            let block = self.alloc_block()?;
+            node.set_data_block(slot, block);
+            self.write_index_node(&node);
+        }
+        Ok(Some(node.data_block(slot)))
+    }
+
+    pub fn read(&mut self, info : &FileInfo, offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        let mut info = *info;
+        if offset >= info.size() {
+            return Ok(0);
+        }
+        let count = buffer.len().min(info.size() - offset);
+        let mut done = 0;
+        while done < count {
+            let position = offset + done;
+            let (logical, start) = (position / BLOCK_SIZE, position % BLOCK_SIZE);
+            let length = (BLOCK_SIZE - start).min(count - done);
+            match self.data_block(&mut info, logical, false)? {
+                Some(index) => buffer[done..done + length].copy_from_slice(&self.read_block(index).data()[start..start + length]),
+                None => buffer[done..done + length].fill(0),
+            }
+            done += length;
+        }
+        Ok(count)
+    }
+
+    pub fn write(&mut self, info : &mut FileInfo, offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+        let mut done = 0;
+        while done < buffer.len() {
+            let position = offset + done;
+            let (logical, start) = (position / BLOCK_SIZE, position % BLOCK_SIZE);
+            let length = (BLOCK_SIZE - start).min(buffer.len() - done);
+            let index = self.data_block(info, logical, true)?.ok_or("Disk Is Full")?;
+            let mut block = if length == BLOCK_SIZE { Block::default() } else { self.read_block(index) };
+            for i in 0..length {
+                block[start + i] = buffer[done + i];
+            }
+            self.write_block(index, &block);
+            done += length;
+        }
+        if offset + done > info.size() {
+            info.set_size(offset + done);
+            self.write_info(info);
+        }
+        Ok(done)
+    }
+
+    /// Frees every data block from `keep` onwards, and any index nodes left empty.
+    fn release(&mut self, info : &mut FileInfo, keep : usize) {
+        let mut previous : Option<IndexNode> = None;
+        let mut current = info.index_node_lead();
+        let mut base = 0;
+        while current != 0 {
+            let mut node = self.read_index_node(current);
+            let next = node.next();
+            if base >= keep {
+                for block in node.data_blocks().iter().filter(|block| **block != 0) {
+                    self.free_block(*block);
+                }
+                self.free_block(current);
+                match previous.as_mut() {
+                    Some(previous) if previous.next() != 0 => {
+                        previous.set_next(0);
+                        self.write_index_node(previous);
+                    },
+                    Some(_) => {},
+                    None => info.set_index_node_lead(0),
+                }
+            } else {
+                for slot in keep.saturating_sub(base).min(INDEXES_PER_NODE)..INDEXES_PER_NODE {
+                    if node.data_block(slot) != 0 {
+                        self.free_block(node.data_block(slot));
+                        node.set_data_block(slot, 0);
+                    }
+                }
+                self.write_index_node(&node);
+                previous = Some(node);
+            }
+            base += INDEXES_PER_NODE;
+            current = next;
+        }
+    }
+
+    pub fn truncate(&mut self, info : &mut FileInfo, size : usize) -> Result<(), &'static str> {
+        if size < info.size() {
+            self.release(info, (size + BLOCK_SIZE - 1) / BLOCK_SIZE);
+            // Zero the rest of the last block, so growing the file again reads zeros.
+            if size % BLOCK_SIZE != 0 {
+                if let Some(index) = self.data_block(info, size / BLOCK_SIZE, false)? {
+                    let mut block = self.read_block(index);
+                    for i in size % BLOCK_SIZE..BLOCK_SIZE {
+                        block[i] = 0;
+                    }
+                    self.write_block(index, &block);
+                }
+            }
+        }
+        info.set_size(size);
+        self.write_info(info);
+        Ok(())
+    }
+
+    pub fn delete(&mut self, path : &str) -> Result<(), &'static str> {
+        let (mut info, location) = self.lookup(path)?;
+        let (table_index, slot) = location.ok_or("Can't Delete The Root Directory")?;
+        if !info.permissions().is_deletable() {
+            return Err("Permission Denied");
+        }
+
+        if info.filetype() == FileType::Directory {
+            let mut table = info.table();
+            while table != 0 {
+                let dir = FileTable::load(self.disk, table);
+                if !dir.is_empty() {
+                    return Err("Directory Not Empty");
+                }
+                table = dir.next_table();
+            }
+            let mut table = info.table();
+            while table != 0 {
+                let next = FileTable::load(self.disk, table).next_table();
+                self.free_block(table);
+                table = next;
+            }
+        }
+        self.release(&mut info, 0);
+        self.free_block(info.sector_num());
+
+        let mut table = FileTable::load(self.disk, table_index);
+        table[slot].clear();
+        table.update_on_disk();
+        Ok(())
+    }
+
+    pub fn read_dir(&self, path : &str) -> Result<Vec<(String, FileInfo)>, &'static str> {
+        let (info, _) = self.lookup(path)?;
+        if info.filetype() != FileType::Directory {
+            return Err("Not A Directory");
+        }
+        let mut list = Vec::new();
+        let mut table = info.table();
+        while table != 0 {
+            let dir = FileTable::load(self.disk, table);
+            for entry in dir.list() {
+                list.push((format!("{}", entry.name()), self.read_info(entry.index())));
+            }
+            table = dir.next_table();
+        }
+        Ok(list)
+    }
+
+    /// Updates the permissions in both the `FileInfo` and the directory entry.
+    pub fn set_permissions(&mut self, path : &str, permissions : FilePermissions) -> Result<(), &'static str> {
+        let (mut info, location) = self.lookup(path)?;
+        info.set_permissions(permissions);
+        self.write_info(&info);
+        if let Some((table_index, slot)) = location {
+            let mut table = FileTable::load(self.disk, table_index);
+            table[slot].set_permissions(permissions);
+            table.update_on_disk();
+        }
+        Ok(())
+    }
+
+    /// Sets the POSIX mode, keeping the permissions in the directory entry in step.
+    pub fn set_mode(&mut self, path : &str, mode : u16) -> Result<(), &'static str> {
+        let (mut info, location) = self.lookup(path)?;
+        info.set_mode(mode);
+        self.write_info(&info);
+        if let Some((table_index, slot)) = location {
+            let mut table = FileTable::load(self.disk, table_index);
+            table[slot].set_permissions(info.permissions());
+            table.update_on_disk();
+        }
+        Ok(())
+    }
+
+    /// Owners are stored in a byte, so ids above 255 can't be represented.
+    pub fn set_owner(&mut self, path : &str, uid : u32, gid : u32) -> Result<(), &'static str> {
+        if uid > u8::MAX as u32 || gid > u8::MAX as u32 {
+            return Err("Id Out Of Range");
+        }
+        let (mut info, location) = self.lookup(path)?;
+        info.set_owner_id(uid as u8);
+        info.set_group_id(gid as u8);
+        self.write_info(&info);
+        if let Some((table_index, slot)) = location {
+            let mut table = FileTable::load(self.disk, table_index);
+            table[slot].set_owner_id(uid as u8);
+            table.update_on_disk();
+        }
+        Ok(())
+    }
+
+    pub fn make_device(&mut self, path : &str, major : u16, minor : u16, block_device : bool) -> Result<(), &'static str> {
+        let mut info = self.create(path, FileType::Device)?;
+        info.set_device(major, minor, block_device);
+        self.write_info(&info);
+        Ok(())
+    }
+}
+
+fn node_kind(info : &FileInfo) -> NodeKind {
+    match info.filetype() {
+        FileType::Directory => NodeKind::Directory,
+        FileType::Device if info.is_block_device() => NodeKind::BlockDevice,
+        FileType::Device => NodeKind::CharDevice,
+        FileType::Pipe => NodeKind::Fifo,
+        FileType::File | FileType::Null => NodeKind::File,
+    }
+}
+
+impl vfs::FileSystem for TinixFs {
+    fn name(&self) -> &'static str {
+        "tinixfs"
+    }
+
+    fn metadata(&self, path : &str) -> Result<vfs::Metadata, &'static str> {
+        let (info, _) = self.lookup(path)?;
+        let mut meta = vfs::Metadata::new(node_kind(&info));
+        meta.inode = info.sector_num() as u64;
+        meta.size = info.size();
+        meta.uid = info.owner_id() as u32;
+        meta.gid = info.group_id() as u32;
+        meta.mode = info.mode() as u32;
+        meta.device = (info.device().0 as u32, info.device().1 as u32);
+        Ok(meta)
+    }
+
+    fn read_dir(&self, path : &str) -> Result<Vec<vfs::DirEntry>, &'static str> {
+        Ok(TinixFs::read_dir(self, path)?.into_iter().map(|(name, info)| vfs::DirEntry {
+            name,
+            kind : node_kind(&info),
+            size : info.size(),
+        }).collect())
+    }
+
+    fn read(&mut self, path : &str, offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        let (info, _) = self.lookup(path)?;
+        match info.filetype() {
+            FileType::Directory => Err("Is A Directory"),
+            _ => TinixFs::read(self, &info, offset, buffer),
+        }
+    }
+
+    fn write(&mut self, path : &str, offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+        let (mut info, _) = self.lookup(path)?;
+        match info.filetype() {
+            FileType::Directory => Err("Is A Directory"),
+            _ => TinixFs::write(self, &mut info, offset, buffer),
+        }
+    }
+
+    fn create(&mut self, path : &str, kind : NodeKind) -> Result<(), &'static str> {
+        match kind {
+            NodeKind::File => TinixFs::create(self, path, FileType::File).map(|_| ()),
+            NodeKind::Directory => TinixFs::create(self, path, FileType::Directory).map(|_| ()),
+            NodeKind::Fifo => TinixFs::create(self, path, FileType::Pipe).map(|_| ()),
+            NodeKind::CharDevice => self.make_device(path, 0, 0, false),
+            NodeKind::BlockDevice => self.make_device(path, 0, 0, true),
+            NodeKind::Symlink => Err("Operation Not Supported"),
+        }
+    }
+
+    fn remove(&mut self, path : &str) -> Result<(), &'static str> {
+        self.delete(path)
+    }
+
+    fn truncate(&mut self, path : &str, size : usize) -> Result<(), &'static str> {
+        let (mut info, _) = self.lookup(path)?;
+        if info.filetype() == FileType::Directory {
+            return Err("Is A Directory");
+        }
+        TinixFs::truncate(self, &mut info, size)
+    }
+
+    fn set_mode(&mut self, path : &str, mode : u32) -> Result<(), &'static str> {
+        TinixFs::set_mode(self, path, mode as u16)
+    }
+
+    fn set_owner(&mut self, path : &str, uid : u32, gid : u32) -> Result<(), &'static str> {
+        TinixFs::set_owner(self, path, uid, gid)
+    }
+}
+
+/// Mounts every TinixFS disk at `/mnt/ata<bus><drive>`.
+pub fn init() -> InitResult<()> {
+    let _ = vfs::create_dir("/mnt");
+    for bus in 0..2 {
+        for drive in 0..2 {
+            if get_disk_sector_count(bus, drive) == 0 {
+                continue;
+            }
+            if let Ok(fs) = TinixFs::mount((bus, drive)) {
+                let path = format!("/mnt/ata{}{}", bus, drive);
+                let _ = vfs::create_dir(&path);
+                match vfs::mount(&path, alloc::boxed::Box::new(fs)) {
+                    Ok(()) => crate::log!("{} - ", path),
+                    Err(error) => crate::log!("{}: {} - ", path, error),
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,18 +1,25 @@
 use core::{fmt::{Display, Write}, mem::size_of, usize};
 
-use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use alloc::{collections::{BTreeMap, VecDeque}, string::String, vec::Vec};
 
-use crate::{io::{IoReader, IoWriter}, kernel::{self, InitResult}, log};
+use crate::{io::{IoReader, IoWriter}, kernel::{self, InitResult, hardware::ata}, log};
 use super::*;
 
 use super::File;
 use lazy_static::lazy_static;
 use spin::{Mutex, MutexGuard};
+use x86_64::instructions::interrupts::without_interrupts;
+
+pub const TAR_BLOCK_SIZE : usize = 512;
+pub const MAX_SYMLINK_DEPTH : usize = 8;
+
+const GNU_LONG_NAME : u8 = b'L';
+const GNU_LONG_LINK : u8 = b'K';
+const PAX_HEADER    : u8 = b'x';
+const LONG_LINK_NAME : &str = "././@LongLink";
+
 lazy_static! {
-    static ref ACTIVE_PARTITION : Mutex<SectorList> = Mutex::new(load_fs(1, 1, 1));
-    static ref FILE_TABLE : Mutex<FileTable> = Mutex::new(FileTable::from_sector(
-        load_fs(1, 1, 1).get_sector(0).unwrap()
-    ));
+    static ref ARCHIVE : Mutex<Option<Archive<DiskStorage>>> = Mutex::new(None);
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -79,38 +86,70 @@ impl MetaNode {
     }
 
     pub fn new(file : &TarFile) -> Self {
-        Self {
-            name : str_to_slice!(file.name, 100),
-            checksum : bin_to_oct!(0,8),
-            device_major : bin_to_oct!(0, 8),
-            device_minor : bin_to_oct!(0, 8),
-            file_size : bin_to_oct!(file.data.len(), 12),
-            file_type : [b'0'; 1],
-            group_id : bin_to_oct!(0, 8),
-            group_name : bin_to_oct!(0, 32),
-            linked_name : str_to_slice!(file.name, 100),
-            magic_id : str_to_slice!("USTAR\0", 6),
-            magic_version : str_to_slice!("00", 2), 
-            mode : bin_to_oct!(0, 8),
-            modif_time: bin_to_oct!(0, 12),
-            name_prefix : str_to_slice!("", 155),
-            owner_id : bin_to_oct!(0, 8),
-            owner_name : str_to_slice!("UNK", 32),
-            padding : [0; 11],
-            usable : [1 ; 1],
-        }
+        let entry = Entry::new(&file.name, EntryKind::File, file.data.len());
+        MetaNode::from_entry(&file.name, "", &entry)
+    }
+
+    /// Builds a POSIX ustar header for `entry`, stored as `prefix` + `/` + `name`.
+    pub fn from_entry(name : &str, prefix : &str, entry : &Entry) -> Self {
+        let mut node = Self::from_sector([0; 512]);
+        node.name = str_to_slice!(name, 100);
+        node.name_prefix = str_to_slice!(prefix, 155);
+        node.mode = octal_field(entry.mode as u64 & 0o7777);
+        node.owner_id = octal_field(entry.uid as u64);
+        node.group_id = octal_field(entry.gid as u64);
+        node.file_size = octal_field(if entry.kind.has_data() { entry.size as u64 } else { 0 });
+        node.modif_time = octal_field(entry.mtime);
+        node.file_type = [entry.kind.flag()];
+        node.linked_name = str_to_slice!(truncate(&entry.link, 100), 100);
+        node.magic_id = *b"ustar\0";
+        node.magic_version = *b"00";
+        node.owner_name = str_to_slice!(truncate(&entry.owner, 31), 32);
+        node.group_name = str_to_slice!(truncate(&entry.group, 31), 32);
+        node.device_major = octal_field(entry.device.0 as u64);
+        node.device_minor = octal_field(entry.device.1 as u64);
+        node
     }
 
     pub fn name(&self) -> String {
-        str_from_slice!(self.name)
+        field_str(&self.name)
+    }
+
+    /// The full member name, including the ustar prefix field when there is one.
+    pub fn path(&self) -> String {
+        let prefix = field_str(&self.name_prefix);
+        if self.is_posix() && !prefix.is_empty() {
+            let mut path = prefix;
+            path.push('/');
+            path.push_str(&self.name());
+            path
+        } else {
+            self.name()
+        }
+    }
+
+    pub fn link_name(&self) -> String {
+        field_str(&self.linked_name)
     }
 
     pub fn owner(&self) -> (String, usize) {
-        (str_from_slice!(self.owner_name), slice_to_bin!(self.owner_id))
+        (field_str(&self.owner_name), slice_to_bin!(self.owner_id))
     }
 
     pub fn group(&self) -> (String, usize) {
-        (str_from_slice!(self.group_name), slice_to_bin!(self.group_id))
+        (field_str(&self.group_name), slice_to_bin!(self.group_id))
+    }
+
+    pub fn mode(&self) -> u32 {
+        slice_to_bin!(self.mode) as u32
+    }
+
+    pub fn modified(&self) -> u64 {
+        slice_to_bin!(self.modif_time) as u64
+    }
+
+    pub fn device(&self) -> (u32, u32) {
+        (slice_to_bin!(self.device_major) as u32, slice_to_bin!(self.device_minor) as u32)
     }
 
     pub fn file_size(&self) -> usize {
@@ -118,47 +157,73 @@ impl MetaNode {
     }
 
     pub fn file_sector_length(&self) -> usize {
-        let mut sectors = self.file_size() / 512;
-        if self.file_size() % 512 > 0 {
-            sectors += 1;
-        }
-        sectors
+        blocks_for(self.file_size())
     }
 
     pub fn file_type(&self) -> String {
-        str_from_slice!(self.file_type)
+        field_str(&self.file_type)
     }
 
+    pub fn type_flag(&self) -> u8 {
+        self.file_type[0]
+    }
+
+    /// POSIX ustar, as opposed to the old GNU format ("ustar  \0") which has no prefix field.
+    pub fn is_posix(&self) -> bool {
+        &self.magic_id == b"ustar\0"
+    }
+
+    pub fn checksum(&self) -> usize {
+        slice_to_bin!(self.checksum)
+    }
 
     pub fn as_slice(&self) -> [u8; 512] {
         let mut buffer : [u8; 512] = [0; 512];
         insert_slice(&self.name, &mut buffer, 0);
-        insert_slice(&self.file_type, &mut buffer, 156);
+        insert_slice(&self.mode, &mut buffer, 100);
+        insert_slice(&self.owner_id, &mut buffer, 108);
+        insert_slice(&self.group_id, &mut buffer, 116);
         insert_slice(&self.file_size, &mut buffer, 124);
+        insert_slice(&self.modif_time, &mut buffer, 136);
+        insert_slice(&self.file_type, &mut buffer, 156);
+        insert_slice(&self.linked_name, &mut buffer, 157);
         insert_slice(&self.magic_id, &mut buffer, 257);
         insert_slice(&self.magic_version, &mut buffer, 263);
-        
-        insert_slice(&bin_to_oct!(MetaNode::gen_checksum(buffer), 8), &mut buffer, 148);
+        insert_slice(&self.owner_name, &mut buffer, 265);
+        insert_slice(&self.group_name, &mut buffer, 297);
+        insert_slice(&self.device_major, &mut buffer, 329);
+        insert_slice(&self.device_minor, &mut buffer, 337);
+        insert_slice(&self.name_prefix, &mut buffer, 345);
+
+        // Six digits, a NUL and a space, the way tar writes it.
+        let checksum : [u8; 7] = octal_field(MetaNode::gen_checksum(buffer) as u64);
+        insert_slice(&checksum, &mut buffer, 148);
+        buffer[155] = b' ';
 
         buffer
     }
 
+    /// Sums the header bytes, counting the checksum field itself as spaces.
     pub fn gen_checksum(buffer : [u8; 512]) -> usize {
         let mut sum : usize = 0;
         for i in 0..512 {
-            sum += buffer[i] as usize;
+            sum += if (148..156).contains(&i) { b' ' as usize } else { buffer[i] as usize };
         }
         sum
     }
 
+    pub fn verify_checksum(&self, raw : [u8; 512]) -> bool {
+        self.checksum() == MetaNode::gen_checksum(raw)
+    }
 }
 
 impl Display for MetaNode {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "File Name: {}\n", self.name());
-        write!(f, "File Size: {}\n", self.file_size());
-        write!(f, "File Sectors: {}\n", self.file_sector_length());
-        write!(f, "File Owner: {}\n", self.owner().0);
+        write!(f, "File Name: {}\n", self.path())?;
+        write!(f, "File Size: {}\n", self.file_size())?;
+        write!(f, "File Sectors: {}\n", self.file_sector_length())?;
+        write!(f, "File Owner: {}\n", self.owner().0)?;
+        write!(f, "File Mode: {:o}\n", self.mode())?;
         write!(f, "File Type: {}\n", self.file_type())
     }
 }
@@ -168,112 +233,652 @@ pub fn check_meta_block_size() {
     log!("Passed MetaNode Size Check...\n");
 }
 
-pub fn get_file_data<'a>(_archive : &'a [u8], _name : &'a str) -> Vec<u8> {
-    let data : Vec<u8> = Vec::new();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    HardLink,
+    Symlink,
+    CharDevice,
+    BlockDevice,
+    Directory,
+    Fifo,
+}
+
+impl EntryKind {
+    pub fn from_flag(flag : u8) -> Option<EntryKind> {
+        match flag {
+            b'0' | b'\0' | b'7' => Some(EntryKind::File),
+            b'1' => Some(EntryKind::HardLink),
+            b'2' => Some(EntryKind::Symlink),
+            b'3' => Some(EntryKind::CharDevice),
+            b'4' => Some(EntryKind::BlockDevice),
+            b'5' => Some(EntryKind::Directory),
+            b'6' => Some(EntryKind::Fifo),
+            _ => None,
+        }
+    }
+
+    pub fn flag(&self) -> u8 {
+        match self {
+            EntryKind::File => b'0',
+            EntryKind::HardLink => b'1',
+            EntryKind::Symlink => b'2',
+            EntryKind::CharDevice => b'3',
+            EntryKind::BlockDevice => b'4',
+            EntryKind::Directory => b'5',
+            EntryKind::Fifo => b'6',
+        }
+    }
 
-    data
+    /// Only regular files store their contents after the header.
+    pub fn has_data(&self) -> bool {
+        *self == EntryKind::File
+    }
 }
 
-pub fn oct2bin(value : &str) -> usize {
-    usize::from_str_radix(value, 8).expect("Invalid Octal String")
+/// One archive member, after long name and PAX overrides have been applied.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path   : String,
+    pub kind   : EntryKind,
+    pub link   : String,
+    pub mode   : u32,
+    pub uid    : u32,
+    pub gid    : u32,
+    pub owner  : String,
+    pub group  : String,
+    pub mtime  : u64,
+    pub size   : usize,
+    pub device : (u32, u32),
+    /// `None` for parent directories that have no member of their own.
+    pub header_block : Option<usize>,
+    pub data_block   : usize,
 }
 
-macro copy($start : expr, $size : expr, $src : expr, $dest : ident) {
-    let mut $dest = [0; $size];
-    $dest.copy_from_slice(&$src[$start..($start + $size)]);
+impl Entry {
+    pub fn new(path : &str, kind : EntryKind, size : usize) -> Self {
+        Self {
+            path : normalize(path),
+            kind,
+            link : String::new(),
+            mode : if kind == EntryKind::Directory { 0o755 } else { 0o644 },
+            uid : 0,
+            gid : 0,
+            owner : String::from("root"),
+            group : String::from("root"),
+            mtime : 0,
+            size,
+            device : (0, 0),
+            header_block : None,
+            data_block : 0,
+        }
+    }
+
+    pub fn link(path : &str, kind : EntryKind, target : &str) -> Self {
+        let mut entry = Entry::new(path, kind, 0);
+        entry.link = String::from(target);
+        entry.mode = 0o777;
+        entry
+    }
+
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or("")
+    }
+
+    pub fn parent(&self) -> &str {
+        parent_of(&self.path)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
 }
 
-macro str_from_slice($slice : expr) {
-    {
-        let mut s = alloc::string::String::with_capacity($slice.len());
-        for item in $slice {
-            s.push(item as char);
+/// Where an archive's 512 byte blocks live.
+pub trait TarStorage {
+    fn read_block(&self, index : usize, buffer : &mut [u8; TAR_BLOCK_SIZE]) -> bool;
+    fn write_block(&mut self, index : usize, buffer : &[u8; TAR_BLOCK_SIZE]) -> Result<(), &'static str>;
+}
+
+impl<'a> TarStorage for &'a [u8] {
+    fn read_block(&self, index : usize, buffer : &mut [u8; TAR_BLOCK_SIZE]) -> bool {
+        let start = index * TAR_BLOCK_SIZE;
+        if start >= self.len() {
+            return false;
         }
-        s
+        let end = (start + TAR_BLOCK_SIZE).min(self.len());
+        buffer.fill(0);
+        buffer[..end - start].copy_from_slice(&self[start..end]);
+        true
+    }
+
+    fn write_block(&mut self, _index : usize, _buffer : &[u8; TAR_BLOCK_SIZE]) -> Result<(), &'static str> {
+        Err("Archive Is Read Only")
     }
 }
 
-macro slice_to_bin($slice : expr) {
-    {
-        let string = str_from_slice!($slice);
-        oct2bin(&string)
+impl TarStorage for Vec<u8> {
+    fn read_block(&self, index : usize, buffer : &mut [u8; TAR_BLOCK_SIZE]) -> bool {
+        self.as_slice().read_block(index, buffer)
+    }
+
+    fn write_block(&mut self, index : usize, buffer : &[u8; TAR_BLOCK_SIZE]) -> Result<(), &'static str> {
+        let start = index * TAR_BLOCK_SIZE;
+        if self.len() < start + TAR_BLOCK_SIZE {
+            self.resize(start + TAR_BLOCK_SIZE, 0);
+        }
+        self[start..start + TAR_BLOCK_SIZE].copy_from_slice(buffer);
+        Ok(())
     }
 }
 
-macro str_to_slice($text : expr, $size : expr) {
-    {
-        let mut slice : [u8; $size] = [0; $size];
-        for i in 0..$text.len() {
-            slice[i] = ($text.as_bytes())[i]
+/// An archive written directly onto an ATA drive, starting at `start`.
+pub struct DiskStorage {
+    pub bus     : u8,
+    pub drive   : u8,
+    pub start   : ata::BlockIndex,
+    pub sectors : usize,
+}
+
+impl DiskStorage {
+    pub fn new(bus : u8, drive : u8) -> Option<Self> {
+        let sectors = get_disk_sector_count(bus, drive);
+        if sectors == 0 {
+            return None;
         }
-        slice
+        Some(Self { bus, drive, start : 0, sectors })
     }
 }
 
-macro bin_to_oct($val : expr, $size : expr) {
-    {
-        let mut s = String::new();
-        s.write_fmt(format_args!("{:0width$o}", $val, width = $size)).expect("Format Error");
-        str_to_slice!(s, $size)
+impl TarStorage for DiskStorage {
+    fn read_block(&self, index : usize, buffer : &mut [u8; TAR_BLOCK_SIZE]) -> bool {
+        if index >= self.sectors {
+            return false;
+        }
+        ata::read_raw(self.bus, self.drive, self.start + index as ata::BlockIndex, buffer);
+        true
+    }
+
+    fn write_block(&mut self, index : usize, buffer : &[u8; TAR_BLOCK_SIZE]) -> Result<(), &'static str> {
+        if index >= self.sectors {
+            return Err("Disk Is Full");
+        }
+        ata::write_raw(self.bus, self.drive, self.start + index as ata::BlockIndex, buffer);
+        Ok(())
     }
 }
 
+/// Values from a PAX extended header that override the next member's header.
+#[derive(Debug, Default)]
+struct Overrides {
+    path  : Option<String>,
+    link  : Option<String>,
+    size  : Option<usize>,
+    uid   : Option<u32>,
+    gid   : Option<u32>,
+    owner : Option<String>,
+    group : Option<String>,
+    mtime : Option<u64>,
+}
 
+impl Overrides {
+    /// Parses "<length> <key>=<value>\n" records.
+    fn parse_pax(&mut self, data : &[u8]) {
+        let mut rest = data;
+        while !rest.is_empty() {
+            let space = match rest.iter().position(|b| *b == b' ') {
+                Some(space) => space,
+                None => return,
+            };
+            let length = match core::str::from_utf8(&rest[..space]).ok().and_then(|l| l.parse::<usize>().ok()) {
+                Some(length) if length > space && length <= rest.len() => length,
+                _ => return,
+            };
+            let record = String::from_utf8_lossy(&rest[space + 1..length]);
+            let record = record.trim_end_matches('\n');
+            if let Some((key, value)) = record.split_once('=') {
+                let integer = || value.split('.').next().and_then(|v| v.parse::<u64>().ok());
+                match key {
+                    "path" => self.path = Some(String::from(value)),
+                    "linkpath" => self.link = Some(String::from(value)),
+                    "size" => self.size = integer().map(|v| v as usize),
+                    "uid" => self.uid = integer().map(|v| v as u32),
+                    "gid" => self.gid = integer().map(|v| v as u32),
+                    "uname" => self.owner = Some(String::from(value)),
+                    "gname" => self.group = Some(String::from(value)),
+                    "mtime" => self.mtime = integer(),
+                    _ => {},
+                }
+            }
+            rest = &rest[length..];
+        }
+    }
+}
 
-#[derive(Debug)]
-pub struct TarFile {
-    data : Vec<u8>,
-    name : String,
+pub struct Archive<S : TarStorage> {
+    storage   : S,
+    entries   : BTreeMap<String, Entry>,
+    end_block : usize,
 }
 
-impl TarFile {
-    pub fn from_metanode(node : MetaNode, sectors : &SectorList, node_index : usize) -> TarFile {
+impl<S : TarStorage> Archive<S> {
+    /// Reads every header in `storage`, failing on the first bad checksum.
+    pub fn open(storage : S) -> Result<Self, &'static str> {
+        let mut archive = Self {
+            storage,
+            entries : BTreeMap::new(),
+            end_block : 0,
+        };
+        archive.index()?;
+        Ok(archive)
+    }
+
+    fn index(&mut self) -> Result<(), &'static str> {
+        let mut block = 0;
+        let mut overrides = Overrides::default();
+        let mut raw = [0; TAR_BLOCK_SIZE];
+
+        loop {
+            if !self.storage.read_block(block, &mut raw) || raw.iter().all(|b| *b == 0) {
+                break;
+            }
+            let node = MetaNode::from_sector(raw);
+            if !node.verify_checksum(raw) {
+                log!("[ustar] Bad header checksum in block {}\n", block);
+                return Err("Bad Tar Header Checksum");
+            }
 
-        let sector_length = node.file_sector_length();
-        let file_size = node.file_size();
+            let data_block = block + 1;
+            match node.type_flag() {
+                GNU_LONG_NAME => {
+                    overrides.path = Some(field_str(&self.read_blocks(data_block, node.file_size())));
+                    block = data_block + node.file_sector_length();
+                },
+                GNU_LONG_LINK => {
+                    overrides.link = Some(field_str(&self.read_blocks(data_block, node.file_size())));
+                    block = data_block + node.file_sector_length();
+                },
+                PAX_HEADER => {
+                    overrides.parse_pax(&self.read_blocks(data_block, node.file_size()));
+                    block = data_block + node.file_sector_length();
+                },
+                flag => {
+                    let size = overrides.size.unwrap_or(node.file_size());
+                    let path = overrides.path.take().unwrap_or_else(|| node.path());
+                    let kind = match EntryKind::from_flag(flag) {
+                        Some(EntryKind::File) if path.ends_with('/') => Some(EntryKind::Directory),
+                        kind => kind,
+                    };
+
+                    // Global PAX headers and other unknown types are skipped.
+                    if let Some(kind) = kind {
+                        let (uid, gid) = (node.owner().1 as u32, node.group().1 as u32);
+                        let entry = Entry {
+                            path : normalize(&path),
+                            kind,
+                            link : overrides.link.take().unwrap_or_else(|| node.link_name()),
+                            mode : node.mode(),
+                            uid : overrides.uid.unwrap_or(uid),
+                            gid : overrides.gid.unwrap_or(gid),
+                            owner : overrides.owner.take().unwrap_or_else(|| node.owner().0),
+                            group : overrides.group.take().unwrap_or_else(|| node.group().0),
+                            mtime : overrides.mtime.unwrap_or(node.modified()),
+                            size : if kind.has_data() { size } else { 0 },
+                            device : node.device(),
+                            header_block : Some(block),
+                            data_block,
+                        };
+                        self.insert(entry);
+                    }
+                    overrides = Overrides::default();
+                    block = data_block + blocks_for(size);
+                },
+            }
+        }
+        self.end_block = block;
+        Ok(())
+    }
 
-        let mut binary = Vec::new();
+    /// Adds `entry`, creating any parent directories the archive didn't list.
+    fn insert(&mut self, entry : Entry) {
+        let mut parent = String::from(entry.parent());
+        while !parent.is_empty() && !self.entries.contains_key(&parent) {
+            let dir = Entry::new(&parent, EntryKind::Directory, 0);
+            parent = String::from(dir.parent());
+            self.entries.insert(dir.path.clone(), dir);
+        }
+        // Later members replace earlier ones, the same as extracting with tar.
+        if !entry.path.is_empty() {
+            self.entries.insert(entry.path.clone(), entry);
+        }
+    }
 
-        'sector_loop: for i in node_index+1..(sector_length + node_index + 1) {
-            if let Some(sector) = sectors.get_sector(i) {
-                //log!("sector {:02x}\n", i);
-                for byte in sector.data() {
-                    //log!("byte {}\n", byte as char);
-                    binary.push(byte);
-                    if binary.len() > file_size  {break 'sector_loop}
-                }
+    fn read_blocks(&self, start : usize, size : usize) -> Vec<u8> {
+        // `size` comes from the header, so grow with what's actually read.
+        let mut data = Vec::new();
+        let mut raw = [0; TAR_BLOCK_SIZE];
+        for block in start..start + blocks_for(size) {
+            if !self.storage.read_block(block, &mut raw) {
+                break;
             }
+            let remaining = size - data.len();
+            data.extend_from_slice(&raw[..remaining.min(TAR_BLOCK_SIZE)]);
+        }
+        data
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Looks up `path` without following any links.
+    pub fn get(&self, path : &str) -> Option<&Entry> {
+        self.entries.get(&normalize(path))
+    }
+
+    /// Resolves symbolic links in every component of `path`.
+    pub fn resolve(&self, path : &str) -> Result<String, &'static str> {
+        let mut pending : VecDeque<String> = components(path).collect();
+        let mut resolved = String::new();
+        let mut depth = 0;
+
+        while let Some(component) = pending.pop_front() {
+            if component == ".." {
+                resolved = String::from(parent_of(&resolved));
+                continue;
+            }
+            let candidate = join(&resolved, &component);
+            match self.entries.get(&candidate) {
+                Some(entry) if entry.kind == EntryKind::Symlink => {
+                    depth += 1;
+                    if depth > MAX_SYMLINK_DEPTH {
+                        return Err("Too Many Levels Of Symbolic Links");
+                    }
+                    if entry.link.starts_with('/') {
+                        resolved.clear();
+                    }
+                    for part in components(&entry.link).collect::<Vec<_>>().into_iter().rev() {
+                        pending.push_front(part);
+                    }
+                },
+                Some(_) => resolved = candidate,
+                None if pending.is_empty() => resolved = candidate,
+                None => return Err("No Such File Or Directory"),
+            }
+        }
+        Ok(resolved)
+    }
+
+    /// Follows symlinks and hardlinks to the entry holding the data.
+    pub fn lookup(&self, path : &str) -> Result<&Entry, &'static str> {
+        let mut entry = self.entries.get(&self.resolve(path)?).ok_or("No Such File Or Directory")?;
+        for _ in 0..MAX_SYMLINK_DEPTH {
+            if entry.kind != EntryKind::HardLink {
+                return Ok(entry);
+            }
+            entry = self.entries.get(&normalize(&entry.link)).ok_or("Dangling Hard Link")?;
+        }
+        Err("Too Many Levels Of Hard Links")
+    }
+
+    pub fn read(&self, path : &str) -> Result<Vec<u8>, &'static str> {
+        let entry = self.lookup(path)?;
+        match entry.kind {
+            EntryKind::File => Ok(self.read_blocks(entry.data_block, entry.size)),
+            EntryKind::Directory => Err("Is A Directory"),
+            _ => Err("Not A Regular File"),
+        }
+    }
+
+    /// The data stored after `entry`'s header, without following links.
+    pub fn contents(&self, entry : &Entry) -> Vec<u8> {
+        if entry.kind.has_data() { self.read_blocks(entry.data_block, entry.size) } else { Vec::new() }
+    }
+
+    pub fn read_dir(&self, path : &str) -> Result<Vec<&Entry>, &'static str> {
+        let dir = self.resolve(path)?;
+        if !dir.is_empty() && !self.lookup(&dir)?.is_dir() {
+            return Err("Not A Directory");
+        }
+        Ok(self.entries.values().filter(|entry| entry.parent() == dir).collect())
+    }
+
+    /// Writes `entry` and `data` at the end of the archive, followed by a new end marker.
+    pub fn append(&mut self, mut entry : Entry, data : &[u8]) -> Result<(), &'static str> {
+        entry.path = normalize(&entry.path);
+        if entry.path.is_empty() {
+            return Err("Invalid Path");
+        }
+        entry.size = if entry.kind.has_data() { data.len() } else { 0 };
+
+        let mut name = entry.path.clone();
+        if entry.is_dir() {
+            name.push('/');
+        }
+        let mut block = self.end_block;
+
+        if entry.link.len() > 100 {
+            block = self.write_long_name(block, GNU_LONG_LINK, &entry.link)?;
+        }
+        let (prefix, short) = match split_name(&name) {
+            Some(split) => split,
+            None => {
+                block = self.write_long_name(block, GNU_LONG_NAME, &name)?;
+                (String::new(), String::from(truncate(&name, 100)))
+            },
+        };
+
+        entry.header_block = Some(block);
+        entry.data_block = block + 1;
+        self.storage.write_block(block, &MetaNode::from_entry(&short, &prefix, &entry).as_slice())?;
+        block = self.write_data(block + 1, &data[..entry.size])?;
+
+        self.storage.write_block(block, &[0; TAR_BLOCK_SIZE])?;
+        self.storage.write_block(block + 1, &[0; TAR_BLOCK_SIZE])?;
+        self.end_block = block;
+        self.insert(entry);
+        Ok(())
+    }
+
+    fn write_long_name(&mut self, block : usize, flag : u8, name : &str) -> Result<usize, &'static str> {
+        let mut data = Vec::from(name.as_bytes());
+        data.push(0);
+        let mut entry = Entry::new(LONG_LINK_NAME, EntryKind::File, data.len());
+        entry.mode = 0;
+        let mut node = MetaNode::from_entry(LONG_LINK_NAME, "", &entry);
+        node.file_type = [flag];
+        self.storage.write_block(block, &node.as_slice())?;
+        self.write_data(block + 1, &data)
+    }
+
+    fn write_data(&mut self, mut block : usize, data : &[u8]) -> Result<usize, &'static str> {
+        for chunk in data.chunks(TAR_BLOCK_SIZE) {
+            let mut raw = [0; TAR_BLOCK_SIZE];
+            raw[..chunk.len()].copy_from_slice(chunk);
+            self.storage.write_block(block, &raw)?;
+            block += 1;
+        }
+        Ok(block)
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+}
+
+/// Reads `name` out of an in-memory archive, or returns nothing if it isn't there.
+pub fn get_file_data<'a>(archive : &'a [u8], name : &'a str) -> Vec<u8> {
+    match Archive::open(archive) {
+        Ok(archive) => archive.read(name).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub fn oct2bin(value : &str) -> usize {
+    usize::from_str_radix(value.trim_matches(|c| c == ' ' || c == '\0'), 8).unwrap_or(0)
+}
+
+/// Parses a numeric header field, either octal text or GNU base-256 for large values.
+pub fn parse_number(field : &[u8]) -> usize {
+    if field.first().map_or(false, |b| b & 0x80 != 0) {
+        return field[1..].iter().fold((field[0] & 0x7F) as usize, |value, b| value << 8 | *b as usize);
+    }
+    oct2bin(&field_str(field))
+}
+
+/// Formats `value` as zero padded octal followed by a NUL.
+fn octal_field<const N : usize>(value : u64) -> [u8; N] {
+    let mut field = [0; N];
+    let mut s = String::new();
+    s.write_fmt(format_args!("{:0width$o}", value, width = N - 1)).expect("Format Error");
+    let digits = s.as_bytes();
+    let skip = digits.len().saturating_sub(N - 1);
+    field[..N - 1].copy_from_slice(&digits[skip..]);
+    field
+}
+
+fn field_str(field : &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+fn truncate(text : &str, length : usize) -> &str {
+    let mut end = text.len().min(length);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+fn blocks_for(size : usize) -> usize {
+    (size + TAR_BLOCK_SIZE - 1) / TAR_BLOCK_SIZE
+}
+
+/// Splits a member name into ustar `prefix` and `name` fields, if it fits.
+fn split_name(name : &str) -> Option<(String, String)> {
+    if name.len() <= 100 {
+        return Some((String::new(), String::from(name)));
+    }
+    for (index, _) in name.match_indices('/') {
+        let (prefix, rest) = (&name[..index], &name[index + 1..]);
+        if prefix.len() <= 155 && rest.len() <= 100 && !rest.is_empty() {
+            return Some((String::from(prefix), String::from(rest)));
         }
+    }
+    None
+}
+
+fn components(path : &str) -> impl Iterator<Item = String> + '_ {
+    path.split('/').filter(|c| !c.is_empty() && *c != ".").map(String::from)
+}
+
+/// Archive paths are relative, without `.` components or a trailing slash.
+pub fn normalize(path : &str) -> String {
+    let mut normal = String::with_capacity(path.len());
+    for component in components(path) {
+        if !normal.is_empty() {
+            normal.push('/');
+        }
+        normal.push_str(&component);
+    }
+    normal
+}
+
+fn parent_of(path : &str) -> &str {
+    path.rfind('/').map_or("", |index| &path[..index])
+}
+
+fn join(dir : &str, name : &str) -> String {
+    if dir.is_empty() {
+        String::from(name)
+    } else {
+        let mut path = String::from(dir);
+        path.push('/');
+        path.push_str(name);
+        path
+    }
+}
+
+macro copy($start : expr, $size : expr, $src : expr, $dest : ident) {
+    let mut $dest = [0; $size];
+    $dest.copy_from_slice(&$src[$start..($start + $size)]);
+}
+
+macro slice_to_bin($slice : expr) {
+    parse_number(&$slice)
+}
+
+macro str_to_slice($text : expr, $size : expr) {
+    {
+        let mut slice : [u8; $size] = [0; $size];
+        for i in 0..$text.len().min($size) {
+            slice[i] = ($text.as_bytes())[i]
+        }
+        slice
+    }
+}
+
+#[derive(Debug)]
+pub struct TarFile {
+    data   : Vec<u8>,
+    name   : String,
+    cursor : usize,
+    dirty  : bool, // Written to since it was opened, so `close` has to save it.
+}
 
+impl TarFile {
+    pub fn from_entry(entry : &Entry, data : Vec<u8>) -> TarFile {
         TarFile {
-            data : binary,
-            name : node.name(),
+            data,
+            name : entry.path.clone(),
+            cursor : 0,
+            dirty : false,
         }
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
 }
 
 impl File<u8> for TarFile {
     fn open(name : &str) -> Self {
         Self {
             data : Vec::new(),
-            name : String::from(name.trim_matches('\0'))
+            name : normalize(name.trim_matches('\0')),
+            cursor : 0,
+            dirty : false,
         }
     }
 
-    //TODO: Have this call save beforehand.
+    /// Appends the file to the mounted archive as a new member, if it was changed.
     fn close(&mut self) {
-        let file_table = FILE_TABLE.lock();
-        save_fs(1, 1, &self.get_sectors(), file_table.table.get(self.name.as_str()).unwrap().1 as u32);
-
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+        let entry = Entry::new(&self.name, EntryKind::File, self.data.len());
+        if let Err(error) = with_archive(|archive| archive.append(entry, &self.data)) {
+            log!("[ustar] Unable to save {}: {}\n", self.name, error);
+        }
     }
 
     fn read_all(&mut self) -> Vec<u8> {
-        let mut v = Vec::new();
-        while let Some(b) = self.read() {
-            v.push(b);
-        }
-        v.reverse();
-        v
+        let rest = Vec::from(&self.data[self.cursor..]);
+        self.cursor = self.data.len();
+        rest
     }
 
     fn write_all(&mut self, data : Vec<u8>) {
@@ -297,19 +902,22 @@ impl File<u8> for TarFile {
         meta_sector.append(&mut list);
         meta_sector
     }
-
-     
 }
 
 impl IoReader<u8> for TarFile {
     fn read(&mut self) -> Option<u8> {
-        self.data.pop()
+        let byte = self.data.get(self.cursor).copied();
+        if byte.is_some() {
+            self.cursor += 1;
+        }
+        byte
     }
 }
 
 impl IoWriter<u8> for TarFile {
     fn write(&mut self, item : u8) {
         self.data.push(item);
+        self.dirty = true;
     }
 }
 
@@ -323,32 +931,46 @@ impl Write for TarFile {
 }
 
 pub fn insert_slice(src : &[u8], dest : &mut [u8], start : usize) {
-    assert!(src.len() + start < dest.len());
+    assert!(src.len() + start <= dest.len());
     for i in start..(start + src.len()) {
         dest[i] = src[i - start];
     }
 }
 
-pub fn list_files() -> Vec<(usize, String, MetaNode)> {
-    let sectors = ACTIVE_PARTITION.lock();
-    let mut names = Vec::new();
-    let mut idx = 0;
-    for sector in sectors.sectors() {
-        let node = MetaNode::from_sector(sector.data());
-        if !node.name().is_empty() && node.file_type() == "0" {
-            names.push((idx, node.name(), node));
+/// Runs `f` on the mounted disk archive.
+pub fn with_archive<T, F : FnOnce(&mut Archive<DiskStorage>) -> Result<T, &'static str>>(f : F) -> Result<T, &'static str> {
+    let mut result = Err("No Archive Mounted");
+    without_interrupts(|| {
+        if let Some(archive) = ARCHIVE.lock().as_mut() {
+            result = f(archive);
         }
-        idx += 1;
-    }
-    names
+    });
+    result
+}
+
+pub fn archive() -> MutexGuard<'static, Option<Archive<DiskStorage>>> {
+    ARCHIVE.lock()
+}
+
+pub fn mount(bus : u8, drive : u8) -> Result<usize, &'static str> {
+    let storage = DiskStorage::new(bus, drive).ok_or("Disk Not Found")?;
+    let archive = Archive::open(storage)?;
+    let count = archive.len();
+    without_interrupts(|| {
+        *ARCHIVE.lock() = Some(archive);
+    });
+    Ok(count)
+}
+
+pub fn list_files() -> Vec<Entry> {
+    with_archive(|archive| Ok(archive.entries().cloned().collect())).unwrap_or_default()
 }
 
 pub fn search(name : &str) -> Option<TarFile> {
-    if let Some((node,index)) = FILE_TABLE.lock().table.get(name) {
-        return Some(TarFile::from_metanode(*node, &(*ACTIVE_PARTITION.lock()), *index));
-    }
-    None
-    
+    with_archive(|archive| {
+        let entry = archive.lookup(name)?.clone();
+        Ok(TarFile::from_entry(&entry, archive.read(name)?))
+    }).ok()
 }
 
 pub fn load_fs(bus : u8, disk : u8, sector_count : usize) -> SectorList {
@@ -360,7 +982,6 @@ pub fn load_fs(bus : u8, disk : u8, sector_count : usize) -> SectorList {
 
 pub fn save_fs(bus : u8, disk : u8, sectors : &SectorList, start_block : u32) {
     kernel::hardware::ata::write_sectors(bus, disk, start_block, sectors);
-    
 }
 
 pub fn open_file(name : &str) -> Option<TarFile> {
@@ -372,104 +993,21 @@ pub fn open_file(name : &str) -> Option<TarFile> {
 }
 
 pub fn file_exists(name : &str) -> bool {
-    FILE_TABLE.lock().table.contains_key(name)
+    with_archive(|archive| archive.lookup(name).map(|_| ())).is_ok()
 }
 
+/// A new empty file, saved as a member when it's closed.
 pub fn file_create(name : &str) -> Option<TarFile> {
-    let file = TarFile::open(name);
+    let mut file = TarFile::open(name);
+    file.dirty = true;
     Some(file)
 }
 
-#[derive(Debug)]
-pub struct FileTable {
-    table : BTreeMap<String, (MetaNode, usize)>
-}
-
-impl FileTable {
-    pub fn new() -> Self {
-        let mut table  = BTreeMap::new();
-
-        for (index,mut name, node) in list_files() {
-            let _ = 0;
-
-            name = String::from(name.trim_end_matches('\0'));
-            table.insert(name, (node, index));
-        }
-
-        Self {
-            table
-        }
-    }
-
-    pub fn from_sector(sector : &Sector) -> FileTable {
-        let mut table : BTreeMap<String, (MetaNode, usize)>  = BTreeMap::new();
-
-        let raw = sector.data();
-
-
-        let mut entry_list : Vec<u16> = Vec::with_capacity(128);
-
-        let mut i = 0;
-        while i < 128 {
-                    
-        let entry = u16::from_be_bytes([
-            raw[i + 0],
-            raw[i + 1]
-        ]);
-
-        if entry > 0 {
-            entry_list.push(entry);
-        }
-
-        i += size_of::<u16>();
-
-
-        }
-        let active_partition = ACTIVE_PARTITION.lock();
-        for entry in entry_list {
-            if let Some(sector) = active_partition.get_sector(entry as usize) {
-                let node = MetaNode::from_sector(sector.data());
-                let mut name = node.name();
-                name = String::from(name.trim_end_matches('\0'));
-                table.insert(name, (node, entry as usize));
-            }
-
-        }
-
-        FileTable {
-            table
-        }
-    }
-
-    pub fn to_sector(&mut self) -> Sector {
-        let mut sector = Sector::new(0);
-        let mut idx = 5;
-        for entry in &self.table {
-            sector.data_mut()[idx + 0] = (entry.1.1 >> 8) as u8;
-            sector.data_mut()[idx + 1] = (entry.1.1 >> 0) as u8;
-
-            idx += 2;
-        }
-        sector
-    }
-
-    pub fn next_free_block(&self) -> usize {
-        let next = 0;
-        let _max_sector = 0;
-        next
-    }
-}
-
-pub fn file_table() -> MutexGuard<'static, FileTable> {
-    FILE_TABLE.lock()
-}
-
+/// Mounts the archive on the secondary slave drive, when there is one.
 pub fn init() -> InitResult<()> {
-    ACTIVE_PARTITION.lock();
-    FILE_TABLE.lock();
-
+    match mount(1, 1) {
+        Ok(count) => log!("{} Archive Members - ", count),
+        Err(error) => log!("{} - ", error),
+    }
     Ok(())
 }
-
-
-pub const FILE_TABLE_ENTRY_COUNT_INDEX : usize = 0x005;
\ No newline at end of file
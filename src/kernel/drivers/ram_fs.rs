@@ -5,6 +5,9 @@ pub const SECTORS_PER_DISK : usize = MB_PER_DISK * SECTORS_PER_MB;
 
 pub const MAX_STRUCTURE_SIZE : usize = BYTES_PER_SECTOR * 1;
 use crate::{data::boxed::*, };//fs::BinarySerializable};
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use crate::kernel::vfs::{self, DirEntry, FileSystem, Metadata, NodeKind};
 
 
 pub trait Read {
@@ -117,4 +120,234 @@ impl Disk {
     //     self.read_slice(data, address);
     //     T::deserialize(data)
     // } 
-}
\ No newline at end of file
+}
+
+pub const ROOT_INODE : u64 = 1;
+
+#[derive(Debug, Clone)]
+struct Inode {
+    meta     : Metadata,
+    data     : Vec<u8>,
+    children : BTreeMap<String, u64>,
+}
+
+/// A filesystem that lives entirely on the heap, used for the initramfs.
+#[derive(Debug, Clone)]
+pub struct RamFs {
+    inodes     : BTreeMap<u64, Inode>,
+    next_inode : u64,
+}
+
+impl RamFs {
+    pub fn new() -> Self {
+        let mut meta = Metadata::new(NodeKind::Directory);
+        meta.inode = ROOT_INODE;
+        let mut inodes = BTreeMap::new();
+        inodes.insert(ROOT_INODE, Inode { meta, data : Vec::new(), children : BTreeMap::new() });
+        Self {
+            inodes,
+            next_inode : ROOT_INODE + 1,
+        }
+    }
+
+    /// Finds the inode at `path` without following symlinks.
+    pub fn lookup(&self, path : &str) -> Option<u64> {
+        let mut inode = ROOT_INODE;
+        for name in vfs::normalize(path).split('/').filter(|name| !name.is_empty()) {
+            inode = *self.inodes.get(&inode)?.children.get(name)?;
+        }
+        Some(inode)
+    }
+
+    fn inode(&self, path : &str) -> Result<&Inode, &'static str> {
+        self.lookup(path).and_then(|inode| self.inodes.get(&inode)).ok_or("No Such File Or Directory")
+    }
+
+    fn inode_mut(&mut self, path : &str) -> Result<&mut Inode, &'static str> {
+        let inode = self.lookup(path).ok_or("No Such File Or Directory")?;
+        self.inodes.get_mut(&inode).ok_or("No Such File Or Directory")
+    }
+
+    fn parent_of(&self, path : &str) -> Result<(u64, String), &'static str> {
+        let path = vfs::normalize(path);
+        let name = vfs::filename(&path);
+        if name.is_empty() {
+            return Err("File Exists");
+        }
+        let parent = self.lookup(vfs::dirname(&path)).ok_or("No Such File Or Directory")?;
+        if !self.inodes[&parent].meta.is_dir() {
+            return Err("Not A Directory");
+        }
+        Ok((parent, String::from(name)))
+    }
+
+    /// Creates a node of any kind and returns its inode number.
+    pub fn mknod(&mut self, path : &str, meta : Metadata) -> Result<u64, &'static str> {
+        let (parent, name) = self.parent_of(path)?;
+        if self.inodes[&parent].children.contains_key(&name) {
+            return Err("File Exists");
+        }
+        let inode = self.next_inode;
+        self.next_inode += 1;
+
+        let mut meta = meta;
+        meta.inode = inode;
+        meta.links = 1;
+        self.inodes.insert(inode, Inode { meta, data : Vec::new(), children : BTreeMap::new() });
+        self.inodes.get_mut(&parent).unwrap().children.insert(name, inode);
+        Ok(inode)
+    }
+
+    /// Adds another name for the node at `target`.
+    pub fn link(&mut self, target : &str, path : &str) -> Result<(), &'static str> {
+        let inode = self.lookup(target).ok_or("No Such File Or Directory")?;
+        if self.inodes[&inode].meta.is_dir() {
+            return Err("Is A Directory");
+        }
+        let (parent, name) = self.parent_of(path)?;
+        if self.inodes[&parent].children.contains_key(&name) {
+            return Err("File Exists");
+        }
+        self.inodes.get_mut(&parent).unwrap().children.insert(name, inode);
+        self.inodes.get_mut(&inode).unwrap().meta.links += 1;
+        Ok(())
+    }
+
+    pub fn symlink(&mut self, target : &str, path : &str) -> Result<(), &'static str> {
+        let mut meta = Metadata::new(NodeKind::Symlink);
+        meta.mode = 0o777;
+        meta.size = target.len();
+        let inode = self.mknod(path, meta)?;
+        self.inodes.get_mut(&inode).unwrap().data = Vec::from(target.as_bytes());
+        Ok(())
+    }
+
+    pub fn set_metadata(&mut self, path : &str, meta : &Metadata) -> Result<(), &'static str> {
+        let inode = self.inode_mut(path)?;
+        inode.meta.mode = meta.mode;
+        inode.meta.uid = meta.uid;
+        inode.meta.gid = meta.gid;
+        inode.meta.mtime = meta.mtime;
+        inode.meta.device = meta.device;
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.inodes.values().map(|inode| inode.data.len()).sum()
+    }
+}
+
+impl Default for RamFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem for RamFs {
+    fn name(&self) -> &'static str {
+        "ramfs"
+    }
+
+    fn metadata(&self, path : &str) -> Result<Metadata, &'static str> {
+        let inode = self.inode(path)?;
+        let mut meta = inode.meta.clone();
+        if meta.kind != NodeKind::Directory {
+            meta.size = inode.data.len();
+        }
+        Ok(meta)
+    }
+
+    fn read_dir(&self, path : &str) -> Result<Vec<DirEntry>, &'static str> {
+        let dir = self.inode(path)?;
+        if !dir.meta.is_dir() {
+            return Err("Not A Directory");
+        }
+        Ok(dir.children.iter().map(|(name, inode)| {
+            let node = &self.inodes[inode];
+            DirEntry { name : name.clone(), kind : node.meta.kind, size : node.data.len() }
+        }).collect())
+    }
+
+    fn read(&mut self, path : &str, offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        let inode = self.inode(path)?;
+        if inode.meta.is_dir() {
+            return Err("Is A Directory");
+        }
+        if offset >= inode.data.len() {
+            return Ok(0);
+        }
+        let count = buffer.len().min(inode.data.len() - offset);
+        buffer[..count].copy_from_slice(&inode.data[offset..offset + count]);
+        Ok(count)
+    }
+
+    fn write(&mut self, path : &str, offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+        let inode = self.inode_mut(path)?;
+        if inode.meta.is_dir() {
+            return Err("Is A Directory");
+        }
+        if inode.data.len() < offset + buffer.len() {
+            inode.data.resize(offset + buffer.len(), 0);
+        }
+        inode.data[offset..offset + buffer.len()].copy_from_slice(buffer);
+        Ok(buffer.len())
+    }
+
+    fn create(&mut self, path : &str, kind : NodeKind) -> Result<(), &'static str> {
+        self.mknod(path, Metadata::new(kind)).map(|_| ())
+    }
+
+    fn remove(&mut self, path : &str) -> Result<(), &'static str> {
+        let (parent, name) = self.parent_of(path)?;
+        let inode = *self.inodes[&parent].children.get(&name).ok_or("No Such File Or Directory")?;
+        let node = &self.inodes[&inode];
+        if node.meta.is_dir() && !node.children.is_empty() {
+            return Err("Directory Not Empty");
+        }
+        self.inodes.get_mut(&parent).unwrap().children.remove(&name);
+
+        let node = self.inodes.get_mut(&inode).unwrap();
+        node.meta.links -= 1;
+        if node.meta.links == 0 {
+            self.inodes.remove(&inode);
+        }
+        Ok(())
+    }
+
+    fn truncate(&mut self, path : &str, size : usize) -> Result<(), &'static str> {
+        let inode = self.inode_mut(path)?;
+        if inode.meta.is_dir() {
+            return Err("Is A Directory");
+        }
+        inode.data.resize(size, 0);
+        Ok(())
+    }
+
+    fn set_mode(&mut self, path : &str, mode : u32) -> Result<(), &'static str> {
+        self.inode_mut(path)?.meta.mode = mode;
+        Ok(())
+    }
+
+    fn set_owner(&mut self, path : &str, uid : u32, gid : u32) -> Result<(), &'static str> {
+        let inode = self.inode_mut(path)?;
+        inode.meta.uid = uid;
+        inode.meta.gid = gid;
+        Ok(())
+    }
+
+    fn symlink(&mut self, target : &str, path : &str) -> Result<(), &'static str> {
+        RamFs::symlink(self, target, path)
+    }
+
+    fn link(&mut self, target : &str, path : &str) -> Result<(), &'static str> {
+        RamFs::link(self, target, path)
+    }
+
+    fn read_link(&self, path : &str) -> Result<String, &'static str> {
+        let inode = self.inode(path)?;
+        if inode.meta.kind != NodeKind::Symlink {
+            return Err("Not A Symlink");
+        }
+        Ok(String::from_utf8_lossy(&inode.data).into_owned())
+    }
+}
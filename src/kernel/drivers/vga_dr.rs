@@ -1,7 +1,10 @@
 
 
+    use alloc::{vec, vec::Vec};
+    use core::sync::atomic::{AtomicBool, Ordering};
     use lazy_static::lazy_static;
     use spin::*;
+    use x86_64::instructions::port::Port;
 
     pub use vga::colors::Color16;
     pub use vga::writers::{Graphics640x480x16, GraphicsWriter};
@@ -14,13 +17,23 @@
         static ref GFX_MODE : Mutex<Graphics640x480x16> = Mutex::new(
             Graphics640x480x16::new()
         );
+        /// A font uploaded for text mode, put back whenever text mode is set again.
+        static ref TEXT_FONT : Mutex<Option<Vec<u8>>> = Mutex::new(None);
     }
 
+    /// Whether the card is in text mode, where plane 2 holds the font. It boots that way.
+    static TEXT_MODE : AtomicBool = AtomicBool::new(true);
+
     pub fn init() -> InitResult<()> {
         GFX_MODE.lock().set_mode();
+        TEXT_MODE.store(false, Ordering::SeqCst);
         Ok(())
     }
 
+    pub fn is_text_mode() -> bool {
+        TEXT_MODE.load(Ordering::SeqCst)
+    }
+
     pub fn clear_screen(color : Color16) {
         GFX_MODE.lock().clear_screen(color);
     }
@@ -29,14 +42,6 @@
         GFX_MODE.lock().set_pixel(x,y,color);
     }
 
-    pub fn draw_str(x : usize, mut y : usize, text : &str, color : Color16) {
-        let mode = GFX_MODE.lock();
-        for (offset, chr) in text.chars().enumerate() {
-                if chr == '\n' || x * 8 + offset > 80 {y += 1};
-                mode.draw_character(x + offset * 8, y, chr, color);
-        }
-    }
-
     pub fn draw_chr(x : usize, y : usize, chr : char, color : Color16) {
         GFX_MODE.lock().draw_character(x, y, chr, color);
     }
@@ -45,4 +50,116 @@
         GFX_MODE.lock().draw_line(start, end, color);
     }
 
+    const SEQUENCER_INDEX : u16 = 0x3C4;
+    const SEQUENCER_DATA : u16 = 0x3C5;
+    const GRAPHICS_INDEX : u16 = 0x3CE;
+    const GRAPHICS_DATA : u16 = 0x3CF;
+    const MAP_MASK : u8 = 0x02;
+    const ENABLE_SET_RESET : u8 = 0x01;
+    const GRAPHICS_MODE : u8 = 0x05;
+    const BIT_MASK : u8 = 0x08;
+    const WRITE_MODE_0 : u8 = 0x00;
+    const WRITE_MODE_2 : u8 = 0x02;
+    const READ_MAP_SELECT : u8 = 0x04;
+    const MEMORY_MODE : u8 = 0x04;
+    const MISCELLANEOUS : u8 = 0x06;
+
+    const FRAME_BUFFER : usize = 0xA0000;
+    /// Bytes in one row of one plane, 8 pixels each.
+    pub const PLANE_PITCH : usize = 80;
+
+    fn write_register(index_port : u16, data_port : u16, index : u8, value : u8) {
+        let mut index_port : Port<u8> = Port::new(index_port);
+        let mut data_port : Port<u8> = Port::new(data_port);
+        unsafe {
+            index_port.write(index);
+            data_port.write(value);
+        }
+    }
+
+    /// Writes whole bytes of planar memory for row `y`, starting `first_byte`
+    /// bytes in. `planes[p][i]` holds bit `p` of the 8 pixels of byte `i`, left
+    /// pixel in the top bit. Leaves the card as the drawing functions expect it.
+    pub fn write_planes(y : usize, first_byte : usize, planes : [&[u8]; 4]) {
+        let _mode = GFX_MODE.lock();
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MODE, WRITE_MODE_0);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, ENABLE_SET_RESET, 0x00);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, BIT_MASK, 0xFF);
+
+        let row = (FRAME_BUFFER + y * PLANE_PITCH + first_byte) as *mut u8;
+        for (plane, bytes) in planes.iter().enumerate() {
+            write_register(SEQUENCER_INDEX, SEQUENCER_DATA, MAP_MASK, 1 << plane);
+            for (offset, byte) in bytes.iter().enumerate() {
+                unsafe { row.add(offset).write_volatile(*byte) };
+            }
+        }
+
+        write_register(SEQUENCER_INDEX, SEQUENCER_DATA, MAP_MASK, 0x0F);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MODE, WRITE_MODE_2);
+    }
+
+    /// The palette index, 0 to 15, of the pixel at (`x`, `y`), read back a plane at a time.
+    pub fn read_pixel(x : usize, y : usize) -> u8 {
+        let _mode = GFX_MODE.lock();
+        let address = (FRAME_BUFFER + y * PLANE_PITCH + x / 8) as *const u8;
+        let mask = 0x80 >> (x % 8);
+        let mut index = 0;
+        for plane in 0..4 {
+            write_register(GRAPHICS_INDEX, GRAPHICS_DATA, READ_MAP_SELECT, plane);
+            if unsafe { address.read_volatile() } & mask != 0 {
+                index |= 1 << plane;
+            }
+        }
+        index
+    }
+
+    /// Glyphs in the text mode character generator, and the bytes each one takes in plane 2.
+    pub const TEXT_GLYPHS : usize = 256;
+    const TEXT_GLYPH_SIZE : usize = 32;
+
+    /// Replaces the text mode font with `glyphs`, 256 glyphs of `height` rows
+    /// of one byte each. Text mode shows the top 16 rows of each. In graphics
+    /// mode the font is only kept, and goes up when text mode is next set.
+    pub fn load_text_font(glyphs : &[u8], height : usize) -> Result<(), &'static str> {
+        if height == 0 || height > TEXT_GLYPH_SIZE || glyphs.len() < TEXT_GLYPHS * height {
+            return Err("Invalid Text Mode Font");
+        }
+        let mut font = vec![0u8; TEXT_GLYPHS * TEXT_GLYPH_SIZE];
+        for (glyph, rows) in glyphs.chunks(height).take(TEXT_GLYPHS).enumerate() {
+            font[glyph * TEXT_GLYPH_SIZE..glyph * TEXT_GLYPH_SIZE + height].copy_from_slice(rows);
+        }
+        if TEXT_MODE.load(Ordering::SeqCst) {
+            write_text_font(&font);
+        }
+        *TEXT_FONT.lock() = Some(font);
+        Ok(())
+    }
+
+    /// Puts the uploaded text mode font back after setting the mode reloaded the
+    /// default one. Call it right after switching to text mode.
+    pub fn reload_text_font() {
+        TEXT_MODE.store(true, Ordering::SeqCst);
+        if let Some(font) = TEXT_FONT.lock().as_ref() {
+            write_text_font(font);
+        }
+    }
 
+    /// Writes plane 2, where the card keeps its font, then goes back to text mode addressing.
+    fn write_text_font(font : &[u8]) {
+        write_register(SEQUENCER_INDEX, SEQUENCER_DATA, MAP_MASK, 0x04);
+        write_register(SEQUENCER_INDEX, SEQUENCER_DATA, MEMORY_MODE, 0x07);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, READ_MAP_SELECT, 0x02);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MODE, 0x00);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, MISCELLANEOUS, 0x04);
+
+        let plane = FRAME_BUFFER as *mut u8;
+        for (offset, byte) in font.iter().enumerate() {
+            unsafe { plane.add(offset).write_volatile(*byte) };
+        }
+
+        write_register(SEQUENCER_INDEX, SEQUENCER_DATA, MAP_MASK, 0x03);
+        write_register(SEQUENCER_INDEX, SEQUENCER_DATA, MEMORY_MODE, 0x03);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, READ_MAP_SELECT, 0x00);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, GRAPHICS_MODE, 0x10);
+        write_register(GRAPHICS_INDEX, GRAPHICS_DATA, MISCELLANEOUS, 0x0E);
+    }
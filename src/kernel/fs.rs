@@ -1,12 +1,14 @@
 
-use alloc::format;
 use alloc::string::String;
 use alloc::vec;
+use alloc::vec::Vec;
 use bit_field::BitField;
+use core::sync::atomic::{AtomicU8, Ordering};
 use lazy_static::lazy_static;
 use spin::Mutex;
 
 use crate::println;
+use crate::kernel::{task, vfs};
 
 lazy_static! {
     pub static ref BLOCK_DEVICE: Mutex<Option<BlockDevice>> = Mutex::new(None);
@@ -14,46 +16,154 @@ lazy_static! {
 
 const MAGIC: &str = "MOROS FS";
 
+// Superblock layout:
+// 0..8 => MAGIC
+// 8    => format version, bumped whenever the on-disk layout changes:
+//         0 => 18 byte directory entry headers, no owners (disks formatted before versioning)
+//         2 => mode, uid and gid in a 24 byte header
+//         3 => link count, a 25 byte header
+// Disks in an older layout are mounted read-only and read with their own headers.
+const FORMAT_VERSION: u8 = 3;
+const VERSION_OFFSET: usize = 8;
+
+/// Format version of the mounted disk.
+static MOUNTED_VERSION: AtomicU8 = AtomicU8::new(FORMAT_VERSION);
+
+// Directory entry layout:
+// 0      => kind
+// 1..5   => addr
+// 5..9   => size
+// 9..17  => time
+// 17..19 => mode
+// 19..21 => uid
+// 21..23 => gid
+// 23     => link count
+// 24     => name length
+// 25..   => name
+//
+// Hard links are entries sharing an address. Every entry for an address
+// carries the same size, time, mode, owner and link count.
+const DIR_ENTRY_HEADER: usize = 25;
+
+/// Length of a directory entry header on the mounted disk.
+fn entry_header() -> usize {
+    match MOUNTED_VERSION.load(Ordering::SeqCst) {
+        0 => 18,
+        2 => 24,
+        _ => DIR_ENTRY_HEADER,
+    }
+}
+
+/// Whether the mounted disk has an older layout, which is never written to.
+pub fn is_read_only() -> bool {
+    MOUNTED_VERSION.load(Ordering::SeqCst) != FORMAT_VERSION
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FileType {
     Dir = 0,
     File = 1,
+    Symlink = 2,
 }
 
+/// How many symlinks a single path walk may follow before giving up on a loop.
+pub const MAX_SYMLINK_DEPTH: usize = 8;
+
 pub enum SeekFrom {
     Start(u32),
     Current(i32),
     End(i32),
 }
 
+/// Everything before the last component of an absolute path, "/" for the root.
 pub fn dirname(pathname: &str) -> &str {
-    let n = pathname.len();
-    let i = match pathname.rfind('/') {
-        Some(0) => 1,
-        Some(i) => i,
-        None => n,
-    };
-    &pathname[0..i]
+    let pathname = pathname.trim_end_matches('/');
+    match pathname.rfind('/') {
+        Some(0) | None => "/",
+        Some(i) => &pathname[0..i],
+    }
 }
 
 pub fn filename(pathname: &str) -> &str {
-    let n = pathname.len();
-    let i = match pathname.rfind('/') {
-        Some(i) => i + 1,
-        None => 0,
-    };
-    &pathname[i..n]
+    let pathname = pathname.trim_end_matches('/');
+    match pathname.rfind('/') {
+        Some(i) => &pathname[i + 1..],
+        None => pathname,
+    }
 }
 
-// Transform "foo.txt" into "/path/to/foo.txt"
-pub fn realpath(pathname: &str) -> String {
-    if pathname.starts_with('/') {
-        pathname.into()
-    } else {
-        let dirname = "/";
-        let sep = if dirname.ends_with('/') { "" } else { "/" };
-        format!("{}{}{}", dirname, sep, pathname)
+/// Checks `pathname` is absolute within the disk, as `vfs` hands paths over.
+/// The disk doesn't know where it's mounted, so relative paths are refused
+/// rather than guessed at: resolve them through `vfs` instead.
+pub fn realpath(pathname: &str) -> Option<String> {
+    if pathname.starts_with('/') { Some(String::from(pathname)) } else { None }
+}
+
+/// Resolves the symlinks in `pathname`, and in its last component when
+/// `follow` is set, giving a path that only goes through real directories.
+/// Absolute targets name a path in the whole VFS tree, and the disk doesn't
+/// know where it's mounted, so those are left to `vfs::resolve`.
+pub fn resolve(pathname: &str, follow: bool) -> Option<String> {
+    let mut pending: Vec<String> = realpath(pathname)?.split('/')
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .rev()
+        .collect();
+    let mut parts: Vec<String> = Vec::new();
+    let mut dirs = vec![Dir::root()];
+    let mut depth = 0;
+
+    while let Some(name) = pending.pop() {
+        match name.as_str() {
+            "." => continue,
+            ".." => {
+                if parts.pop().is_some() {
+                    dirs.pop();
+                }
+                continue;
+            },
+            _ => {},
+        }
+        let dir = *dirs.last().unwrap();
+        let entry = match dir.find(&name) {
+            Some(entry) => entry,
+            // Only the last component may be missing, for creating it.
+            None if pending.is_empty() => {
+                parts.push(name);
+                break;
+            },
+            None => return None,
+        };
+        let last = pending.is_empty();
+        if entry.is_symlink() && (follow || !last) {
+            depth += 1;
+            if depth > MAX_SYMLINK_DEPTH {
+                return None;
+            }
+            let target = entry.target();
+            if target.starts_with('/') {
+                return None;
+            }
+            pending.extend(target.split('/').filter(|name| !name.is_empty()).map(String::from).rev());
+            continue;
+        }
+        if entry.is_dir() {
+            dirs.push(entry.to_dir());
+        } else if !last {
+            return None;
+        }
+        parts.push(name);
+    }
+
+    let mut path = String::new();
+    for part in parts {
+        path.push('/');
+        path.push_str(&part);
     }
+    if path.is_empty() {
+        path.push('/');
+    }
+    Some(path)
 }
 
 #[derive(Clone)]
@@ -68,7 +178,7 @@ pub struct File {
 
 impl File {
     pub fn create(pathname: &str) -> Option<Self> {
-        let pathname = realpath(pathname);
+        let pathname = resolve(pathname, false)?;
         let dirname = dirname(&pathname);
         let filename = filename(&pathname);
         if let Some(dir) = Dir::open(dirname) {
@@ -80,7 +190,7 @@ impl File {
     }
 
     pub fn open(pathname: &str) -> Option<Self> {
-        let pathname = realpath(pathname);
+        let pathname = resolve(pathname, true)?;
         let dirname = dirname(&pathname);
         let filename = filename(&pathname);
         if let Some(dir) = Dir::open(dirname) {
@@ -154,6 +264,9 @@ impl File {
     }
 
     pub fn write(&mut self, buf: &[u8]) -> Result<usize, ()> {
+        if is_read_only() {
+            return Err(());
+        }
         let buf_len = buf.len();
         let mut addr = self.addr;
         let mut bytes = 0; // Number of bytes written
@@ -174,15 +287,9 @@ impl File {
                 pos += 1;
             }
 
+            // Blocks past the written range still hold the rest of the file.
             addr = match block.next() {
-                Some(next_block) => {
-                    if bytes < buf_len {
-                        next_block.addr()
-                    } else {
-                        // TODO: Free the next block(s)
-                        0
-                    }
-                }
+                Some(next_block) => next_block.addr(),
                 None => {
                     if bytes < buf_len {
                         match Block::alloc() {
@@ -198,17 +305,52 @@ impl File {
             block.set_next(addr);
             block.write();
         }
-        self.size = self.offset;
+        self.size = self.size.max(self.offset);
         self.dir.update_entry(&self.name, self.size);
         Ok(bytes)
     }
 
+    /// Cuts the file down to `size` bytes, freeing the blocks past it, or
+    /// grows it with zeros.
+    pub fn truncate(&mut self, size: usize) -> Result<(), ()> {
+        if is_read_only() {
+            return Err(());
+        }
+        let size = size as u32;
+        if size > self.size {
+            let zeros = vec![0; (size - self.size) as usize];
+            self.offset = self.size;
+            self.write(&zeros)?;
+            return Ok(());
+        }
+
+        // The first block always stays, it's where the entry points.
+        let data_len = Block::new(0).data().len() as u32;
+        let kept = ((size + data_len - 1) / data_len).max(1);
+        let mut block = Block::read(self.addr);
+        for _ in 1..kept {
+            block = block.next().ok_or(())?;
+        }
+        let mut next = block.next();
+        block.set_next(0);
+        block.write();
+        while let Some(freed) = next {
+            BlockBitmap::free(freed.addr());
+            next = freed.next();
+        }
+
+        self.size = size;
+        self.offset = self.offset.min(size);
+        self.dir.update_entry(&self.name, self.size);
+        Ok(())
+    }
+
     pub fn addr(&self) -> u32 {
         self.addr
     }
 
     pub fn delete(pathname: &str) -> Result<(), ()> {
-        let pathname = realpath(pathname);
+        let pathname = resolve(pathname, false).ok_or(())?;
         let dirname = dirname(&pathname);
         let filename = filename(&pathname);
         if let Some(mut dir) = Dir::open(dirname) {
@@ -219,6 +361,63 @@ impl File {
     }
 }
 
+/// Creates a symlink at `pathname` pointing at `target`, which is not checked.
+pub fn symlink(target: &str, pathname: &str) -> Result<(), ()> {
+    // The target is kept in the first block of the entry.
+    if target.is_empty() || target.len() > Block::new(0).data().len() {
+        return Err(());
+    }
+    let pathname = resolve(pathname, false).ok_or(())?;
+    let mut dir = Dir::open(dirname(&pathname)).ok_or(())?;
+    let entry = dir.create_entry(FileType::Symlink, filename(&pathname)).ok_or(())?;
+    let mut block = Block::read(entry.addr);
+    block.data_mut()[0..target.len()].copy_from_slice(target.as_bytes());
+    block.write();
+    dir.update_entry(&entry.name, target.len() as u32);
+    Ok(())
+}
+
+pub fn read_link(pathname: &str) -> Option<String> {
+    let entry = entry(pathname)?;
+    if entry.is_symlink() { Some(entry.target()) } else { None }
+}
+
+/// Adds the name `pathname` for the file at `target`. Directories can't be
+/// hard linked, so the tree stays a tree.
+pub fn link(target: &str, pathname: &str) -> Result<(), ()> {
+    let target = entry(target).ok_or(())?;
+    if target.is_dir() {
+        return Err(());
+    }
+    let pathname = resolve(pathname, false).ok_or(())?;
+    let dir = Dir::open(dirname(&pathname)).ok_or(())?;
+    if target.links == u8::MAX {
+        return Err(());
+    }
+    let links = target.links + 1;
+    dir.insert_entry(&target, filename(&pathname))?;
+    update_links(target.addr, |header| header[23] = links);
+    Ok(())
+}
+
+/// Calls `f` with the header of every entry pointing at `addr`.
+fn update_links<F: FnMut(&mut [u8])>(addr: u32, mut f: F) {
+    let mut dirs = vec![Dir::root()];
+    while let Some(dir) = dirs.pop() {
+        let mut read_dir = dir.read();
+        while let Some(entry) = read_dir.next() {
+            if entry.addr == addr {
+                let i = read_dir.data_offset - entry.len();
+                f(&mut read_dir.block.data_mut()[i..i + DIR_ENTRY_HEADER]);
+                read_dir.block.write();
+            }
+            if entry.is_dir() {
+                dirs.push(entry.to_dir());
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Block {
     addr: u32,
@@ -379,13 +578,34 @@ pub struct DirEntry {
     addr: u32,
     size: u32,
     time: u64,
+    mode: u16,
+    uid: u16,
+    gid: u16,
+    links: u8,
     name: String,
 }
 
 impl DirEntry {
     pub fn new(dir: Dir, kind: FileType, addr: u32, size: u32, time: u64, name: &str) -> Self {
         let name = String::from(name);
-        Self { dir, kind, addr, size, time, name }
+        let mode = match kind {
+            FileType::Dir => 0o755,
+            FileType::File => 0o644,
+            FileType::Symlink => 0o777,
+        };
+        Self { dir, kind, addr, size, time, mode, uid: 0, gid: 0, links: 1, name }
+    }
+
+    pub fn with_links(mut self, links: u8) -> Self {
+        self.links = links;
+        self
+    }
+
+    pub fn with_owner(mut self, mode: u16, uid: u16, gid: u16) -> Self {
+        self.mode = mode;
+        self.uid = uid;
+        self.gid = gid;
+        self
     }
 
     pub fn is_dir(&self) -> bool {
@@ -396,6 +616,22 @@ impl DirEntry {
         self.kind == FileType::File
     }
 
+    pub fn is_symlink(&self) -> bool {
+        self.kind == FileType::Symlink
+    }
+
+    pub fn links(&self) -> u8 {
+        self.links
+    }
+
+    /// The path a symlink points at.
+    pub fn target(&self) -> String {
+        assert!(self.kind == FileType::Symlink);
+        let block = Block::read(self.addr);
+        let n = (self.size as usize).min(block.data().len());
+        String::from_utf8_lossy(&block.data()[0..n]).into_owned()
+    }
+
     pub fn size(&self) -> u32 {
         self.size
     }
@@ -408,6 +644,22 @@ impl DirEntry {
         self.name.clone()
     }
 
+    pub fn addr(&self) -> u32 {
+        self.addr
+    }
+
+    pub fn mode(&self) -> u16 {
+        self.mode
+    }
+
+    pub fn uid(&self) -> u16 {
+        self.uid
+    }
+
+    pub fn gid(&self) -> u16 {
+        self.gid
+    }
+
     pub fn to_dir(&self) -> Dir {
         assert!(self.kind == FileType::Dir);
         Dir { addr: self.addr }
@@ -426,7 +678,7 @@ impl DirEntry {
     }
 
     pub fn len(&self) -> usize {
-        1 + 4 + 4 + 8 + 1 + self.name.len()
+        entry_header() + self.name.len()
     }
 }
 
@@ -441,7 +693,7 @@ impl Dir {
     }
 
     pub fn create(pathname: &str) -> Option<Self> {
-        let pathname = realpath(pathname);
+        let pathname = resolve(pathname, false)?;
         let dirname = dirname(&pathname);
         let filename = filename(&pathname);
         if let Some(dir) = Dir::open(dirname) {
@@ -453,13 +705,13 @@ impl Dir {
     }
 
     pub fn open(pathname: &str) -> Option<Self> {
-        let pathname = realpath(pathname);
-        let mut dir = Dir::root();
-
         if !is_mounted() {
             return None;
         }
 
+        let pathname = resolve(pathname, true)?;
+        let mut dir = Dir::root();
+
         if pathname == "/" {
             return Some(dir);
         }
@@ -502,15 +754,50 @@ impl Dir {
         self.create_entry(FileType::Dir, name)
     }
 
+    /// Adds an entry named `name` sharing the address and attributes of `target`.
+    fn insert_entry(&self, target: &DirEntry, name: &str) -> Result<(), ()> {
+        if is_read_only() || self.find(name).is_some() || name.len() > u8::MAX as usize {
+            return Err(());
+        }
+
+        let mut read_dir = self.read();
+        while read_dir.next().is_some() {}
+
+        if read_dir.block.data().len() - read_dir.data_offset < DIR_ENTRY_HEADER + name.len() {
+            let new_block = Block::alloc().ok_or(())?;
+            read_dir.block.set_next(new_block.addr);
+            read_dir.block.write();
+            read_dir.block = new_block;
+            read_dir.data_offset = 0;
+        }
+
+        let entry_name = name.as_bytes();
+        let n = entry_name.len();
+        let i = read_dir.data_offset;
+        let data = read_dir.block.data_mut();
+        data[i] = target.kind as u8;
+        data[i + 1..i + 5].copy_from_slice(&target.addr.to_be_bytes());
+        data[i + 5..i + 9].copy_from_slice(&target.size.to_be_bytes());
+        data[i + 9..i + 17].copy_from_slice(&target.time.to_be_bytes());
+        data[i + 17..i + 19].copy_from_slice(&target.mode.to_be_bytes());
+        data[i + 19..i + 21].copy_from_slice(&target.uid.to_be_bytes());
+        data[i + 21..i + 23].copy_from_slice(&target.gid.to_be_bytes());
+        data[i + 23] = target.links;
+        data[i + 24] = n as u8;
+        data[i + DIR_ENTRY_HEADER..i + DIR_ENTRY_HEADER + n].copy_from_slice(entry_name);
+        read_dir.block.write();
+        Ok(())
+    }
+
     fn create_entry(&self, kind: FileType, name: &str) -> Option<DirEntry> {
-        if self.find(name).is_some() {
+        if is_read_only() || self.find(name).is_some() {
             return None;
         }
 
         let mut read_dir = self.read();
         while read_dir.next().is_some() {}
 
-        if read_dir.block.data().len() - read_dir.data_offset < name.len() + 10 {
+        if read_dir.block.data().len() - read_dir.data_offset < DIR_ENTRY_HEADER + name.len() {
             let new_block = Block::alloc().unwrap(); // TODO
             read_dir.block.set_next(new_block.addr);
             read_dir.block.write();
@@ -525,6 +812,10 @@ impl Dir {
         let entry_time = 0 as u64; // TODO(George, 06/08/21): Add RTC Implementation 
         let entry_addr = new_block.addr();
         let entry_name = name.as_bytes();
+        let entry_mode = DirEntry::new(*self, kind, 0, 0, 0, name).mode;
+        let credentials = task::credentials();
+        let entry_uid = credentials.uid as u16;
+        let entry_gid = credentials.gid as u16;
 
         let n = entry_name.len();
         let i = read_dir.data_offset;
@@ -546,21 +837,35 @@ impl Dir {
         data[i + 14] = entry_time.get_bits(16..24) as u8;
         data[i + 15] = entry_time.get_bits(8..16) as u8;
         data[i + 16] = entry_time.get_bits(0..8) as u8;
-        data[i + 17] = n as u8;
+        data[i + 17] = entry_mode.get_bits(8..16) as u8;
+        data[i + 18] = entry_mode.get_bits(0..8) as u8;
+        data[i + 19] = entry_uid.get_bits(8..16) as u8;
+        data[i + 20] = entry_uid.get_bits(0..8) as u8;
+        data[i + 21] = entry_gid.get_bits(8..16) as u8;
+        data[i + 22] = entry_gid.get_bits(0..8) as u8;
+        data[i + 23] = 1;
+        data[i + 24] = n as u8;
         for j in 0..n {
-            data[i + 18 + j] = entry_name[j];
+            data[i + DIR_ENTRY_HEADER + j] = entry_name[j];
         }
         read_dir.block.write();
 
-        Some(DirEntry::new(*self, kind, entry_addr, entry_size, entry_time, name))
+        let entry = DirEntry::new(*self, kind, entry_addr, entry_size, entry_time, name);
+        Some(entry.with_owner(entry_mode, entry_uid, entry_gid))
     }
 
     // Deleting an entry is done by setting the entry address to 0
-    // TODO: If the entry is a directory, remove its entries recursively
+    // A directory must be emptied first, or its entries' blocks would leak.
     pub fn delete_entry(&mut self, name: &str) -> Result<(), ()> {
+        if is_read_only() {
+            return Err(());
+        }
         let mut read_dir = self.read();
         for entry in &mut read_dir {
             if entry.name == name {
+                if entry.is_dir() && entry.to_dir().read().next().is_some() {
+                    return Err(());
+                }
                 // Zeroing entry addr
                 let data = read_dir.block.data_mut();
                 let i = read_dir.data_offset - entry.len();
@@ -570,6 +875,13 @@ impl Dir {
                 data[i + 4] = 0;
                 read_dir.block.write();
 
+                // Other names still reference the blocks
+                if entry.links > 1 {
+                    let links = entry.links - 1;
+                    update_links(entry.addr, |header| header[23] = links);
+                    return Ok(());
+                }
+
                 // Freeing entry blocks
                 let mut entry_block = Block::read(entry.addr);
                 loop {
@@ -587,28 +899,42 @@ impl Dir {
     }
 
     fn update_entry(&mut self, name: &str, size: u32) {
+        let time = 0 as u64;
+        let update = |data: &mut [u8]| {
+            data[5..9].copy_from_slice(&size.to_be_bytes());
+            data[9..17].copy_from_slice(&time.to_be_bytes());
+        };
+        self.update_inode(name, update);
+    }
+
+    /// Applies `f` to the header of the entry `name`, and of its other hard links.
+    fn update_inode<F: FnMut(&mut [u8])>(&mut self, name: &str, mut f: F) -> Option<()> {
+        if is_read_only() {
+            return None;
+        }
         let mut read_dir = self.read();
-        for entry in &mut read_dir {
+        while let Some(entry) = read_dir.next() {
             if entry.name == name {
-                let time = 0 as u64;
-                let data = read_dir.block.data_mut();
-                let i = read_dir.data_offset - entry.len();
-                data[i +  5] = size.get_bits(24..32) as u8;
-                data[i +  6] = size.get_bits(16..24) as u8;
-                data[i +  7] = size.get_bits(8..16) as u8;
-                data[i +  8] = size.get_bits(0..8) as u8;
-                data[i +  9] = time.get_bits(56..64) as u8;
-                data[i + 10] = time.get_bits(48..56) as u8;
-                data[i + 11] = time.get_bits(40..48) as u8;
-                data[i + 12] = time.get_bits(32..40) as u8;
-                data[i + 13] = time.get_bits(24..32) as u8;
-                data[i + 14] = time.get_bits(16..24) as u8;
-                data[i + 15] = time.get_bits(8..16) as u8;
-                data[i + 16] = time.get_bits(0..8) as u8;
-                read_dir.block.write();
-                break;
+                if entry.links > 1 {
+                    update_links(entry.addr, f);
+                } else {
+                    let i = read_dir.data_offset - entry.len();
+                    f(&mut read_dir.block.data_mut()[i..i + DIR_ENTRY_HEADER]);
+                    read_dir.block.write();
+                }
+                return Some(());
             }
         }
+        None
+    }
+
+    pub fn update_attributes(&mut self, name: &str, mode: u16, uid: u16, gid: u16) -> Result<(), ()> {
+        let update = |data: &mut [u8]| {
+            data[17..19].copy_from_slice(&mode.to_be_bytes());
+            data[19..21].copy_from_slice(&uid.to_be_bytes());
+            data[21..23].copy_from_slice(&gid.to_be_bytes());
+        };
+        self.update_inode(name, update).ok_or(())
     }
 
     pub fn read(&self) -> ReadDir {
@@ -620,7 +946,7 @@ impl Dir {
     }
 
     pub fn delete(pathname: &str) -> Result<(), ()> {
-        let pathname = realpath(pathname);
+        let pathname = resolve(pathname, false).ok_or(())?;
         let dirname = dirname(&pathname);
         let filename = filename(&pathname);
         if let Some(mut dir) = Dir::open(dirname) {
@@ -645,14 +971,16 @@ impl Iterator for ReadDir {
             let data = self.block.data();
             let mut i = self.data_offset;
 
+            let header = entry_header();
             loop {
-                if i == data.len() - 10 { // No space left for another entry in the block
+                if i + header > data.len() { // No space left for another entry in the block
                     break;
                 }
 
                 let entry_kind = match data[i + 0] {
                     0 => FileType::Dir,
                     1 => FileType::File,
+                    2 => FileType::Symlink,
                     _ => break,
                 };
 
@@ -674,7 +1002,17 @@ impl Iterator for ReadDir {
                                | (data[i + 14] as u64) << 16
                                | (data[i + 15] as u64) << 8
                                | (data[i + 16] as u64);
-                i += 17;
+
+                // Older layouts stop after the time, or after the owner.
+                let owner = if header > 18 {
+                    Some(((data[i + 17] as u16) << 8 | (data[i + 18] as u16),
+                          (data[i + 19] as u16) << 8 | (data[i + 20] as u16),
+                          (data[i + 21] as u16) << 8 | (data[i + 22] as u16)))
+                } else {
+                    None
+                };
+                let entry_links = if header > 24 { data[i + 23] } else { 1 };
+                i += header - 1;
 
                 let mut n = data[i];
                 if n == 0 || n as usize >= data.len() - i {
@@ -700,7 +1038,11 @@ impl Iterator for ReadDir {
                     continue;
                 }
 
-                return Some(DirEntry::new(self.dir, entry_kind, entry_addr, entry_size, entry_time, &entry_name));
+                let mut entry = DirEntry::new(self.dir, entry_kind, entry_addr, entry_size, entry_time, &entry_name);
+                if let Some((mode, uid, gid)) = owner {
+                    entry = entry.with_owner(mode, uid, gid);
+                }
+                return Some(entry.with_links(entry_links));
             }
 
             match self.block.next() {
@@ -740,13 +1082,19 @@ pub fn is_mounted() -> bool {
 }
 
 pub fn mount(bus: u8, dsk: u8) {
+    mount_version(bus, dsk, FORMAT_VERSION);
+}
+
+fn mount_version(bus: u8, dsk: u8, version: u8) {
     let block_device = BlockDevice::new(bus, dsk);
     *BLOCK_DEVICE.lock() = Some(block_device);
+    MOUNTED_VERSION.store(version, Ordering::SeqCst);
 }
 
 pub fn format(bus: u8, dsk: u8) {
     // Write superblock
     let mut buf = MAGIC.as_bytes().to_vec();
+    buf.push(FORMAT_VERSION);
     buf.resize(512, 0);
     let block_device = BlockDevice::new(bus, dsk);
     block_device.write(SUPERBLOCK_ADDR, &buf);
@@ -765,11 +1113,158 @@ pub fn init() {
             crate::kernel::hardware::ata::read_raw(bus, dsk, SUPERBLOCK_ADDR, &mut buf);
             if let Ok(header) = String::from_utf8(buf[0..8].to_vec()) {
                 if header == MAGIC {
-                    println!("MFS Superblock found in ATA {}:{}\n", bus, dsk);
-                    mount(bus, dsk);
+                    // Writing would mix layouts, so older disks are only read.
+                    match buf[VERSION_OFFSET] {
+                        FORMAT_VERSION => {
+                            println!("MFS Superblock found in ATA {}:{}\n", bus, dsk);
+                            mount(bus, dsk);
+                        },
+                        version @ 0 | version @ 2 => {
+                            println!("MFS Superblock found in ATA {}:{} has format version {}, mounting read-only\n",
+                                bus, dsk, version);
+                            mount_version(bus, dsk, version);
+                        },
+                        version => {
+                            println!("MFS Superblock found in ATA {}:{} has format version {}, expected {} - not mounting\n",
+                                bus, dsk, version, FORMAT_VERSION);
+                        },
+                    }
                 }
             }
         }
     }
 }
 
+
+/// The directory entry for `pathname`, or `None` for the root and missing paths.
+/// A symlink in the last component is not followed.
+pub fn entry(pathname: &str) -> Option<DirEntry> {
+    let pathname = resolve(pathname, false)?;
+    Dir::open(dirname(&pathname))?.find(filename(&pathname))
+}
+
+fn node_kind(entry: &DirEntry) -> vfs::NodeKind {
+    match entry.kind {
+        FileType::Dir => vfs::NodeKind::Directory,
+        FileType::File => vfs::NodeKind::File,
+        FileType::Symlink => vfs::NodeKind::Symlink,
+    }
+}
+
+fn check_writable() -> Result<(), &'static str> {
+    if is_read_only() { Err("Read-Only Filesystem") } else { Ok(()) }
+}
+
+/// The mounted MOROS filesystem, as seen through `vfs`.
+pub struct MorosFs;
+
+impl vfs::FileSystem for MorosFs {
+    fn name(&self) -> &'static str {
+        "morosfs"
+    }
+
+    // `vfs` follows symlinks itself, so it needs to see them here.
+    fn metadata(&self, pathname: &str) -> Result<vfs::Metadata, &'static str> {
+        let pathname = resolve(pathname, false).ok_or("No Such File Or Directory")?;
+        if pathname == "/" {
+            let mut meta = vfs::Metadata::new(vfs::NodeKind::Directory);
+            meta.inode = Dir::root().addr() as u64;
+            return Ok(meta);
+        }
+        let entry = entry(&pathname).ok_or("No Such File Or Directory")?;
+        let mut meta = vfs::Metadata::new(node_kind(&entry));
+        meta.inode = entry.addr() as u64;
+        meta.size = entry.size() as usize;
+        meta.mtime = entry.time();
+        meta.mode = entry.mode() as u32;
+        meta.uid = entry.uid() as u32;
+        meta.gid = entry.gid() as u32;
+        meta.links = entry.links() as u32;
+        Ok(meta)
+    }
+
+    fn read_dir(&self, pathname: &str) -> Result<Vec<vfs::DirEntry>, &'static str> {
+        let dir = Dir::open(pathname).ok_or("Not A Directory")?;
+        Ok(dir.read().map(|entry| vfs::DirEntry {
+            name: entry.name(),
+            kind: node_kind(&entry),
+            size: entry.size() as usize,
+        }).collect())
+    }
+
+    fn read(&mut self, pathname: &str, offset: usize, buf: &mut [u8]) -> Result<usize, &'static str> {
+        let mut file = File::open(pathname).ok_or("No Such File")?;
+        if offset >= file.size() {
+            return Ok(0);
+        }
+        file.seek(SeekFrom::Start(offset as u32)).map_err(|_| "Invalid Offset")?;
+        Ok(file.read(buf))
+    }
+
+    fn write(&mut self, pathname: &str, offset: usize, buf: &[u8]) -> Result<usize, &'static str> {
+        check_writable()?;
+        let mut file = File::open(pathname).ok_or("No Such File")?;
+        file.seek(SeekFrom::Start(offset as u32)).map_err(|_| "Invalid Offset")?;
+        file.write(buf).map_err(|_| "Disk Is Full")
+    }
+
+    fn truncate(&mut self, pathname: &str, size: usize) -> Result<(), &'static str> {
+        check_writable()?;
+        let mut file = File::open(pathname).ok_or("No Such File")?;
+        file.truncate(size).map_err(|_| "Disk Is Full")
+    }
+
+    fn create(&mut self, pathname: &str, kind: vfs::NodeKind) -> Result<(), &'static str> {
+        check_writable()?;
+        let created = match kind {
+            vfs::NodeKind::File => File::create(pathname).is_some(),
+            vfs::NodeKind::Directory => Dir::create(pathname).is_some(),
+            _ => return Err("Operation Not Supported"),
+        };
+        if created { Ok(()) } else { Err("Unable To Create") }
+    }
+
+    fn remove(&mut self, pathname: &str) -> Result<(), &'static str> {
+        check_writable()?;
+        let entry = entry(pathname).ok_or("No Such File Or Directory")?;
+        if entry.is_dir() && entry.to_dir().read().next().is_some() {
+            return Err("Directory Not Empty");
+        }
+        File::delete(pathname).map_err(|_| "No Such File Or Directory")
+    }
+
+    fn set_mode(&mut self, pathname: &str, mode: u32) -> Result<(), &'static str> {
+        check_writable()?;
+        let pathname = resolve(pathname, false).ok_or("No Such File Or Directory")?;
+        let entry = entry(&pathname).ok_or("Operation Not Supported")?;
+        let mut dir = Dir::open(dirname(&pathname)).ok_or("No Such File Or Directory")?;
+        dir.update_attributes(filename(&pathname), mode as u16, entry.uid(), entry.gid())
+            .map_err(|_| "No Such File Or Directory")
+    }
+
+    fn set_owner(&mut self, pathname: &str, uid: u32, gid: u32) -> Result<(), &'static str> {
+        check_writable()?;
+        if uid > u16::MAX as u32 || gid > u16::MAX as u32 {
+            return Err("Id Out Of Range");
+        }
+        let pathname = resolve(pathname, false).ok_or("No Such File Or Directory")?;
+        let entry = entry(&pathname).ok_or("Operation Not Supported")?;
+        let mut dir = Dir::open(dirname(&pathname)).ok_or("No Such File Or Directory")?;
+        dir.update_attributes(filename(&pathname), entry.mode(), uid as u16, gid as u16)
+            .map_err(|_| "No Such File Or Directory")
+    }
+
+    fn symlink(&mut self, target: &str, pathname: &str) -> Result<(), &'static str> {
+        check_writable()?;
+        symlink(target, pathname).map_err(|_| "Unable To Create")
+    }
+
+    fn link(&mut self, target: &str, pathname: &str) -> Result<(), &'static str> {
+        check_writable()?;
+        link(target, pathname).map_err(|_| "Unable To Create")
+    }
+
+    fn read_link(&self, pathname: &str) -> Result<String, &'static str> {
+        read_link(pathname).ok_or("Not A Symlink")
+    }
+}
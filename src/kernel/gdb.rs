@@ -0,0 +1,510 @@
+//! A GDB Remote Serial Protocol stub on COM2.
+//!
+//! Run QEMU with a second serial port, e.g. `-serial stdio -serial pty`, then
+//! `target remote /dev/pts/N` from gdb. Sending Ctrl+C (or any packet) while the
+//! kernel is running stops it through the COM2 interrupt.
+
+use core::arch::asm;
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::{string::String, vec::Vec};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::port::{Port, PortReadOnly};
+use x86_64::registers::control::{Cr0, Cr0Flags};
+use uart_16550::SerialPort;
+
+use crate::kernel::arch::x64::trap::{TrapFrame, BREAKPOINT_VECTOR, DEBUG_VECTOR, TRAP_FLAG};
+use crate::kernel::arch::x64::mem;
+use crate::kernel::InitResult;
+
+pub const COM2 : u16 = 0x2F8;
+pub const COM2_IRQ : u8 = 3;
+
+const SIGTRAP : u8 = 5;
+const INT3 : u8 = 0xCC;
+const MAX_PACKET : usize = 0x1000;
+
+static ENABLED : AtomicBool = AtomicBool::new(false);
+
+struct Breakpoint {
+    address  : u64,
+    original : u8,
+}
+
+lazy_static! {
+    static ref BREAKPOINTS : Mutex<Vec<Breakpoint>> = Mutex::new(Vec::new());
+}
+
+/// Polled access to COM2. The stub only runs with interrupts disabled, so it
+/// never goes through the interrupt-driven serial driver.
+struct GdbPort {
+    data        : Port<u8>,
+    line_status : PortReadOnly<u8>,
+}
+
+impl GdbPort {
+    fn new() -> Self {
+        Self {
+            data : Port::new(COM2),
+            line_status : PortReadOnly::new(COM2 + 5),
+        }
+    }
+
+    fn has_data(&mut self) -> bool {
+        unsafe { self.line_status.read() & 0x01 != 0 }
+    }
+
+    fn read(&mut self) -> u8 {
+        while !self.has_data() {
+            spin_loop();
+        }
+        unsafe { self.data.read() }
+    }
+
+    fn write(&mut self, byte : u8) {
+        while unsafe { self.line_status.read() } & 0x20 == 0 {
+            spin_loop();
+        }
+        unsafe { self.data.write(byte) }
+    }
+
+    /// Waits for a `$data#cs` packet with a valid checksum, acknowledging it.
+    fn receive_packet(&mut self) -> Vec<u8> {
+        loop {
+            while self.read() != b'$' {}
+
+            let mut packet = Vec::new();
+            let mut sum : u8 = 0;
+            loop {
+                let byte = self.read();
+                if byte == b'#' {
+                    break;
+                }
+                sum = sum.wrapping_add(byte);
+                if packet.len() < MAX_PACKET {
+                    packet.push(byte);
+                }
+            }
+
+            let checksum = (hex_value(self.read()) << 4) | hex_value(self.read());
+            if checksum == sum {
+                self.write(b'+');
+                return packet;
+            }
+            self.write(b'-');
+        }
+    }
+
+    fn send_packet(&mut self, data : &[u8]) {
+        loop {
+            let mut sum : u8 = 0;
+            self.write(b'$');
+            for &byte in data {
+                sum = sum.wrapping_add(byte);
+                self.write(byte);
+            }
+            self.write(b'#');
+            self.write(HEX_DIGITS[(sum >> 4) as usize]);
+            self.write(HEX_DIGITS[(sum & 0xF) as usize]);
+
+            match self.read() {
+                b'+' => return,
+                _ => continue,
+            }
+        }
+    }
+}
+
+const HEX_DIGITS : &[u8; 16] = b"0123456789abcdef";
+
+fn hex_value(digit : u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        b'A'..=b'F' => digit - b'A' + 10,
+        _ => 0,
+    }
+}
+
+fn push_hex_bytes(out : &mut String, bytes : &[u8]) {
+    for &byte in bytes {
+        out.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+        out.push(HEX_DIGITS[(byte & 0xF) as usize] as char);
+    }
+}
+
+fn parse_hex(text : &[u8]) -> Option<u64> {
+    if text.is_empty() {
+        return None;
+    }
+    let mut value : u64 = 0;
+    for &digit in text {
+        if !digit.is_ascii_hexdigit() {
+            return None;
+        }
+        value = value.checked_mul(16)? | hex_value(digit) as u64;
+    }
+    Some(value)
+}
+
+fn decode_hex_bytes(text : &[u8]) -> Vec<u8> {
+    text.chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| (hex_value(pair[0]) << 4) | hex_value(pair[1]))
+        .collect()
+}
+
+/// Splits "addr,len" (optionally followed by ":data") into its parts.
+fn parse_address_length(args : &[u8]) -> Option<(u64, u64, &[u8])> {
+    let (range, data) = match args.iter().position(|&b| b == b':') {
+        Some(colon) => (&args[..colon], &args[colon + 1..]),
+        None => (args, &args[args.len()..]),
+    };
+    let comma = range.iter().position(|&b| b == b',')?;
+    Some((parse_hex(&range[..comma])?, parse_hex(&range[comma + 1..])?, data))
+}
+
+// The register numbering of gdb's i386:x86-64 architecture. 0..=16 are 64 bits
+// wide, the flags and segment registers after them 32.
+const GENERAL_REGISTERS : usize = 17;
+const REGISTER_COUNT : usize = 24;
+
+fn register(frame : &TrapFrame, index : usize) -> Option<u64> {
+    Some(match index {
+        0 => frame.rax,
+        1 => frame.rbx,
+        2 => frame.rcx,
+        3 => frame.rdx,
+        4 => frame.rsi,
+        5 => frame.rdi,
+        6 => frame.rbp,
+        7 => frame.rsp,
+        8 => frame.r8,
+        9 => frame.r9,
+        10 => frame.r10,
+        11 => frame.r11,
+        12 => frame.r12,
+        13 => frame.r13,
+        14 => frame.r14,
+        15 => frame.r15,
+        16 => frame.rip,
+        17 => frame.rflags,
+        18 => frame.cs,
+        19 => frame.ss,
+        20..=23 => 0, // ds, es, fs, gs are unused in long mode.
+        _ => return None,
+    })
+}
+
+fn set_register(frame : &mut TrapFrame, index : usize, value : u64) -> bool {
+    let slot = match index {
+        0 => &mut frame.rax,
+        1 => &mut frame.rbx,
+        2 => &mut frame.rcx,
+        3 => &mut frame.rdx,
+        4 => &mut frame.rsi,
+        5 => &mut frame.rdi,
+        6 => &mut frame.rbp,
+        7 => &mut frame.rsp,
+        8 => &mut frame.r8,
+        9 => &mut frame.r9,
+        10 => &mut frame.r10,
+        11 => &mut frame.r11,
+        12 => &mut frame.r12,
+        13 => &mut frame.r13,
+        14 => &mut frame.r14,
+        15 => &mut frame.r15,
+        16 => &mut frame.rip,
+        17 => &mut frame.rflags,
+        18..=23 => return true, // Segments can't be changed from here, ignore them.
+        _ => return false,
+    };
+    *slot = value;
+    true
+}
+
+fn register_width(index : usize) -> usize {
+    if index < GENERAL_REGISTERS { 8 } else { 4 }
+}
+
+fn push_register(out : &mut String, frame : &TrapFrame, index : usize) {
+    let value = register(frame, index).unwrap_or(0);
+    push_hex_bytes(out, &value.to_le_bytes()[..register_width(index)]);
+}
+
+fn read_le(bytes : &[u8]) -> u64 {
+    let mut value = 0;
+    for (i, &byte) in bytes.iter().enumerate().take(8) {
+        value |= (byte as u64) << (i * 8);
+    }
+    value
+}
+
+/// Runs `f` with CR0.WP cleared, so breakpoints can be written into read-only kernel text.
+fn with_write_protect_disabled<F : FnOnce()>(f : F) {
+    let flags = Cr0::read();
+    unsafe { Cr0::write(flags - Cr0Flags::WRITE_PROTECT) };
+    f();
+    unsafe { Cr0::write(flags) };
+}
+
+fn read_memory(address : u64, length : u64) -> Option<Vec<u8>> {
+    if !mem::is_mapped(address, length) {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(length as usize);
+    for i in 0..length {
+        bytes.push(unsafe { core::ptr::read_volatile((address + i) as *const u8) });
+    }
+    // Hide our own breakpoints from gdb.
+    for breakpoint in BREAKPOINTS.lock().iter() {
+        if breakpoint.address >= address && breakpoint.address < address + length {
+            bytes[(breakpoint.address - address) as usize] = breakpoint.original;
+        }
+    }
+    Some(bytes)
+}
+
+fn write_memory(address : u64, data : &[u8]) -> bool {
+    if !mem::is_mapped(address, data.len() as u64) {
+        return false;
+    }
+    with_write_protect_disabled(|| {
+        for (i, &byte) in data.iter().enumerate() {
+            unsafe { core::ptr::write_volatile((address + i as u64) as *mut u8, byte) };
+        }
+    });
+    true
+}
+
+fn insert_breakpoint(address : u64) -> bool {
+    let mut breakpoints = BREAKPOINTS.lock();
+    if breakpoints.iter().any(|b| b.address == address) {
+        return true;
+    }
+    if !mem::is_mapped(address, 1) {
+        return false;
+    }
+    let original = unsafe { core::ptr::read_volatile(address as *const u8) };
+    with_write_protect_disabled(|| unsafe { core::ptr::write_volatile(address as *mut u8, INT3) });
+    breakpoints.push(Breakpoint { address, original });
+    true
+}
+
+fn remove_breakpoint(address : u64) -> bool {
+    let mut breakpoints = BREAKPOINTS.lock();
+    match breakpoints.iter().position(|b| b.address == address) {
+        Some(index) => {
+            let breakpoint = breakpoints.remove(index);
+            with_write_protect_disabled(|| unsafe {
+                core::ptr::write_volatile(breakpoint.address as *mut u8, breakpoint.original)
+            });
+            true
+        },
+        None => false,
+    }
+}
+
+fn remove_all_breakpoints() {
+    let addresses : Vec<u64> = BREAKPOINTS.lock().iter().map(|b| b.address).collect();
+    for address in addresses {
+        remove_breakpoint(address);
+    }
+}
+
+enum Action {
+    Reply(String),
+    Resume,
+}
+
+fn ok() -> Action {
+    Action::Reply(String::from("OK"))
+}
+
+fn error(code : u8) -> Action {
+    let mut reply = String::from("E");
+    push_hex_bytes(&mut reply, &[code]);
+    Action::Reply(reply)
+}
+
+fn stop_reply(swbreak : bool) -> String {
+    let mut reply = String::from("T");
+    push_hex_bytes(&mut reply, &[SIGTRAP]);
+    if swbreak {
+        reply.push_str("swbreak:;");
+    }
+    reply
+}
+
+fn handle_packet(packet : &[u8], frame : &mut TrapFrame) -> Action {
+    let (&command, args) = match packet.split_first() {
+        Some(split) => split,
+        None => return Action::Reply(String::new()),
+    };
+
+    match command {
+        b'?' => Action::Reply(stop_reply(false)),
+        b'g' => {
+            let mut reply = String::new();
+            for index in 0..REGISTER_COUNT {
+                push_register(&mut reply, frame, index);
+            }
+            Action::Reply(reply)
+        },
+        b'G' => {
+            let bytes = decode_hex_bytes(args);
+            let mut offset = 0;
+            for index in 0..REGISTER_COUNT {
+                let width = register_width(index);
+                if offset + width > bytes.len() {
+                    break;
+                }
+                set_register(frame, index, read_le(&bytes[offset..offset + width]));
+                offset += width;
+            }
+            ok()
+        },
+        b'p' => match parse_hex(args).and_then(|index| register(frame, index as usize).map(|_| index as usize)) {
+            Some(index) => {
+                let mut reply = String::new();
+                push_register(&mut reply, frame, index);
+                Action::Reply(reply)
+            },
+            None => error(0),
+        },
+        b'P' => {
+            let equals = match args.iter().position(|&b| b == b'=') {
+                Some(equals) => equals,
+                None => return error(0),
+            };
+            match parse_hex(&args[..equals]) {
+                Some(index) if set_register(frame, index as usize, read_le(&decode_hex_bytes(&args[equals + 1..]))) => ok(),
+                _ => error(0),
+            }
+        },
+        b'm' => match parse_address_length(args).and_then(|(address, length, _)| read_memory(address, length)) {
+            Some(bytes) => {
+                let mut reply = String::new();
+                push_hex_bytes(&mut reply, &bytes);
+                Action::Reply(reply)
+            },
+            None => error(14),
+        },
+        b'M' => match parse_address_length(args) {
+            Some((address, length, data)) => {
+                let bytes = decode_hex_bytes(data);
+                if bytes.len() as u64 == length && write_memory(address, &bytes) { ok() } else { error(14) }
+            },
+            None => error(0),
+        },
+        b'Z' | b'z' => {
+            // Only software breakpoints: "Z0,addr,kind".
+            if args.first() != Some(&b'0') {
+                return Action::Reply(String::new());
+            }
+            let address = args.get(2..)
+                .and_then(|rest| rest.split(|&b| b == b',').next())
+                .and_then(parse_hex);
+            match address {
+                Some(address) => {
+                    let done = if command == b'Z' { insert_breakpoint(address) } else { remove_breakpoint(address) };
+                    if done { ok() } else { error(14) }
+                },
+                None => error(0),
+            }
+        },
+        b'c' | b's' => {
+            if let Some(address) = parse_hex(args) {
+                frame.rip = address;
+            }
+            if command == b's' {
+                frame.rflags |= TRAP_FLAG;
+            } else {
+                frame.rflags &= !TRAP_FLAG;
+            }
+            Action::Resume
+        },
+        b'D' | b'k' => {
+            remove_all_breakpoints();
+            frame.rflags &= !TRAP_FLAG;
+            if command == b'D' {
+                GdbPort::new().send_packet(b"OK");
+            }
+            Action::Resume
+        },
+        b'H' => ok(),
+        b'q' => {
+            if args.starts_with(b"Supported") {
+                Action::Reply(String::from("PacketSize=1000;swbreak+"))
+            } else if args == b"Attached" {
+                Action::Reply(String::from("1"))
+            } else if args == b"C" {
+                Action::Reply(String::from("QC1"))
+            } else if args == b"fThreadInfo" {
+                Action::Reply(String::from("m1"))
+            } else if args == b"sThreadInfo" {
+                Action::Reply(String::from("l"))
+            } else {
+                Action::Reply(String::new())
+            }
+        },
+        _ => Action::Reply(String::new()),
+    }
+}
+
+/// Called from the breakpoint and debug exception handlers. Talks to gdb until
+/// it tells us to continue, step or detach.
+pub fn on_trap(frame : &mut TrapFrame, vector : u8) {
+    let mut swbreak = false;
+    if vector == DEBUG_VECTOR {
+        frame.rflags &= !TRAP_FLAG;
+    }
+    if vector == BREAKPOINT_VECTOR {
+        // Report our own breakpoints at their address, not the byte after the int3.
+        let address = frame.rip.wrapping_sub(1);
+        if BREAKPOINTS.lock().iter().any(|b| b.address == address) {
+            frame.rip = address;
+            swbreak = true;
+        }
+    }
+
+    let mut port = GdbPort::new();
+    port.send_packet(stop_reply(swbreak).as_bytes());
+    loop {
+        let packet = port.receive_packet();
+        match handle_packet(&packet, frame) {
+            Action::Reply(reply) => port.send_packet(reply.as_bytes()),
+            Action::Resume => return,
+        }
+    }
+}
+
+/// Stops in the debugger, as if a breakpoint had been hit here.
+pub fn breakpoint() {
+    unsafe { asm!("int3") };
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// gdb sends Ctrl+C (0x03) to interrupt a running target; any other byte means it
+/// has just connected. Either way, stop and let the stub handle it.
+fn on_serial_interrupt(_irq : u8) {
+    let mut port = GdbPort::new();
+    if !port.has_data() {
+        return;
+    }
+    port.read();
+    breakpoint();
+}
+
+pub fn init() -> InitResult<()> {
+    unsafe { SerialPort::new(COM2).init() };
+    ENABLED.store(true, Ordering::SeqCst);
+    crate::kernel::arch::set_named_interrupt(COM2_IRQ, "gdb::on_serial_interrupt", on_serial_interrupt)
+        .expect("Unable To Setup GDB Interrupt");
+    Ok(())
+}
@@ -4,6 +4,7 @@ pub mod uart;
 pub mod vga_hw;
 pub mod cmos;
 pub mod ata;
+pub mod mouse;
 
 
 pub use x86_64::instructions::port::*;
@@ -0,0 +1,294 @@
+use alloc::collections::VecDeque;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+use x86_64::instructions::port::{Port, PortReadOnly};
+
+use crate::graphics;
+use crate::kernel::{InitResult, arch, task};
+use crate::warn;
+
+pub const MOUSE_IRQ : u8 = 12;
+/// Events kept for programs that haven't read them yet, the oldest are dropped first.
+pub const EVENT_BUFFER_SIZE : usize = 128;
+
+const DATA_PORT : u16 = 0x60;
+const STATUS_PORT : u16 = 0x64;
+const COMMAND_PORT : u16 = 0x64;
+
+const STATUS_OUTPUT_FULL : u8 = 1 << 0;
+const STATUS_INPUT_FULL  : u8 = 1 << 1;
+
+// Controller commands.
+const READ_CONFIG : u8 = 0x20;
+const WRITE_CONFIG : u8 = 0x60;
+const ENABLE_AUX : u8 = 0xA8;
+const WRITE_AUX : u8 = 0xD4;
+const CONFIG_AUX_IRQ : u8 = 1 << 1;
+const CONFIG_AUX_CLOCK_OFF : u8 = 1 << 5;
+
+// Mouse commands.
+const SET_DEFAULTS : u8 = 0xF6;
+const ENABLE_REPORTING : u8 = 0xF4;
+const SET_SAMPLE_RATE : u8 = 0xF3;
+const GET_ID : u8 = 0xF2;
+const ACK : u8 = 0xFA;
+/// ID reported by a mouse with a scroll wheel, after the IntelliMouse sample rate knock.
+const WHEEL_MOUSE_ID : u8 = 3;
+
+// First byte of every packet.
+const PACKET_ALWAYS_SET : u8 = 1 << 3;
+const PACKET_X_SIGN : u8 = 1 << 4;
+const PACKET_Y_SIGN : u8 = 1 << 5;
+const PACKET_OVERFLOW : u8 = 0b11 << 6;
+
+const TIMEOUT : usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+impl MouseButton {
+    const ALL : [MouseButton; 3] = [MouseButton::Left, MouseButton::Right, MouseButton::Middle];
+
+    fn mask(&self) -> u8 {
+        match self {
+            MouseButton::Left => 1 << 0,
+            MouseButton::Right => 1 << 1,
+            MouseButton::Middle => 1 << 2,
+        }
+    }
+}
+
+/// Something the mouse did, with where the cursor was afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEvent {
+    Move { x : usize, y : usize, dx : isize, dy : isize },
+    Press { button : MouseButton, x : usize, y : usize },
+    Release { button : MouseButton, x : usize, y : usize },
+    /// Wheel turned, negative away from the user.
+    Scroll { delta : isize, x : usize, y : usize },
+}
+
+struct Mouse {
+    data    : Port<u8>,
+    packet  : [u8; 4],
+    index   : usize,
+    packet_size : usize, // 4 with a wheel, 3 without.
+    x       : usize,
+    y       : usize,
+    buttons : u8,
+    events  : VecDeque<MouseEvent>,
+}
+
+impl Mouse {
+    fn new() -> Self {
+        Mouse {
+            data : Port::new(DATA_PORT),
+            packet : [0; 4],
+            index : 0,
+            packet_size : 3,
+            x : graphics::WIDTH / 2,
+            y : graphics::HEIGHT / 2,
+            buttons : 0,
+            events : VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, event : MouseEvent) {
+        if self.events.len() == EVENT_BUFFER_SIZE {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    fn add_byte(&mut self, byte : u8) {
+        // Resynchronise on a byte that can't start a packet.
+        if self.index == 0 && byte & PACKET_ALWAYS_SET == 0 {
+            return;
+        }
+        self.packet[self.index] = byte;
+        self.index += 1;
+        if self.index == self.packet_size {
+            self.index = 0;
+            self.decode();
+        }
+    }
+
+    fn decode(&mut self) {
+        let flags = self.packet[0];
+        if flags & PACKET_OVERFLOW == 0 {
+            let dx = self.packet[1] as isize - if flags & PACKET_X_SIGN != 0 { 0x100 } else { 0 };
+            // The mouse counts up as moving away from the user, the screen counts down.
+            let dy = -(self.packet[2] as isize - if flags & PACKET_Y_SIGN != 0 { 0x100 } else { 0 });
+            if dx != 0 || dy != 0 {
+                self.x = (self.x as isize + dx).clamp(0, graphics::WIDTH as isize - 1) as usize;
+                self.y = (self.y as isize + dy).clamp(0, graphics::HEIGHT as isize - 1) as usize;
+                self.push(MouseEvent::Move { x : self.x, y : self.y, dx, dy });
+            }
+        }
+
+        let buttons = flags & 0b111;
+        for button in MouseButton::ALL.iter().copied() {
+            let (was, is) = (self.buttons & button.mask() != 0, buttons & button.mask() != 0);
+            if is && !was {
+                self.push(MouseEvent::Press { button, x : self.x, y : self.y });
+            } else if was && !is {
+                self.push(MouseEvent::Release { button, x : self.x, y : self.y });
+            }
+        }
+        self.buttons = buttons;
+
+        if self.packet_size == 4 {
+            // A 4-bit two's complement count in the low bits.
+            let delta = ((self.packet[3] << 4) as i8 >> 4) as isize;
+            if delta != 0 {
+                self.push(MouseEvent::Scroll { delta, x : self.x, y : self.y });
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref MOUSE : Mutex<Mouse> = Mutex::new(Mouse::new());
+}
+
+/// Talks to the PS/2 controller while setting the mouse up.
+struct Controller {
+    data    : Port<u8>,
+    status  : PortReadOnly<u8>,
+    command : Port<u8>,
+}
+
+impl Controller {
+    fn new() -> Self {
+        Controller { data : Port::new(DATA_PORT), status : PortReadOnly::new(STATUS_PORT), command : Port::new(COMMAND_PORT) }
+    }
+
+    fn wait_input(&mut self) -> Result<(), &'static str> {
+        for _ in 0..TIMEOUT {
+            if unsafe { self.status.read() } & STATUS_INPUT_FULL == 0 {
+                return Ok(());
+            }
+        }
+        Err("PS/2 Controller Timed Out")
+    }
+
+    fn read(&mut self) -> Result<u8, &'static str> {
+        for _ in 0..TIMEOUT {
+            if unsafe { self.status.read() } & STATUS_OUTPUT_FULL != 0 {
+                return Ok(unsafe { self.data.read() });
+            }
+        }
+        Err("No Response From Mouse")
+    }
+
+    fn command(&mut self, command : u8) -> Result<(), &'static str> {
+        self.wait_input()?;
+        unsafe { self.command.write(command) };
+        Ok(())
+    }
+
+    fn write_data(&mut self, byte : u8) -> Result<(), &'static str> {
+        self.wait_input()?;
+        unsafe { self.data.write(byte) };
+        Ok(())
+    }
+
+    /// Sends a byte to the mouse and waits for it to acknowledge.
+    fn write_mouse(&mut self, byte : u8) -> Result<(), &'static str> {
+        self.command(WRITE_AUX)?;
+        self.write_data(byte)?;
+        match self.read()? {
+            ACK => Ok(()),
+            _ => Err("Mouse Refused Command"),
+        }
+    }
+
+    fn set_sample_rate(&mut self, rate : u8) -> Result<(), &'static str> {
+        self.write_mouse(SET_SAMPLE_RATE)?;
+        self.write_mouse(rate)
+    }
+
+    /// Enables the auxiliary port and its interrupt, then sets the mouse up,
+    /// returning its packet size.
+    fn setup(&mut self) -> Result<usize, &'static str> {
+        self.command(ENABLE_AUX)?;
+        self.command(READ_CONFIG)?;
+        let config = (self.read()? | CONFIG_AUX_IRQ) & !CONFIG_AUX_CLOCK_OFF;
+        self.command(WRITE_CONFIG)?;
+        self.write_data(config)?;
+
+        self.write_mouse(SET_DEFAULTS)?;
+        // Sample rates 200, 100, 80 switch an IntelliMouse into reporting its wheel.
+        for rate in [200, 100, 80].iter() {
+            self.set_sample_rate(*rate)?;
+        }
+        self.write_mouse(GET_ID)?;
+        let packet_size = if self.read()? == WHEEL_MOUSE_ID { 4 } else { 3 };
+        self.set_sample_rate(100)?;
+        self.write_mouse(ENABLE_REPORTING)?;
+        Ok(packet_size)
+    }
+}
+
+fn on_mouse_data(_irq : u8) {
+    without_interrupts(|| {
+        let mut mouse = MOUSE.lock();
+        let byte = unsafe { mouse.data.read() };
+        mouse.add_byte(byte);
+    });
+}
+
+/// Sets up a PS/2 mouse on IRQ12. A missing mouse is logged rather than failing boot.
+pub fn init() -> InitResult<()> {
+    let packet_size = without_interrupts(|| Controller::new().setup());
+    match packet_size {
+        Ok(packet_size) => {
+            without_interrupts(|| MOUSE.lock().packet_size = packet_size);
+            arch::set_named_interrupt(MOUSE_IRQ, "mouse::on_mouse_data", on_mouse_data)
+                .expect("Unable To Setup Mouse Interrupt");
+            arch::enable_irq(MOUSE_IRQ);
+        },
+        Err(error) => warn!("{}", error),
+    }
+    Ok(())
+}
+
+/// The next mouse event, if there is one.
+pub fn event() -> Option<MouseEvent> {
+    without_interrupts(|| MOUSE.lock().events.pop_front())
+}
+
+/// Waits for the next mouse event. Ctrl+C ends the waiting task.
+pub fn wait_event() -> MouseEvent {
+    let mut next = None;
+    task::block_until(|| {
+        next = event();
+        next.is_some()
+    });
+    next.unwrap()
+}
+
+/// Where the cursor is, within `graphics::WIDTH` x `graphics::HEIGHT`.
+pub fn position() -> (usize, usize) {
+    without_interrupts(|| {
+        let mouse = MOUSE.lock();
+        (mouse.x, mouse.y)
+    })
+}
+
+pub fn set_position(x : usize, y : usize) {
+    without_interrupts(|| {
+        let mut mouse = MOUSE.lock();
+        mouse.x = x.min(graphics::WIDTH - 1);
+        mouse.y = y.min(graphics::HEIGHT - 1);
+    });
+}
+
+pub fn is_pressed(button : MouseButton) -> bool {
+    without_interrupts(|| MOUSE.lock().buttons & button.mask() != 0)
+}
@@ -1,10 +1,16 @@
 use crate::kernel::InitResult;
 use pic8259::ChainedPics;
 use spin::Mutex;
+use x86_64::instructions::port::Port;
 
 pub const PIC_1_OFFSET: u8 = 32;
 pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 
+const PIC_1_COMMAND: u16 = 0x20;
+const PIC_2_COMMAND: u16 = 0xA0;
+const CMD_READ_ISR: u8 = 0x0B;
+const CMD_END_OF_INTERRUPT: u8 = 0x20;
+
 // Map PIC interrupts to 0x20 through 0x2f.
 static PICS: Mutex<ChainedPics> = {
     Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) })
@@ -17,4 +23,25 @@ pub fn init() -> InitResult<()> {
 
 pub fn notify_end_of_interrupt(index : u8) {
     unsafe {PICS.lock().notify_end_of_interrupt(index)};
-}
\ No newline at end of file
+}
+
+/// Acknowledges only the master PIC, used when the slave raised a spurious IRQ15.
+pub fn notify_master_end_of_interrupt() {
+    let mut command: Port<u8> = Port::new(PIC_1_COMMAND);
+    unsafe { command.write(CMD_END_OF_INTERRUPT) };
+}
+
+/// Reads the In-Service Register of both PICs, the slave in the high byte.
+pub fn read_isr() -> u16 {
+    let mut master: Port<u8> = Port::new(PIC_1_COMMAND);
+    let mut slave: Port<u8> = Port::new(PIC_2_COMMAND);
+    unsafe {
+        master.write(CMD_READ_ISR);
+        slave.write(CMD_READ_ISR);
+        (slave.read() as u16) << 8 | master.read() as u16
+    }
+}
+
+pub fn in_service(irq : u8) -> bool {
+    read_isr() & (1 << irq) != 0
+}
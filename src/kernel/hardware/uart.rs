@@ -1,24 +1,465 @@
 use core::fmt::*;
+use core::hint::spin_loop;
 
-pub use uart_16550::*;
 use lazy_static::lazy_static;
 use spin::Mutex;
+use x86_64::instructions::interrupts::{self, without_interrupts};
+use x86_64::instructions::port::Port;
+
+use crate::kernel::{task, InitResult};
+use crate::kernel::ring_buffer::RingBuffer;
+
+pub const BUFFER_SIZE : usize = 1024;
+pub const UART_CLOCK : u32 = 115200;
+
+// Register offsets from the port base.
+const DATA          : u16 = 0;
+const INT_ENABLE    : u16 = 1;
+const FIFO_CONTROL  : u16 = 2;
+const INT_ID        : u16 = 2;
+const LINE_CONTROL  : u16 = 3;
+const MODEM_CONTROL : u16 = 4;
+const LINE_STATUS   : u16 = 5;
+const SCRATCH       : u16 = 7;
+
+const IER_RX_AVAILABLE : u8 = 1 << 0;
+const IER_TX_EMPTY     : u8 = 1 << 1;
+const LSR_DATA_READY   : u8 = 1 << 0;
+const LSR_OVERRUN      : u8 = 1 << 1;
+const LSR_TX_EMPTY     : u8 = 1 << 5;
+const IIR_NONE_PENDING : u8 = 1 << 0;
+const LCR_DLAB         : u8 = 1 << 7;
+const FIFO_DEPTH       : usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComPort {
+    Com1 = 0,
+    Com2 = 1,
+    Com3 = 2,
+    Com4 = 3,
+}
+
+pub const COM_PORTS : [ComPort; 4] = [ComPort::Com1, ComPort::Com2, ComPort::Com3, ComPort::Com4];
+
+impl ComPort {
+    pub fn base(&self) -> u16 {
+        match self {
+            ComPort::Com1 => 0x3F8,
+            ComPort::Com2 => 0x2F8,
+            ComPort::Com3 => 0x3E8,
+            ComPort::Com4 => 0x2E8,
+        }
+    }
+
+    /// COM1/COM3 share IRQ4 and COM2/COM4 share IRQ3.
+    pub fn irq(&self) -> u8 {
+        match self {
+            ComPort::Com1 | ComPort::Com3 => 4,
+            ComPort::Com2 | ComPort::Com4 => 3,
+        }
+    }
+
+    pub fn from_index(index : usize) -> Option<ComPort> {
+        COM_PORTS.get(index).copied()
+    }
+}
+
+impl Display for ComPort {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "COM{}", *self as usize + 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+    Mark,
+    Space,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineConfig {
+    pub baud      : u32,
+    pub data_bits : u8, // 5..=8
+    pub parity    : Parity,
+    pub stop_bits : StopBits,
+}
+
+impl Default for LineConfig {
+    fn default() -> Self {
+        Self {
+            baud : 38400,
+            data_bits : 8,
+            parity : Parity::None,
+            stop_bits : StopBits::One,
+        }
+    }
+}
+
+impl LineConfig {
+    fn divisor(&self) -> u16 {
+        (UART_CLOCK / self.baud.max(1)).clamp(1, u16::MAX as u32) as u16
+    }
+
+    fn line_control(&self) -> u8 {
+        let data = self.data_bits.clamp(5, 8) - 5;
+        let stop = match self.stop_bits {
+            StopBits::One => 0,
+            StopBits::Two => 1 << 2,
+        };
+        let parity = match self.parity {
+            Parity::None => 0b000,
+            Parity::Odd => 0b001,
+            Parity::Even => 0b011,
+            Parity::Mark => 0b101,
+            Parity::Space => 0b111,
+        } << 3;
+        data | stop | parity
+    }
+}
+
+impl Display for LineConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Odd => 'O',
+            Parity::Even => 'E',
+            Parity::Mark => 'M',
+            Parity::Space => 'S',
+        };
+        let stop = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        write!(f, "{} {}{}{}", self.baud, self.data_bits, parity, stop)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UartStats {
+    pub received    : u64,
+    pub transmitted : u64,
+    pub dropped     : u64, // Bytes lost because the RX buffer was full.
+    pub overruns    : u64, // Bytes lost by the UART itself.
+}
+
+pub struct Uart {
+    port       : ComPort,
+    config     : LineConfig,
+    present    : bool,
+    interrupts : bool,
+    rx         : RingBuffer<u8, BUFFER_SIZE>,
+    tx         : RingBuffer<u8, BUFFER_SIZE>,
+    stats      : UartStats,
+}
+
+impl Uart {
+    fn new(port : ComPort) -> Self {
+        Self {
+            port,
+            config : LineConfig::default(),
+            present : false,
+            interrupts : false,
+            rx : RingBuffer::new(),
+            tx : RingBuffer::new(),
+            stats : UartStats::default(),
+        }
+    }
+
+    fn register(&self, offset : u16) -> Port<u8> {
+        Port::new(self.port.base() + offset)
+    }
+
+    fn read_register(&self, offset : u16) -> u8 {
+        unsafe { self.register(offset).read() }
+    }
+
+    fn write_register(&self, offset : u16, value : u8) {
+        unsafe { self.register(offset).write(value) }
+    }
+
+    /// The scratch register is absent when there's no UART at this address.
+    fn probe(&mut self) -> bool {
+        self.write_register(SCRATCH, 0x5A);
+        self.present = self.read_register(SCRATCH) == 0x5A;
+        self.present
+    }
+
+    fn configure(&mut self, config : LineConfig) {
+        let divisor = config.divisor();
+        self.write_register(INT_ENABLE, 0x00);
+        self.write_register(LINE_CONTROL, LCR_DLAB);
+        self.write_register(DATA, divisor as u8);
+        self.write_register(INT_ENABLE, (divisor >> 8) as u8);
+        self.write_register(LINE_CONTROL, config.line_control());
+        self.write_register(FIFO_CONTROL, 0xC7); // Enable and clear FIFOs, 14 byte RX threshold.
+        self.write_register(MODEM_CONTROL, 0x0B); // DTR, RTS and OUT2, which gates the IRQ.
+        self.config = config;
+        self.update_interrupts();
+    }
+
+    fn update_interrupts(&self) {
+        let mut enable = 0;
+        if self.interrupts {
+            enable |= IER_RX_AVAILABLE;
+            if !self.tx.is_empty() {
+                enable |= IER_TX_EMPTY;
+            }
+        }
+        self.write_register(INT_ENABLE, enable);
+    }
+
+    fn tx_ready(&self) -> bool {
+        self.read_register(LINE_STATUS) & LSR_TX_EMPTY != 0
+    }
+
+    fn poll_receive(&mut self) {
+        loop {
+            let status = self.read_register(LINE_STATUS);
+            if status & LSR_OVERRUN != 0 {
+                self.stats.overruns += 1;
+            }
+            if status & LSR_DATA_READY == 0 {
+                return;
+            }
+            let byte = self.read_register(DATA);
+            self.stats.received += 1;
+            if !self.rx.push(byte) {
+                self.stats.dropped += 1;
+            }
+        }
+    }
+
+    fn fill_fifo(&mut self) {
+        if !self.tx_ready() {
+            return;
+        }
+        for _ in 0..FIFO_DEPTH {
+            match self.tx.pop() {
+                Some(byte) => {
+                    self.write_register(DATA, byte);
+                    self.stats.transmitted += 1;
+                },
+                None => break,
+            }
+        }
+    }
+
+    fn send_polled(&mut self, byte : u8) {
+        while !self.tx_ready() {
+            spin_loop();
+        }
+        self.write_register(DATA, byte);
+        self.stats.transmitted += 1;
+    }
+
+    /// Drains the TX buffer by polling, for when interrupts can't do it.
+    fn flush_polled(&mut self) {
+        while let Some(byte) = self.tx.pop() {
+            self.send_polled(byte);
+        }
+    }
+
+    fn handle_interrupt(&mut self) {
+        while self.read_register(INT_ID) & IIR_NONE_PENDING == 0 {
+            self.poll_receive();
+            self.fill_fifo();
+        }
+        self.update_interrupts();
+    }
+
+    /// Buffers `byte` when interrupts will drain the buffer, otherwise writes it straight out.
+    /// `interrupts_enabled` is whether the caller had interrupts on before taking the lock.
+    fn send(&mut self, byte : u8, interrupts_enabled : bool) {
+        if !self.interrupts || !interrupts_enabled {
+            self.flush_polled();
+            self.send_polled(byte);
+            return;
+        }
+        if self.tx.is_full() {
+            self.flush_polled();
+        }
+        self.tx.push(byte);
+        self.fill_fifo();
+        self.update_interrupts();
+    }
+
+    /// `interrupts_enabled` is whether the caller had interrupts on before taking the lock.
+    fn receive(&mut self, interrupts_enabled : bool) -> Option<u8> {
+        if !self.interrupts || !interrupts_enabled {
+            self.poll_receive();
+        }
+        self.rx.pop()
+    }
+}
 
 lazy_static! {
-    static ref SERIAL : Mutex<SerialPort> = Mutex::new(unsafe {SerialPort::new(0x3f8)});
+    static ref UARTS : [Mutex<Uart>; 4] = [
+        Mutex::new(Uart::new(ComPort::Com1)),
+        Mutex::new(Uart::new(ComPort::Com2)),
+        Mutex::new(Uart::new(ComPort::Com3)),
+        Mutex::new(Uart::new(ComPort::Com4)),
+    ];
+}
+
+fn uart(port : ComPort) -> &'static Mutex<Uart> {
+    &UARTS[port as usize]
+}
+
+/// The kernel debugger owns COM2 when it's built in.
+fn is_reserved(port : ComPort) -> bool {
+    cfg!(feature = "gdbstub") && port == ComPort::Com2
+}
+
+pub fn is_present(port : ComPort) -> bool {
+    let mut present = false;
+    without_interrupts(|| {
+        present = uart(port).lock().present;
+    });
+    present
+}
+
+pub fn configure(port : ComPort, config : LineConfig) {
+    without_interrupts(|| {
+        let mut uart = uart(port).lock();
+        uart.flush_polled();
+        uart.configure(config);
+    });
+}
+
+pub fn config(port : ComPort) -> LineConfig {
+    let mut config = LineConfig::default();
+    without_interrupts(|| {
+        config = uart(port).lock().config;
+    });
+    config
+}
+
+pub fn stats(port : ComPort) -> UartStats {
+    let mut stats = UartStats::default();
+    without_interrupts(|| {
+        stats = uart(port).lock().stats;
+    });
+    stats
+}
+
+pub fn send(port : ComPort, byte : u8) {
+    let interrupts_enabled = interrupts::are_enabled();
+    without_interrupts(|| {
+        uart(port).lock().send(byte, interrupts_enabled);
+    });
+}
+
+/// Returns the next received byte, if there is one.
+pub fn try_receive(port : ComPort) -> Option<u8> {
+    let interrupts_enabled = interrupts::are_enabled();
+    let mut byte = None;
+    without_interrupts(|| {
+        byte = uart(port).lock().receive(interrupts_enabled);
+    });
+    byte
+}
+
+/// Waits for the next received byte. A caller with interrupts off has the port
+/// polled rather than them turned on behind its back.
+pub fn receive(port : ComPort) -> u8 {
+    if !interrupts::are_enabled() {
+        loop {
+            if let Some(byte) = try_receive(port) {
+                return byte;
+            }
+            spin_loop();
+        }
+    }
+    let mut byte = None;
+    task::block_until(|| {
+        byte = try_receive(port);
+        byte.is_some()
+    });
+    byte.unwrap()
+}
+
+pub fn available(port : ComPort) -> usize {
+    let mut count = 0;
+    without_interrupts(|| {
+        count = uart(port).lock().rx.len();
+    });
+    count
 }
 
 pub fn write_u8(byte : u8) {
-    SERIAL.lock().send(byte);
+    send(ComPort::Com1, byte);
 }
 
 pub fn read_u8() -> u8 {
-    SERIAL.lock().receive()
+    receive(ComPort::Com1)
+}
+
+pub fn try_read_u8() -> Option<u8> {
+    try_receive(ComPort::Com1)
+}
+
+struct PortWriter(ComPort);
+
+impl Write for PortWriter {
+    fn write_str(&mut self, s: &str) -> Result {
+        for byte in s.bytes() {
+            send(self.0, byte);
+        }
+        Ok(())
+    }
 }
 
 pub fn write_str(args : Arguments) {
-    SERIAL.lock().write_fmt(args).expect("| ERR | Unable To Write To Serial...");
+    PortWriter(ComPort::Com1).write_fmt(args).expect("| ERR | Unable To Write To Serial...");
 }
 
-pub fn on_serial_interrupt(_irq : u8) {
+pub fn write_fmt(port : ComPort, args : Arguments) -> Result {
+    PortWriter(port).write_fmt(args)
+}
+
+pub fn on_serial_interrupt(irq : u8) {
+    for port in COM_PORTS.iter() {
+        if port.irq() != irq || is_reserved(*port) {
+            continue;
+        }
+        let mut uart = uart(*port).lock();
+        if uart.present && uart.interrupts {
+            uart.handle_interrupt();
+        }
+    }
+}
+
+/// Probes COM1-COM4 and switches every port found over to interrupt-driven I/O.
+pub fn init() -> InitResult<()> {
+    let mut irqs = [false; 16];
+    for port in COM_PORTS.iter() {
+        if is_reserved(*port) {
+            continue;
+        }
+        without_interrupts(|| {
+            let mut uart = uart(*port).lock();
+            if uart.probe() {
+                uart.interrupts = true;
+                uart.configure(LineConfig::default());
+                irqs[port.irq() as usize] = true;
+            }
+        });
+    }
+
+    for (irq, used) in irqs.iter().enumerate() {
+        if *used {
+            crate::kernel::arch::set_named_interrupt(irq as u8, "uart::on_serial_interrupt", on_serial_interrupt)
+                .expect("Unable To Setup Serial Interrupt");
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,97 @@
+use alloc::{boxed::Box, string::String};
+
+use crate::kernel::{InitResult, fs, path::Path, vfs::{self, Metadata, NodeKind}};
+use crate::kernel::drivers::{ram_fs::RamFs, file_systems::ustar::{Archive, Entry, EntryKind}};
+use crate::log;
+
+/// The ustar archive built from `initramfs/` by `make initramfs`, or empty.
+static INITRAMFS : &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/initramfs.tar"));
+
+/// Where the ATA-backed `kernel::fs` is mounted when a disk has one.
+pub const DISK_MOUNT_POINT : &str = "/disk";
+
+/// Directories the root always has, even when the archive is empty.
+const ROOT_DIRS : [&str; 6] = ["/bin", "/dev", "/etc", "/proc", "/tmp", DISK_MOUNT_POINT];
+
+pub fn archive() -> &'static [u8] {
+    INITRAMFS
+}
+
+fn metadata(entry : &Entry, kind : NodeKind) -> Metadata {
+    let mut meta = Metadata::new(kind);
+    meta.mode = entry.mode;
+    meta.uid = entry.uid;
+    meta.gid = entry.gid;
+    meta.mtime = entry.mtime;
+    meta.device = entry.device;
+    meta
+}
+
+/// Archive members are named relative to the root, whatever the working directory.
+fn member_path(name : &str) -> String {
+    Path::root().join(name).normalize().into()
+}
+
+/// Copies every member of `archive` into `ramfs`.
+pub fn unpack(archive : &[u8], ramfs : &mut RamFs) -> Result<usize, &'static str> {
+    let archive = Archive::open(archive)?;
+    let mut count = 0;
+
+    // Entries are sorted by path, so parents come first. Hard links wait
+    // until everything they could point at exists.
+    for entry in archive.entries() {
+        let path = member_path(&entry.path);
+        let result = match entry.kind {
+            EntryKind::Directory => match ramfs.lookup(&path) {
+                Some(_) => ramfs.set_metadata(&path, &metadata(entry, NodeKind::Directory)),
+                None => ramfs.mknod(&path, metadata(entry, NodeKind::Directory)).map(|_| ()),
+            },
+            EntryKind::File => ramfs.mknod(&path, metadata(entry, NodeKind::File))
+                .and_then(|_| vfs::FileSystem::write(ramfs, &path, 0, &archive.contents(entry)).map(|_| ())),
+            EntryKind::Symlink => ramfs.symlink(&entry.link, &path),
+            EntryKind::CharDevice => ramfs.mknod(&path, metadata(entry, NodeKind::CharDevice)).map(|_| ()),
+            EntryKind::BlockDevice => ramfs.mknod(&path, metadata(entry, NodeKind::BlockDevice)).map(|_| ()),
+            EntryKind::Fifo => ramfs.mknod(&path, metadata(entry, NodeKind::Fifo)).map(|_| ()),
+            EntryKind::HardLink => continue,
+        };
+        match result {
+            Ok(()) => count += 1,
+            Err(error) => log!("[initramfs] {}: {}\n", path, error),
+        }
+    }
+
+    for entry in archive.entries().filter(|entry| entry.kind == EntryKind::HardLink) {
+        let path = member_path(&entry.path);
+        match ramfs.link(&member_path(&entry.link), &path) {
+            Ok(()) => count += 1,
+            Err(error) => log!("[initramfs] {}: {}\n", path, error),
+        }
+    }
+    Ok(count)
+}
+
+/// Unpacks the embedded archive as `/`, then mounts the disk filesystem under it.
+pub fn init() -> InitResult<()> {
+    let mut ramfs = RamFs::new();
+    match unpack(INITRAMFS, &mut ramfs) {
+        Ok(count) => log!("{} Files, {} KB - ", count, ramfs.size() / 1024),
+        Err(error) => log!("{} - ", error),
+    }
+    for dir in ROOT_DIRS.iter() {
+        if ramfs.lookup(dir).is_none() {
+            let _ = ramfs.mknod(dir, Metadata::new(NodeKind::Directory));
+        }
+    }
+    // Anyone may create files in /tmp, but only remove their own.
+    let _ = vfs::FileSystem::set_mode(&mut ramfs, "/tmp", 0o777 | vfs::MODE_STICKY);
+    vfs::mount("/", Box::new(ramfs)).expect("Unable To Mount Root");
+
+    fs::init();
+    if fs::is_mounted() {
+        match vfs::mount(DISK_MOUNT_POINT, Box::new(fs::MorosFs)) {
+            Ok(()) => log!("Disk Mounted At {} - ", DISK_MOUNT_POINT),
+            Err(error) => log!("{} - ", error),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Arguments, Write};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::kernel::ring_buffer::RingBuffer;
+use crate::io::devices::console;
+use crate::time;
+
+/// Bytes of log text kept in memory for `dmesg`, oldest lines are dropped first.
+pub const LOG_BUFFER_SIZE : usize = 16 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+/// Where log records go. Each has its own filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sink {
+    Vga,
+    Serial,
+    Buffer,
+}
+
+struct Logger {
+    // The most verbose level each sink takes, `None` when it's off.
+    filters : [Option<Level>; 3],
+    buffer : RingBuffer<u8, LOG_BUFFER_SIZE>,
+    wrapped : bool, // The oldest line in `buffer` may have lost its start.
+    line_start : bool, // Nothing has been written on the last line of `buffer` yet.
+}
+
+impl Logger {
+    fn enabled(&self, sink : Sink, level : Level) -> bool {
+        self.filters[sink as usize].map_or(false, |max| level <= max)
+    }
+}
+
+impl Write for Logger {
+    fn write_str(&mut self, s : &str) -> fmt::Result {
+        for byte in s.bytes() {
+            if self.buffer.is_full() {
+                self.wrapped = true;
+            }
+            self.buffer.push_overwrite(byte);
+            self.line_start = byte == b'\n';
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref LOGGER : Mutex<Logger> = Mutex::new(Logger {
+        filters : [Some(Level::Info), Some(Level::Debug), Some(Level::Trace)],
+        buffer : RingBuffer::new(),
+        wrapped : false,
+        line_start : true,
+    });
+}
+
+/// Sets the most verbose level `sink` shows, `None` turns it off.
+pub fn set_level(sink : Sink, level : Option<Level>) {
+    without_interrupts(|| LOGGER.lock().filters[sink as usize] = level);
+}
+
+pub fn level(sink : Sink) -> Option<Level> {
+    without_interrupts(|| LOGGER.lock().filters[sink as usize])
+}
+
+/// The tag of a record, the last part of the module path that logged it.
+pub fn tag(module : &'static str) -> &'static str {
+    module.rsplit("::").next().unwrap_or(module)
+}
+
+/// Writes one record, as `[seconds.millis] LEVEL tag: message`, to every sink
+/// that takes `level`. Errors and warnings show on the console in the error colors.
+pub fn _log(level : Level, tag : &str, args : Arguments) {
+    let (seconds, millis) = timestamp();
+
+    let (vga, serial) = without_interrupts(|| {
+        let mut logger = LOGGER.lock();
+        if logger.enabled(Sink::Buffer, level) {
+            if !logger.line_start {
+                let _ = logger.write_char('\n');
+            }
+            let _ = writeln!(logger, "[{:>5}.{:03}] {:<5} {}: {}", seconds, millis, level.name(), tag, args);
+        }
+        (logger.enabled(Sink::Vga, level), logger.enabled(Sink::Serial, level))
+    });
+    if serial {
+        crate::input::serial_print!("[{:>5}.{:03}] {:<5} {}: {}\r\n", seconds, millis, level.name(), tag, args);
+    }
+    if vga {
+        if level <= Level::Warn {
+            console::_eprint(format_args!("{}: {}\n", tag, args));
+        } else {
+            console::_print(format_args!("{}: {}\n", tag, args));
+        }
+    }
+}
+
+/// Writes `args` as it is to the console and serial, for output that builds
+/// up lines a piece at a time. The buffer gets the same text, with a record
+/// prefix at the start of each line.
+pub fn _log_raw(level : Level, tag : &str, args : Arguments) {
+    let (seconds, millis) = timestamp();
+
+    // With one CPU the logger is only held here if logging itself panicked,
+    // so print the message rather than wait forever for the lock.
+    let (vga, serial) = without_interrupts(|| match LOGGER.try_lock() {
+        Some(mut logger) => {
+            if logger.enabled(Sink::Buffer, level) {
+                let _ = Stamped { logger : &mut *logger, seconds, millis, level, tag }.write_fmt(args);
+            }
+            (logger.enabled(Sink::Vga, level), logger.enabled(Sink::Serial, level))
+        },
+        None => (true, true),
+    });
+    if serial {
+        crate::input::serial_print!("{}", args);
+    }
+    if vga {
+        if level <= Level::Warn {
+            console::_eprint(args);
+        } else {
+            console::_print(args);
+        }
+    }
+}
+
+/// Writes into the buffer, putting a record prefix before each new line.
+struct Stamped<'a> {
+    logger  : &'a mut Logger,
+    seconds : u128,
+    millis  : u128,
+    level   : Level,
+    tag     : &'a str,
+}
+
+impl Write for Stamped<'_> {
+    fn write_str(&mut self, s : &str) -> fmt::Result {
+        // Carriage returns only redraw a line on screen, the buffer keeps every piece.
+        for c in s.chars().filter(|c| *c != '\r') {
+            if self.logger.line_start {
+                write!(self.logger, "[{:>5}.{:03}] {:<5} {}: ", self.seconds, self.millis, self.level.name(), self.tag)?;
+            }
+            self.logger.write_char(c)?;
+        }
+        Ok(())
+    }
+}
+
+fn timestamp() -> (u128, u128) {
+    let ticks = time::ticks();
+    let per_second = time::TICKS_PER_SECOND as u128;
+    (ticks / per_second, ticks % per_second * 1000 / per_second)
+}
+
+/// Everything still in the log buffer, one record per line.
+pub fn dmesg() -> String {
+    without_interrupts(|| {
+        let logger = LOGGER.lock();
+        let mut bytes = logger.buffer.iter();
+        if logger.wrapped {
+            // Skip what's left of a line whose start was overwritten.
+            bytes.by_ref().find(|byte| *byte == b'\n');
+        }
+        String::from_utf8_lossy(&bytes.collect::<Vec<u8>>()).into_owned()
+    })
+}
+
+pub fn clear() {
+    without_interrupts(|| {
+        let mut logger = LOGGER.lock();
+        logger.buffer.clear();
+        logger.wrapped = false;
+        logger.line_start = true;
+    });
+}
+
+#[macro_export]
+macro_rules! klog {
+    ($level : expr, $($args:tt)*) => {
+        $crate::kernel::klog::_log($level, $crate::kernel::klog::tag(module_path!()), format_args!($($args)*))
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($args:tt)*) => { $crate::klog!($crate::kernel::klog::Level::Error, $($args)*) };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($args:tt)*) => { $crate::klog!($crate::kernel::klog::Level::Warn, $($args)*) };
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($args:tt)*) => { $crate::klog!($crate::kernel::klog::Level::Info, $($args)*) };
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($args:tt)*) => { $crate::klog!($crate::kernel::klog::Level::Debug, $($args)*) };
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($args:tt)*) => { $crate::klog!($crate::kernel::klog::Level::Trace, $($args)*) };
+}
@@ -2,6 +2,17 @@ pub mod arch;
 pub mod drivers;
 pub mod hardware;
 pub mod fs;
+pub mod task;
+pub mod symbols;
+pub mod gdb;
+pub mod ring_buffer;
+pub mod pipe;
+pub mod klog;
+pub mod path;
+pub mod vfs;
+pub mod devfs;
+pub mod procfs;
+pub mod initramfs;
 
 use bootloader::BootInfo;
 use x86_64::{VirtAddr};
@@ -32,7 +43,7 @@ pub fn boot(_boot_info : &'static BootInfo) {
     init_component!(hardware::pic::init, ());
     disable_irq(1);
     set_interrupt!(0, crate::time::update);
-    //set_interrupt!(4, crate::kernel::hardware::uart::on_serial_interrupt);
+    init_component!(hardware::uart::init, ());
     // for i in -1..10 {
     //     let bad = 1 / i;
     //     log!("{}", bad);
@@ -54,18 +65,28 @@ pub fn boot(_boot_info : &'static BootInfo) {
         }
 
         log!("Detected {:3} MB of Memory\n", memory_size >> 20);
+        kernel::procfs::set_memory_map(&_boot_info.memory_map);
 
         unsafe {crate::sys::mem::TOTAL_MEMORY = memory_size}
 
      crate::kernel::allocator::init_heap(&mut mapper, &mut frame_allocator).expect("Couldn't Initialize Allocator...");
+     init_component!(crate::io::devices::console::init, ());
     
+     #[cfg(feature = "gdbstub")]
+     init_component!(gdb::init, ());
+
      init_component!(crate::kernel::hardware::ata::init, ());
 
      log!("Disk 0 Is Present: {}\n",kernel::hardware::ata::drive_is_present(0));
 
      init_component!(kernel::drivers::file_systems::ustar::init, ());
+     init_component!(kernel::initramfs::init, ());
+     init_component!(kernel::devfs::init, ());
+     init_component!(kernel::procfs::init, ());
+     init_component!(kernel::drivers::file_systems::file_table::init, ());
     
     init_component!(input::init, ());
+    init_component!(hardware::mouse::init, ());
 
     crate::println!("Press Any Key To Start...");
     while input::key().is_none() {
@@ -96,7 +117,7 @@ pub macro set_interrupt($irq : expr, $path : path) {
     {
         let f : fn(u8) = $path;
         $crate::log!("[Boot] IRQ{} -> {} - ", $irq, stringify!($path));
-        let result = $crate::kernel::arch::set_interrupt($irq, f).expect("Failed");
+        let result = $crate::kernel::arch::set_named_interrupt($irq, stringify!($path), f).expect("Failed");
         $crate::kernel::arch::enable_irq($irq);
         $crate::log!("[OK]\n");
         result
@@ -0,0 +1,146 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use crate::kernel::task;
+
+/// A path with repeated slashes, `.` components and any trailing slash
+/// removed. `..` is kept until [Path::normalize] folds it, because only a
+/// filesystem that knows its symlinks can say where `..` really leads.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Path {
+    inner : String,
+}
+
+impl Path {
+    pub fn new(path : &str) -> Self {
+        let mut inner = String::with_capacity(path.len());
+        if path.starts_with('/') {
+            inner.push('/');
+        }
+        for part in path.split('/').filter(|part| !part.is_empty() && *part != ".") {
+            if !inner.is_empty() && !inner.ends_with('/') {
+                inner.push('/');
+            }
+            inner.push_str(part);
+        }
+        if inner.is_empty() {
+            inner.push('.');
+        }
+        Self { inner }
+    }
+
+    pub fn root() -> Self {
+        Self { inner : String::from("/") }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
+
+    pub fn is_absolute(&self) -> bool {
+        self.inner.starts_with('/')
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.inner == "/"
+    }
+
+    /// The names in the path, without slashes.
+    pub fn components(&self) -> impl Iterator<Item = &str> {
+        self.inner.split('/').filter(|part| !part.is_empty() && *part != ".")
+    }
+
+    /// `other` if it's absolute, otherwise `other` below this path.
+    pub fn join(&self, other : &str) -> Path {
+        if other.starts_with('/') {
+            return Path::new(other);
+        }
+        let mut joined = self.inner.clone();
+        joined.push('/');
+        joined.push_str(other);
+        Path::new(&joined)
+    }
+
+    /// Folds `..` into the component before it. `..` at the root stays at the
+    /// root, while a relative path keeps the `..`s it can't fold.
+    pub fn normalize(&self) -> Path {
+        let mut parts : Vec<&str> = Vec::new();
+        for part in self.components() {
+            match part {
+                ".." if parts.last().map_or(false, |last| *last != "..") => { parts.pop(); },
+                ".." if self.is_absolute() => {},
+                part => parts.push(part),
+            }
+        }
+        let mut normal = String::new();
+        if self.is_absolute() {
+            normal.push('/');
+        }
+        normal.push_str(&parts.join("/"));
+        Path::new(&normal)
+    }
+
+    /// This path from the current task's working directory, with `..` folded.
+    pub fn absolute(&self) -> Path {
+        if self.is_absolute() {
+            self.normalize()
+        } else {
+            Path::new(&task::cwd()).join(&self.inner).normalize()
+        }
+    }
+
+    /// The path without its last component. The root is its own parent.
+    pub fn parent(&self) -> Path {
+        match self.inner.rfind('/') {
+            Some(0) => Path::root(),
+            Some(index) => Path::new(&self.inner[..index]),
+            None if self.inner == "." => Path::new(".."),
+            None => Path::new("."),
+        }
+    }
+
+    /// The last component, or `""` for the root.
+    pub fn file_name(&self) -> &str {
+        match self.inner.rsplit('/').next() {
+            Some(".") | None => "",
+            Some(name) => name,
+        }
+    }
+
+    /// The rest of this path when it's `prefix` or below it, as an absolute path.
+    pub fn strip_prefix(&self, prefix : &str) -> Option<Path> {
+        let prefix = Path::new(prefix);
+        if prefix.is_root() {
+            return Some(self.clone());
+        }
+        match self.inner.strip_prefix(prefix.as_str())? {
+            "" => Some(Path::root()),
+            rest if rest.starts_with('/') => Some(Path::new(rest)),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Path {
+    fn from(path : &str) -> Self {
+        Path::new(path)
+    }
+}
+
+impl From<Path> for String {
+    fn from(path : Path) -> Self {
+        path.inner
+    }
+}
+
+impl AsRef<str> for Path {
+    fn as_ref(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.inner)
+    }
+}
@@ -0,0 +1,337 @@
+use alloc::{collections::{BTreeMap, VecDeque}, string::String, sync::Arc, vec::Vec};
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::kernel::{ring_buffer::RingBuffer, task};
+
+pub const PIPE_CAPACITY : usize = 4096;
+
+struct Pipe {
+    buffer  : RingBuffer<u8, PIPE_CAPACITY>,
+    readers : usize,
+    writers : usize,
+    named   : bool, // FIFOs keep their data while nobody has them open.
+    // Pipes between pipeline stages take everything a stage writes, as the
+    // next stage only starts once it's done. Whatever doesn't fit waits here.
+    staged  : Option<VecDeque<u8>>,
+}
+
+impl Pipe {
+    /// Takes what's buffered, `Err("Would Block")` if that's nothing but a
+    /// writer could still add more.
+    fn take(&mut self, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        if self.buffer.is_empty() {
+            return if self.writers == 0 || buffer.is_empty() { Ok(0) } else { Err("Would Block") };
+        }
+        let mut count = 0;
+        while count < buffer.len() {
+            match self.buffer.pop() {
+                Some(byte) => { buffer[count] = byte; count += 1; },
+                None => break,
+            }
+        }
+        if let Some(staged) = &mut self.staged {
+            while !self.buffer.is_full() {
+                match staged.pop_front() {
+                    Some(byte) => { self.buffer.push(byte); },
+                    None => break,
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Adds as much of `buffer` as fits, `Err("Would Block")` if that's nothing.
+    fn put(&mut self, buffer : &[u8]) -> Result<usize, &'static str> {
+        if self.readers == 0 && !self.named {
+            return Err("Broken Pipe");
+        }
+        if let Some(staged) = &mut self.staged {
+            let count = buffer.len().min(self.buffer.free());
+            for byte in &buffer[..count] {
+                self.buffer.push(*byte);
+            }
+            staged.extend(&buffer[count..]);
+            return Ok(buffer.len());
+        }
+        if self.buffer.is_full() && !buffer.is_empty() {
+            return Err("Would Block");
+        }
+        let count = buffer.len().min(self.buffer.free());
+        for byte in &buffer[..count] {
+            self.buffer.push(*byte);
+        }
+        Ok(count)
+    }
+}
+
+type SharedPipe = Arc<Mutex<Pipe>>;
+
+fn with_pipe<T, F : FnOnce(&mut Pipe) -> T>(pipe : &SharedPipe, f : F) -> T {
+    without_interrupts(|| f(&mut pipe.lock()))
+}
+
+/// The read end of a pipe. Reads return 0 once the pipe is empty and every
+/// writer has been dropped.
+pub struct PipeReader {
+    pipe : SharedPipe,
+    end  : EndId,
+}
+
+/// The write end of a pipe. Writing fails with "Broken Pipe" once every
+/// reader of an anonymous pipe has been dropped.
+pub struct PipeWriter {
+    pipe : SharedPipe,
+    end  : EndId,
+}
+
+type EndId = usize;
+
+/// An open end of a pipe, and the task that opened it.
+struct End {
+    task   : Option<task::TaskId>,
+    pipe   : SharedPipe,
+    writer : bool,
+}
+
+static NEXT_END : AtomicUsize = AtomicUsize::new(1);
+
+lazy_static! {
+    // Every open end, so a task that exits without dropping its ends still closes them.
+    static ref ENDS : Mutex<BTreeMap<EndId, End>> = Mutex::new(BTreeMap::new());
+}
+
+fn new_pipe(named : bool) -> SharedPipe {
+    Arc::new(Mutex::new(Pipe { buffer : RingBuffer::new(), readers : 0, writers : 0, named, staged : None }))
+}
+
+fn open_end(pipe : &SharedPipe, writer : bool) -> EndId {
+    with_pipe(pipe, |pipe| if writer { pipe.writers += 1 } else { pipe.readers += 1 });
+    let end = NEXT_END.fetch_add(1, Ordering::Relaxed);
+    let task = task::current();
+    without_interrupts(|| ENDS.lock().insert(end, End { task, pipe : pipe.clone(), writer }));
+    end
+}
+
+/// Closes `end`, unless it was already closed when its task exited.
+fn close_end(end : EndId) {
+    if let Some(end) = without_interrupts(|| ENDS.lock().remove(&end)) {
+        with_pipe(&end.pipe, |pipe| if end.writer { pipe.writers -= 1 } else { pipe.readers -= 1 });
+    }
+}
+
+fn reader(pipe : &SharedPipe) -> PipeReader {
+    PipeReader { pipe : pipe.clone(), end : open_end(pipe, false) }
+}
+
+fn writer(pipe : &SharedPipe) -> PipeWriter {
+    PipeWriter { pipe : pipe.clone(), end : open_end(pipe, true) }
+}
+
+/// Whether an end of `pipe`, a writer or a reader as `writer` says, is held
+/// by something that can run while the current task waits. Tasks are nested,
+/// so the ones still running are all waiting on it; ends held outside of any
+/// task, or kept by one that has returned, may still be used from interrupts.
+fn can_progress(pipe : &SharedPipe, writer : bool) -> bool {
+    without_interrupts(|| ENDS.lock().values().any(|end| {
+        end.writer == writer && Arc::ptr_eq(&end.pipe, pipe) && end.task.map_or(true, |task| !task::is_running(task))
+    }))
+}
+
+/// Closes the pipe ends `task` still has open. [task::exit] leaves a task
+/// without running its destructors, so this is how its readers see EOF.
+pub fn release_task(task : task::TaskId) {
+    let ends : Vec<EndId> = without_interrupts(|| {
+        ENDS.lock().iter().filter(|(_, end)| end.task == Some(task)).map(|(id, _)| *id).collect()
+    });
+    for end in ends {
+        close_end(end);
+    }
+}
+
+/// Creates an anonymous pipe.
+pub fn pipe() -> (PipeReader, PipeWriter) {
+    let pipe = new_pipe(false);
+    (reader(&pipe), writer(&pipe))
+}
+
+impl PipeReader {
+    /// Reads what's buffered without waiting, `Err("Would Block")` if that's
+    /// nothing but a writer could still add more.
+    pub fn try_read(&mut self, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        with_pipe(&self.pipe, |pipe| pipe.take(buffer))
+    }
+
+    /// Waits for at least one byte, or for the last writer to go away. Fails
+    /// with "Deadlock" when every writer belongs to a task waiting on this one.
+    pub fn read(&mut self, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        let mut result = Ok(0);
+        let mut stuck = false;
+        task::block_until(|| {
+            result = self.try_read(buffer);
+            if result != Err("Would Block") {
+                return true;
+            }
+            stuck = !can_progress(&self.pipe, true);
+            stuck
+        });
+        if stuck { Err("Deadlock") } else { result }
+    }
+
+    pub fn available(&self) -> usize {
+        with_pipe(&self.pipe, |pipe| pipe.buffer.len())
+    }
+}
+
+impl PipeWriter {
+    /// Writes as much as fits without waiting.
+    pub fn try_write(&mut self, buffer : &[u8]) -> Result<usize, &'static str> {
+        with_pipe(&self.pipe, |pipe| pipe.put(buffer))
+    }
+
+    /// Waits for room, then writes as much of `buffer` as fits and returns how
+    /// much that was. Fails with "Deadlock" when the pipe is full and every
+    /// reader belongs to a task waiting on this one.
+    pub fn write(&mut self, buffer : &[u8]) -> Result<usize, &'static str> {
+        let mut result = Ok(0);
+        let mut stuck = false;
+        task::block_until(|| {
+            result = self.try_write(buffer);
+            if result != Err("Would Block") {
+                return true;
+            }
+            stuck = !can_progress(&self.pipe, false);
+            stuck
+        });
+        if stuck { Err("Deadlock") } else { result }
+    }
+
+    /// Writes all of `buffer`, waiting for room as needed.
+    pub fn write_all(&mut self, buffer : &[u8]) -> Result<(), &'static str> {
+        let mut written = 0;
+        while written < buffer.len() {
+            written += self.write(&buffer[written..])?;
+        }
+        Ok(())
+    }
+}
+
+/// The pipe ends of one pipeline stage, `None` for the input of the first
+/// stage and the output of the last.
+#[derive(Debug)]
+pub struct StageIo {
+    pub input  : Option<PipeReader>,
+    pub output : Option<PipeWriter>,
+}
+
+pub type Stage = fn(&mut StageIo) -> task::ExitStatus;
+
+fn run_stage(start : &(Stage, Mutex<StageIo>)) -> task::ExitStatus {
+    let mut io = start.1.lock();
+    (start.0)(&mut io)
+}
+
+/// Runs `stages` as tasks, each one's output piped into the next one's input,
+/// and returns the exit status of the last. Tasks are nested, so the stages
+/// can't run side by side: each runs to the end in turn, and the pipe to the
+/// next stage keeps all it wrote, however much that is.
+pub fn pipeline(stages : &[(&str, Stage)]) -> task::ExitStatus {
+    let mut input = None;
+    let mut status = 0;
+    for (index, (name, entry)) in stages.iter().enumerate() {
+        let (next_input, output) = if index + 1 < stages.len() {
+            let pipe = new_pipe(false);
+            with_pipe(&pipe, |pipe| pipe.staged = Some(VecDeque::new()));
+            (Some(reader(&pipe)), Some(writer(&pipe)))
+        } else {
+            (None, None)
+        };
+        let start = (*entry, Mutex::new(StageIo { input : input.take(), output }));
+        status = task::run(name, run_stage, &start);
+        // Dropping the stage's ends here is what lets the next stage see EOF.
+        drop(start);
+        input = next_input;
+    }
+    status
+}
+
+impl Clone for PipeReader {
+    fn clone(&self) -> Self {
+        reader(&self.pipe)
+    }
+}
+
+impl Clone for PipeWriter {
+    fn clone(&self) -> Self {
+        writer(&self.pipe)
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        close_end(self.end);
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        close_end(self.end);
+    }
+}
+
+impl fmt::Debug for PipeReader {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PipeReader({} Bytes Buffered)", self.available())
+    }
+}
+
+impl fmt::Debug for PipeWriter {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PipeWriter")
+    }
+}
+
+/// One end of a pipe, as held by an open FIFO.
+#[derive(Debug, Clone)]
+pub enum PipeEnd {
+    Reader(PipeReader),
+    Writer(PipeWriter),
+}
+
+lazy_static! {
+    // The pipes behind FIFO nodes, by absolute path.
+    static ref FIFOS : Mutex<BTreeMap<String, SharedPipe>> = Mutex::new(BTreeMap::new());
+}
+
+fn fifo(path : &str) -> SharedPipe {
+    without_interrupts(|| {
+        FIFOS.lock().entry(String::from(path)).or_insert_with(|| new_pipe(true)).clone()
+    })
+}
+
+/// Opens the FIFO at `path`, an absolute path to a node already checked to be one.
+pub fn open_fifo(path : &str, write : bool) -> PipeEnd {
+    let pipe = fifo(path);
+    if write { PipeEnd::Writer(writer(&pipe)) } else { PipeEnd::Reader(reader(&pipe)) }
+}
+
+/// Reads what's buffered in the FIFO at `path` without opening an end, 0 if
+/// that's nothing.
+pub fn read_fifo(path : &str, buffer : &mut [u8]) -> Result<usize, &'static str> {
+    with_pipe(&fifo(path), |pipe| pipe.take(buffer)).or_else(|error| if error == "Would Block" { Ok(0) } else { Err(error) })
+}
+
+/// Writes what fits into the FIFO at `path` without opening an end.
+pub fn write_fifo(path : &str, buffer : &[u8]) -> Result<usize, &'static str> {
+    with_pipe(&fifo(path), |pipe| pipe.put(buffer))
+}
+
+/// Forgets the data buffered for a FIFO that was removed from the filesystem.
+pub fn remove_fifo(path : &str) {
+    without_interrupts(|| {
+        FIFOS.lock().remove(path);
+    });
+}
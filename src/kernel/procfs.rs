@@ -0,0 +1,203 @@
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use bootloader::bootinfo::MemoryMap;
+use core::fmt::Write;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::kernel::{InitResult, arch, hardware::ata, klog, task::{self, TaskId}};
+use crate::kernel::vfs::{self, DirEntry, FileSystem, Metadata, NodeKind};
+use crate::{log, sys, time};
+
+pub const MOUNT_POINT : &str = "/proc";
+
+/// Files at the top of `/proc`, each generated when it's read. They run with
+/// the mount table locked, so they can't go through `vfs` themselves.
+const FILES : [(&str, fn() -> String); 7] = [
+    ("disks", disks),
+    ("interrupts", interrupts),
+    ("kmsg", klog::dmesg),
+    ("meminfo", meminfo),
+    ("memmap", memmap),
+    ("uptime", uptime),
+    ("version", version),
+];
+
+/// Files in each `/proc/<task id>` directory.
+const TASK_FILES : [&str; 3] = ["cwd", "name", "status"];
+
+lazy_static! {
+    static ref MEMORY_MAP : Mutex<Option<&'static MemoryMap>> = Mutex::new(None);
+}
+
+/// Keeps the bootloader's memory map for `/proc/memmap`.
+pub fn set_memory_map(memory_map : &'static MemoryMap) {
+    without_interrupts(|| *MEMORY_MAP.lock() = Some(memory_map));
+}
+
+fn meminfo() -> String {
+    format!(
+        "MemTotal:  {:>10} kB\nHeapTotal: {:>10} kB\nHeapUsed:  {:>10} kB\nHeapFree:  {:>10} kB\n",
+        sys::mem::total_ram() / 1024,
+        sys::mem::total() / 1024,
+        sys::mem::used() / 1024,
+        sys::mem::free() / 1024,
+    )
+}
+
+fn uptime() -> String {
+    let ticks = time::ticks();
+    let per_second = time::TICKS_PER_SECOND as u128;
+    format!("{}.{:02}\n", ticks / per_second, ticks % per_second * 100 / per_second)
+}
+
+fn disks() -> String {
+    let mut text = String::new();
+    without_interrupts(|| {
+        for disk in ata::DISKS.lock().iter() {
+            let _ = writeln!(text, "{}:{} {:>10} {}", disk.bus, disk.drive, disk.size / 512, disk.name);
+        }
+    });
+    text
+}
+
+fn memmap() -> String {
+    let mut text = String::new();
+    if let Some(memory_map) = without_interrupts(|| *MEMORY_MAP.lock()) {
+        for region in memory_map.iter() {
+            let _ = writeln!(text, "{:#016X}-{:#016X} {:?}", region.range.start_addr(), region.range.end_addr(), region.region_type);
+        }
+    }
+    text
+}
+
+fn interrupts() -> String {
+    let mut text = String::new();
+    for line in arch::interrupt_table() {
+        if line.handlers.is_empty() && line.stats.fired == 0 {
+            continue;
+        }
+        let names = line.handlers.iter().map(|(_, name)| *name).collect::<Vec<_>>().join(", ");
+        let _ = writeln!(text, "{:>3}: {:>10} {:>6} {}", line.irq, line.stats.fired, line.stats.unhandled, names);
+    }
+    text
+}
+
+fn version() -> String {
+    format!("Tinix-core v{}\n", crate::version())
+}
+
+fn task_file(id : TaskId, name : &str) -> Option<String> {
+    let task = task::list().into_iter().find(|task| task.id() == id)?;
+    match name {
+        "cwd" => Some(format!("{}\n", task.cwd())),
+        "name" => Some(format!("{}\n", task.name())),
+        "status" => {
+            let credentials = task.credentials();
+            Some(format!(
+                "Name:  {}\nState: {:?}\nUid:   {}\nGid:   {}\n",
+                task.name(), task.state(), credentials.uid, credentials.gid,
+            ))
+        },
+        _ => None,
+    }
+}
+
+enum Node {
+    Root,
+    File(String),
+    Task(TaskId),
+}
+
+/// The read-only filesystem at `/proc`, showing system state as text.
+pub struct ProcFs;
+
+impl ProcFs {
+    fn node(path : &str) -> Result<Node, &'static str> {
+        let mut parts = path.split('/').filter(|part| !part.is_empty());
+        let node = match (parts.next(), parts.next(), parts.next()) {
+            (None, _, _) => Some(Node::Root),
+            (Some(name), None, _) => match FILES.iter().find(|(file, _)| *file == name) {
+                Some((_, generate)) => Some(Node::File(generate())),
+                None => name.parse().ok()
+                    .filter(|id| task::list().iter().any(|task| task.id() == *id))
+                    .map(Node::Task),
+            },
+            (Some(id), Some(name), None) => id.parse().ok().and_then(|id| task_file(id, name)).map(Node::File),
+            _ => None,
+        };
+        node.ok_or("No Such File Or Directory")
+    }
+}
+
+fn directory() -> Metadata {
+    let mut meta = Metadata::new(NodeKind::Directory);
+    meta.mode = 0o555;
+    meta
+}
+
+impl FileSystem for ProcFs {
+    fn name(&self) -> &'static str {
+        "procfs"
+    }
+
+    fn metadata(&self, path : &str) -> Result<Metadata, &'static str> {
+        match Self::node(path)? {
+            Node::Root | Node::Task(_) => Ok(directory()),
+            Node::File(text) => {
+                let mut meta = Metadata::new(NodeKind::File);
+                meta.mode = 0o444;
+                meta.size = text.len();
+                Ok(meta)
+            },
+        }
+    }
+
+    fn read_dir(&self, path : &str) -> Result<Vec<DirEntry>, &'static str> {
+        let file = |name : &str| DirEntry { name : String::from(name), kind : NodeKind::File, size : 0 };
+        match Self::node(path)? {
+            Node::Root => {
+                let mut entries : Vec<DirEntry> = FILES.iter().map(|(name, _)| file(name)).collect();
+                for task in task::list() {
+                    entries.push(DirEntry { name : format!("{}", task.id()), kind : NodeKind::Directory, size : 0 });
+                }
+                Ok(entries)
+            },
+            Node::Task(_) => Ok(TASK_FILES.iter().map(|name| file(name)).collect()),
+            Node::File(_) => Err("Not A Directory"),
+        }
+    }
+
+    fn read(&mut self, path : &str, offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        match Self::node(path)? {
+            Node::File(text) => {
+                let text = text.as_bytes();
+                let start = offset.min(text.len());
+                let count = buffer.len().min(text.len() - start);
+                buffer[..count].copy_from_slice(&text[start..start + count]);
+                Ok(count)
+            },
+            _ => Err("Is A Directory"),
+        }
+    }
+
+    fn write(&mut self, _path : &str, _offset : usize, _buffer : &[u8]) -> Result<usize, &'static str> {
+        Err("Read Only Filesystem")
+    }
+
+    fn create(&mut self, _path : &str, _kind : NodeKind) -> Result<(), &'static str> {
+        Err("Read Only Filesystem")
+    }
+
+    fn remove(&mut self, _path : &str) -> Result<(), &'static str> {
+        Err("Read Only Filesystem")
+    }
+}
+
+pub fn init() -> InitResult<()> {
+    let _ = vfs::create_dir(MOUNT_POINT);
+    if let Err(error) = vfs::mount(MOUNT_POINT, Box::new(ProcFs)) {
+        log!("{} - ", error);
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+/// A fixed-size FIFO that never allocates, so it can be filled from interrupt handlers.
+#[derive(Debug, Clone)]
+pub struct RingBuffer<T : Copy, const N : usize> {
+    data : [T; N],
+    head : usize,
+    len  : usize,
+}
+
+impl<T : Copy + Default, const N : usize> RingBuffer<T, N> {
+    pub fn new() -> Self {
+        Self {
+            data : [T::default(); N],
+            head : 0,
+            len : 0,
+        }
+    }
+
+    /// Appends `item`, returning false (and dropping it) if the buffer is full.
+    pub fn push(&mut self, item : T) -> bool {
+        if self.is_full() {
+            return false;
+        }
+        self.data[(self.head + self.len) % N] = item;
+        self.len += 1;
+        true
+    }
+
+    /// Appends `item`, dropping the oldest entry to make room if needed.
+    pub fn push_overwrite(&mut self, item : T) {
+        if self.is_full() {
+            self.pop();
+        }
+        self.push(item);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let item = self.data[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(item)
+    }
+
+    pub fn peek(&self) -> Option<T> {
+        if self.is_empty() { None } else { Some(self.data[self.head]) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn free(&self) -> usize {
+        N - self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    /// Iterates from the oldest entry to the newest without removing anything.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.len).map(move |i| self.data[(self.head + i) % N])
+    }
+}
+
+impl<T : Copy + Default, const N : usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
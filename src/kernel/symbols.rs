@@ -0,0 +1,51 @@
+use core::fmt::Display;
+
+/// The kernel's own symbol table, "address name" lines sorted by address and
+/// padded with zeroes. Filled in by build.rs from `target/tinix.sym`.
+static KSYMS : &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/ksyms.bin"));
+
+fn lines() -> impl Iterator<Item = (u64, &'static str)> {
+    let end = KSYMS.iter().position(|&b| b == 0).unwrap_or(KSYMS.len());
+    KSYMS[..end].split(|&b| b == b'\n').filter_map(|line| {
+        let line = core::str::from_utf8(line).ok()?;
+        let (address, name) = line.split_once(' ')?;
+        Some((u64::from_str_radix(address, 16).ok()?, name))
+    })
+}
+
+pub fn is_loaded() -> bool {
+    KSYMS.first().map_or(false, |&b| b != 0)
+}
+
+pub fn count() -> usize {
+    lines().count()
+}
+
+/// Finds the function containing `address`, returning its name and the offset into it.
+pub fn resolve(address : u64) -> Option<(&'static str, u64)> {
+    let mut best = None;
+    for (start, name) in lines() {
+        if start > address {
+            break;
+        }
+        best = Some((name, address - start));
+    }
+    best
+}
+
+pub fn lookup(name : &str) -> Option<u64> {
+    lines().find(|(_, symbol)| *symbol == name).map(|(address, _)| address)
+}
+
+/// Displays an address as `0x... function+0xoffset`.
+#[derive(Debug, Clone, Copy)]
+pub struct Symbolized(pub u64);
+
+impl Display for Symbolized {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match resolve(self.0) {
+            Some((name, offset)) => write!(f, "0x{:016x} {}+{:#x}", self.0, name, offset),
+            None => write!(f, "0x{:016x} ??", self.0),
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use core::arch::global_asm;
+
+use alloc::{string::String, vec::Vec};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::kernel::{pipe, vfs};
+
+pub type TaskId = usize;
+pub type ExitStatus = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    Running,
+    Exited(ExitStatus),
+    Killed(ExitStatus),
+}
+
+pub type Uid = u32;
+pub type Gid = u32;
+
+pub const ROOT_UID : Uid = 0;
+
+/// Exit status of a task ended by Ctrl+C, 128 + SIGINT as a shell would report it.
+pub const INTERRUPT_EXIT_STATUS : ExitStatus = 130;
+
+/// Who a task is acting as, for filesystem access checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub uid    : Uid,
+    pub gid    : Gid,
+    pub groups : Vec<Gid>, // Supplementary groups.
+}
+
+impl Credentials {
+    pub fn root() -> Self {
+        Self::new(ROOT_UID, 0)
+    }
+
+    pub fn new(uid : Uid, gid : Gid) -> Self {
+        Self { uid, gid, groups : Vec::new() }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.uid == ROOT_UID
+    }
+
+    pub fn in_group(&self, gid : Gid) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+}
+
+/// A program being run by [run]. Tasks are nested rather than scheduled: the
+/// innermost running task is the current one.
+#[derive(Debug, Clone)]
+pub struct Task {
+    id      : TaskId,
+    name    : String,
+    state   : TaskState,
+    context : usize, // Address of the saved stack pointer, see `tinix_task_enter`.
+    credentials : Credentials,
+    cwd     : String,
+    interrupted : bool, // Ctrl+C was pressed and the task hasn't seen it yet.
+    killable : usize, // How deep in [killable] sections the task is.
+}
+
+impl Task {
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn state(&self) -> TaskState {
+        self.state
+    }
+
+    pub fn credentials(&self) -> &Credentials {
+        &self.credentials
+    }
+
+    pub fn cwd(&self) -> &str {
+        &self.cwd
+    }
+}
+
+struct TaskList {
+    running : Vec<Task>,
+    next_id : TaskId,
+    cwd     : String, // Working directory when no task is running.
+}
+
+impl TaskList {
+    fn cwd_mut(&mut self) -> &mut String {
+        match self.running.last_mut() {
+            Some(task) => &mut task.cwd,
+            None => &mut self.cwd,
+        }
+    }
+}
+
+lazy_static! {
+    static ref TASKS : Mutex<TaskList> = Mutex::new(TaskList {
+        running : Vec::new(),
+        next_id : 1,
+        cwd : String::from("/"),
+    });
+}
+
+// tinix_task_enter(context, entry, arg) saves the callee-saved registers on the
+// stack, stores the stack pointer in *context and calls entry(arg).
+// tinix_task_abort(context, status) unwinds back to that point, making
+// tinix_task_enter return `status` no matter how deep the task was.
+global_asm!(r#"
+.global tinix_task_enter
+tinix_task_enter:
+    push rbp
+    push rbx
+    push r12
+    push r13
+    push r14
+    push r15
+    sub rsp, 8
+    mov [rdi], rsp
+    mov rdi, rdx
+    call rsi
+tinix_task_leave:
+    add rsp, 8
+    pop r15
+    pop r14
+    pop r13
+    pop r12
+    pop rbx
+    pop rbp
+    ret
+
+.global tinix_task_abort
+tinix_task_abort:
+    mov rsp, [rdi]
+    mov rax, rsi
+    jmp tinix_task_leave
+"#);
+
+extern "C" {
+    fn tinix_task_enter(context : *mut usize, entry : extern "C" fn(usize) -> usize, arg : usize) -> usize;
+    fn tinix_task_abort(context : usize, status : usize) -> !;
+}
+
+struct TaskStart<'a, T> {
+    entry : fn(&T) -> ExitStatus,
+    arg   : &'a T,
+}
+
+extern "C" fn task_main<T>(start : usize) -> usize {
+    let start = unsafe { &*(start as *const TaskStart<T>) };
+    (start.entry)(start.arg)
+}
+
+/// Runs `entry` as a new task and returns its exit status, either the value it
+/// returned, the one it passed to [exit], or the one it was [kill]ed with.
+pub fn run<T>(name : &str, entry : fn(&T) -> ExitStatus, arg : &T) -> ExitStatus {
+    let mut context : usize = 0;
+    let start = TaskStart { entry, arg };
+
+    without_interrupts(|| {
+        let mut tasks = TASKS.lock();
+        let id = tasks.next_id;
+        tasks.next_id += 1;
+        // A task acts as whoever started it.
+        let credentials = tasks.running.last().map_or_else(Credentials::root, |task| task.credentials.clone());
+        let cwd = tasks.cwd_mut().clone();
+        tasks.running.push(Task {
+            id,
+            name : String::from(name),
+            state : TaskState::Running,
+            context : &mut context as *mut usize as usize,
+            credentials,
+            cwd,
+            interrupted : false,
+            killable : 0,
+        });
+    });
+
+    let status = unsafe {
+        tinix_task_enter(&mut context, task_main::<T>, &start as *const TaskStart<T> as usize)
+    };
+
+    let task = without_interrupts(|| TASKS.lock().running.pop());
+    // A task that exited or was killed skipped its destructors, so close what it left open.
+    if let Some(task) = task.filter(|task| task.state != TaskState::Running) {
+        pipe::release_task(task.id);
+    }
+    status
+}
+
+/// Runs `entry` as the program at `path`, in a task named after the file, if
+/// the current task may execute it.
+pub fn exec<T>(path : &str, entry : fn(&T) -> ExitStatus, arg : &T) -> Result<ExitStatus, &'static str> {
+    vfs::check_exec(path)?;
+    Ok(run(vfs::filename(path), entry, arg))
+}
+
+/// Ends the current task with `status`.
+pub fn exit(status : ExitStatus) -> ! {
+    let mut context = 0;
+    without_interrupts(|| {
+        let mut tasks = TASKS.lock();
+        let task = tasks.running.last_mut().expect("exit() Called Outside Of A Task");
+        if task.state == TaskState::Running {
+            task.state = TaskState::Exited(status);
+        }
+        context = task.context;
+    });
+    unsafe { tinix_task_abort(context, status) }
+}
+
+/// Marks the current task as killed. The caller is responsible for making it
+/// run [exit_killed] next, which is what the fault handlers do.
+pub fn kill(status : ExitStatus) -> bool {
+    let mut killed = false;
+    without_interrupts(|| {
+        if let Some(task) = TASKS.lock().running.last_mut() {
+            task.state = TaskState::Killed(status);
+            killed = true;
+        }
+    });
+    killed
+}
+
+/// Runs `f` so that a CPU fault in it kills the current task rather than
+/// panicking. A killed task skips its destructors, so `f` must not hold a lock,
+/// or anything else another task would wait on, while it might fault.
+pub fn killable<T, F : FnOnce() -> T>(f : F) -> T {
+    set_killable(1);
+    let result = f();
+    set_killable(-1);
+    result
+}
+
+fn set_killable(change : isize) {
+    without_interrupts(|| {
+        if let Some(task) = TASKS.lock().running.last_mut() {
+            task.killable = (task.killable as isize + change) as usize;
+        }
+    });
+}
+
+/// Whether the current task is in a [killable] section.
+pub fn is_killable() -> bool {
+    without_interrupts(|| TASKS.lock().running.last().map_or(false, |task| task.killable > 0))
+}
+
+/// Landing point for a killed task, entered by rewriting an interrupt stack frame.
+pub extern "C" fn exit_killed() -> ! {
+    let mut status = 0;
+    without_interrupts(|| {
+        if let Some(Task { state : TaskState::Killed(s), .. }) = TASKS.lock().running.last() {
+            status = *s;
+        }
+    });
+    exit(status)
+}
+
+pub fn current() -> Option<TaskId> {
+    let mut id = None;
+    without_interrupts(|| {
+        id = TASKS.lock().running.last().map(|task| task.id);
+    });
+    id
+}
+
+/// Whether `id` is one of the tasks still running, the current one or one it's nested in.
+pub fn is_running(id : TaskId) -> bool {
+    without_interrupts(|| TASKS.lock().running.iter().any(|task| task.id == id))
+}
+
+pub fn current_name() -> Option<String> {
+    let mut name = None;
+    without_interrupts(|| {
+        name = TASKS.lock().running.last().map(|task| task.name.clone());
+    });
+    name
+}
+
+pub fn list() -> Vec<Task> {
+    let mut tasks = Vec::new();
+    without_interrupts(|| {
+        tasks = TASKS.lock().running.clone();
+    });
+    tasks
+}
+
+/// The current task's credentials, or root's while booting.
+pub fn credentials() -> Credentials {
+    let mut credentials = Credentials::root();
+    without_interrupts(|| {
+        if let Some(task) = TASKS.lock().running.last() {
+            credentials = task.credentials.clone();
+        }
+    });
+    credentials
+}
+
+/// Changes who the current task acts as. Only root can do this.
+pub fn set_credentials(credentials : Credentials) -> Result<(), &'static str> {
+    let mut result = Err("No Current Task");
+    without_interrupts(|| {
+        if let Some(task) = TASKS.lock().running.last_mut() {
+            result = if task.credentials.is_root() {
+                task.credentials = credentials;
+                Ok(())
+            } else {
+                Err("Permission Denied")
+            };
+        }
+    });
+    result
+}
+
+/// The current task's working directory, an absolute path.
+pub fn cwd() -> String {
+    without_interrupts(|| TASKS.lock().cwd_mut().clone())
+}
+
+/// Sets the working directory without checking it, see `vfs::chdir`.
+pub fn set_cwd(cwd : &str) {
+    without_interrupts(|| *TASKS.lock().cwd_mut() = String::from(cwd));
+}
+
+/// Asks the current task to stop, as Ctrl+C does. Tasks can't be preempted, so
+/// it only takes effect when the task blocks or calls [check_interrupt].
+pub fn interrupt() -> bool {
+    without_interrupts(|| match TASKS.lock().running.last_mut() {
+        Some(task) => { task.interrupted = true; true },
+        None => false,
+    })
+}
+
+/// Whether the current task was interrupted, clearing it. Tasks that handle
+/// Ctrl+C themselves call this instead of [check_interrupt].
+pub fn take_interrupt() -> bool {
+    without_interrupts(|| match TASKS.lock().running.last_mut() {
+        Some(task) => core::mem::replace(&mut task.interrupted, false),
+        None => false,
+    })
+}
+
+/// Exits with [INTERRUPT_EXIT_STATUS] if the current task was interrupted.
+pub fn check_interrupt() {
+    if take_interrupt() {
+        exit(INTERRUPT_EXIT_STATUS);
+    }
+}
+
+/// Waits until `ready` returns true, halting between checks so interrupts can
+/// make progress. Tasks are nested rather than scheduled, so an outer task
+/// can't run while the current one waits: anything that blocks here must be
+/// woken from an interrupt handler or by data another task already left behind.
+/// An interrupted task exits instead of waiting.
+pub fn block_until<F : FnMut() -> bool>(ready : F) {
+    if !block_until_or_interrupt(ready) {
+        exit(INTERRUPT_EXIT_STATUS);
+    }
+}
+
+/// Like [block_until], but returns false when the task is interrupted,
+/// taking the interrupt, rather than ending the task. Interrupts are enabled
+/// while halting, and disabled again on return if the caller had them off.
+pub fn block_until_or_interrupt<F : FnMut() -> bool>(mut ready : F) -> bool {
+    let enabled = x86_64::instructions::interrupts::are_enabled();
+    let result = loop {
+        if take_interrupt() {
+            break false;
+        }
+        if ready() {
+            break true;
+        }
+        x86_64::instructions::interrupts::enable_and_hlt();
+    };
+    if !enabled {
+        x86_64::instructions::interrupts::disable();
+    }
+    result
+}
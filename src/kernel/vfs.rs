@@ -0,0 +1,653 @@
+use alloc::{boxed::Box, string::String, vec::Vec};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::kernel::{devfs, path::Path, pipe::{self, PipeEnd}, task::{self, Credentials, Gid, Uid}};
+
+/// Bits for [access], matching the rwx triplets in a mode.
+pub const ACCESS_READ  : u32 = 0o4;
+pub const ACCESS_WRITE : u32 = 0o2;
+pub const ACCESS_EXEC  : u32 = 0o1;
+
+pub const MODE_STICKY : u32 = 0o1000;
+
+/// Symlinks followed while resolving one path before it's taken to be a loop.
+pub const MAX_SYMLINK_DEPTH : usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    File,
+    Directory,
+    Symlink,
+    CharDevice,
+    BlockDevice,
+    Fifo,
+}
+
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    pub kind   : NodeKind,
+    pub inode  : u64,
+    pub size   : usize,
+    pub mode   : u32,
+    pub uid    : u32,
+    pub gid    : u32,
+    pub mtime  : u64,
+    pub links  : u32,
+    pub device : (u32, u32),
+}
+
+impl Metadata {
+    pub fn new(kind : NodeKind) -> Self {
+        Self {
+            kind,
+            inode : 0,
+            size : 0,
+            mode : if kind == NodeKind::Directory { 0o755 } else { 0o644 },
+            uid : 0,
+            gid : 0,
+            mtime : 0,
+            links : 1,
+            device : (0, 0),
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == NodeKind::Directory
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == NodeKind::File
+    }
+
+    pub fn is_fifo(&self) -> bool {
+        self.kind == NodeKind::Fifo
+    }
+
+    pub fn is_device(&self) -> bool {
+        self.kind == NodeKind::CharDevice || self.kind == NodeKind::BlockDevice
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    pub name : String,
+    pub kind : NodeKind,
+    pub size : usize,
+}
+
+/// A mounted filesystem. Paths are absolute within the filesystem itself,
+/// so a filesystem mounted at `/disk` sees `/disk/a` as `/a`.
+pub trait FileSystem : Send {
+    fn name(&self) -> &'static str;
+
+    fn metadata(&self, path : &str) -> Result<Metadata, &'static str>;
+    fn read_dir(&self, path : &str) -> Result<Vec<DirEntry>, &'static str>;
+    fn read(&mut self, path : &str, offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str>;
+    fn write(&mut self, path : &str, offset : usize, buffer : &[u8]) -> Result<usize, &'static str>;
+    fn create(&mut self, path : &str, kind : NodeKind) -> Result<(), &'static str>;
+    fn remove(&mut self, path : &str) -> Result<(), &'static str>;
+
+    fn truncate(&mut self, _path : &str, _size : usize) -> Result<(), &'static str> {
+        Err("Operation Not Supported")
+    }
+
+    /// Changes the permission bits, the low 12 bits of `mode`.
+    fn set_mode(&mut self, _path : &str, _mode : u32) -> Result<(), &'static str> {
+        Err("Operation Not Supported")
+    }
+
+    fn set_owner(&mut self, _path : &str, _uid : Uid, _gid : Gid) -> Result<(), &'static str> {
+        Err("Operation Not Supported")
+    }
+
+    fn symlink(&mut self, _target : &str, _path : &str) -> Result<(), &'static str> {
+        Err("Operation Not Supported")
+    }
+
+    /// Adds `path` as another name for `target`, both within this filesystem.
+    fn link(&mut self, _target : &str, _path : &str) -> Result<(), &'static str> {
+        Err("Operation Not Supported")
+    }
+
+    fn read_link(&self, _path : &str) -> Result<String, &'static str> {
+        Err("Not A Symlink")
+    }
+}
+
+struct Mount {
+    path : String,
+    fs   : Box<dyn FileSystem>,
+}
+
+lazy_static! {
+    static ref MOUNTS : Mutex<Vec<Mount>> = Mutex::new(Vec::new());
+}
+
+/// Turns `path` into an absolute path without `.`, `..` or repeated slashes.
+/// Relative paths start at the current task's working directory. This only
+/// looks at the text: `..` after a symlink needs [resolve].
+pub fn normalize(path : &str) -> String {
+    Path::new(path).absolute().into()
+}
+
+/// Turns `path` into an absolute path that only goes through real directories,
+/// following symlinks a component at a time and across mounts, and in the last
+/// component too when `follow` is set. `..` goes up from wherever a symlink
+/// led. The last component may be missing, so it can be created.
+pub fn resolve(path : &str, follow : bool) -> Result<String, &'static str> {
+    // A stack of the components still to walk, the next one last.
+    let mut pending : Vec<String> = path.split('/').rev().map(String::from).collect();
+    if !path.starts_with('/') {
+        pending.extend(task::cwd().split('/').rev().map(String::from));
+    }
+    let mut parts : Vec<String> = Vec::new();
+    let mut depth = 0;
+
+    while let Some(name) = pending.pop() {
+        match name.as_str() {
+            "" | "." => continue,
+            ".." => {
+                parts.pop();
+                continue;
+            },
+            _ => {},
+        }
+        let last = pending.iter().all(|name| name.is_empty() || name == ".");
+        let current = join_parts(&parts, &name);
+        match with_mount(&current, |fs, path| fs.metadata(path)) {
+            Ok(meta) if meta.kind == NodeKind::Symlink && (follow || !last) => {
+                depth += 1;
+                if depth > MAX_SYMLINK_DEPTH {
+                    return Err("Too Many Levels Of Symbolic Links");
+                }
+                let target = with_mount(&current, |fs, path| fs.read_link(path))?;
+                if target.starts_with('/') {
+                    parts.clear();
+                }
+                pending.extend(target.split('/').rev().map(String::from));
+            },
+            Ok(meta) if !last && !meta.is_dir() => return Err("Not A Directory"),
+            Ok(_) => parts.push(name),
+            Err(_) if last => parts.push(name),
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(join_parts(&parts, ""))
+}
+
+fn join_parts(parts : &[String], name : &str) -> String {
+    let mut path = String::new();
+    for part in parts.iter().map(String::as_str).chain(Some(name).filter(|name| !name.is_empty())) {
+        path.push('/');
+        path.push_str(part);
+    }
+    if path.is_empty() {
+        path.push('/');
+    }
+    path
+}
+
+pub fn dirname(path : &str) -> &str {
+    match path.rfind('/') {
+        Some(0) | None => "/",
+        Some(index) => &path[..index],
+    }
+}
+
+pub fn filename(path : &str) -> &str {
+    path.rsplit('/').next().unwrap_or("")
+}
+
+/// Where `path` falls under `mount`, if it does.
+fn relative<'a>(mount : &str, path : &'a str) -> Option<&'a str> {
+    if mount == "/" {
+        return Some(path);
+    }
+    let rest = path.strip_prefix(mount)?;
+    match rest {
+        "" => Some("/"),
+        rest if rest.starts_with('/') => Some(rest),
+        _ => None,
+    }
+}
+
+/// Runs `f` on the filesystem with the longest mount point containing `path`,
+/// after following any symlinks in it.
+pub fn with_fs<T, F : FnOnce(&mut dyn FileSystem, &str) -> Result<T, &'static str>>(path : &str, f : F) -> Result<T, &'static str> {
+    with_mount(&resolve(path, true)?, f)
+}
+
+/// Like [with_fs] for a path [resolve] already gave.
+fn with_mount<T, F : FnOnce(&mut dyn FileSystem, &str) -> Result<T, &'static str>>(path : &str, f : F) -> Result<T, &'static str> {
+    let mut result = Err("No Filesystem Mounted");
+    without_interrupts(|| {
+        let mut mounts = MOUNTS.lock();
+        let best = mounts.iter_mut()
+            .filter_map(|mount| relative(&mount.path, &path).map(|rest| (mount.path.len(), rest, mount)))
+            .max_by_key(|(length, _, _)| *length);
+        if let Some((_, rest, mount)) = best {
+            let rest = String::from(rest);
+            result = f(mount.fs.as_mut(), &rest);
+        }
+    });
+    result
+}
+
+/// The mount point of the filesystem holding `path`, a resolved path.
+fn mount_point(path : &str) -> Option<String> {
+    without_interrupts(|| {
+        MOUNTS.lock().iter()
+            .filter(|mount| relative(&mount.path, path).is_some())
+            .max_by_key(|mount| mount.path.len())
+            .map(|mount| mount.path.clone())
+    })
+}
+
+pub fn mount(path : &str, fs : Box<dyn FileSystem>) -> Result<(), &'static str> {
+    let path = resolve(path, true)?;
+    if path != "/" && !metadata(&path).map_or(false, |meta| meta.is_dir()) {
+        return Err("Mount Point Is Not A Directory");
+    }
+    without_interrupts(|| {
+        let mut mounts = MOUNTS.lock();
+        if mounts.iter().any(|mount| mount.path == path) {
+            return Err("Already Mounted");
+        }
+        mounts.push(Mount { path, fs });
+        Ok(())
+    })
+}
+
+pub fn unmount(path : &str) -> Result<(), &'static str> {
+    let path = resolve(path, true)?;
+    without_interrupts(|| {
+        let mut mounts = MOUNTS.lock();
+        if mounts.iter().any(|mount| mount.path != path && relative(&path, &mount.path).is_some()) {
+            return Err("Filesystem Is Busy");
+        }
+        let before = mounts.len();
+        mounts.retain(|mount| mount.path != path);
+        if mounts.len() == before { Err("Not Mounted") } else { Ok(()) }
+    })
+}
+
+/// Lists mount points and the name of the filesystem on each.
+pub fn mounts() -> Vec<(String, &'static str)> {
+    without_interrupts(|| {
+        MOUNTS.lock().iter().map(|mount| (mount.path.clone(), mount.fs.name())).collect()
+    })
+}
+
+pub fn metadata(path : &str) -> Result<Metadata, &'static str> {
+    with_fs(path, |fs, path| fs.metadata(path))
+}
+
+/// Like [metadata], but describes a symlink itself rather than what it points at.
+pub fn symlink_metadata(path : &str) -> Result<Metadata, &'static str> {
+    with_mount(&resolve(path, false)?, |fs, path| fs.metadata(path))
+}
+
+pub fn exists(path : &str) -> bool {
+    metadata(path).is_ok()
+}
+
+/// Whether `credentials` may `access` a node with `meta`. Root may read and
+/// write anything, but only execute files with at least one execute bit.
+pub fn permits(meta : &Metadata, credentials : &Credentials, access : u32) -> bool {
+    if credentials.is_root() {
+        return access & ACCESS_EXEC == 0 || meta.is_dir() || meta.mode & 0o111 != 0;
+    }
+    let bits = if credentials.uid == meta.uid {
+        meta.mode >> 6
+    } else if credentials.in_group(meta.gid) {
+        meta.mode >> 3
+    } else {
+        meta.mode
+    } & 0o7;
+    bits & access == access
+}
+
+/// Checks the current task can `access` `path`, and search every directory above it.
+pub fn access(path : &str, access : u32) -> Result<Metadata, &'static str> {
+    let path = resolve(path, true)?;
+    let credentials = task::credentials();
+    search_parents(&path, &credentials)?;
+    let meta = with_mount(&path, |fs, path| fs.metadata(path))?;
+    if permits(&meta, &credentials, access) { Ok(meta) } else { Err("Permission Denied") }
+}
+
+fn search_parents(path : &str, credentials : &Credentials) -> Result<(), &'static str> {
+    let mut dir = String::new();
+    let parents = path.trim_start_matches('/').split('/').collect::<Vec<_>>();
+    for name in parents.iter().take(parents.len().saturating_sub(1)) {
+        dir.push('/');
+        dir.push_str(name);
+        let meta = with_mount(&dir, |fs, path| fs.metadata(path))?;
+        if !meta.is_dir() {
+            return Err("Not A Directory");
+        }
+        if !permits(&meta, credentials, ACCESS_EXEC) {
+            return Err("Permission Denied");
+        }
+    }
+    let root = with_mount("/", |fs, path| fs.metadata(path))?;
+    if permits(&root, credentials, ACCESS_EXEC) { Ok(()) } else { Err("Permission Denied") }
+}
+
+/// Checks the current task can add or remove names in the directory holding
+/// `path`, a resolved path.
+fn check_modify_parent(path : &str) -> Result<(), &'static str> {
+    access(dirname(path), ACCESS_WRITE | ACCESS_EXEC).map(|_| ())
+}
+
+/// Checks `path` is a regular file the current task may execute.
+pub fn check_exec(path : &str) -> Result<(), &'static str> {
+    let meta = access(path, ACCESS_EXEC)?;
+    if meta.is_file() { Ok(()) } else { Err("Permission Denied") }
+}
+
+pub fn read_dir(path : &str) -> Result<Vec<DirEntry>, &'static str> {
+    access(path, ACCESS_READ)?;
+    with_fs(path, |fs, path| fs.read_dir(path))
+}
+
+// Device nodes on any filesystem, like those unpacked from the initramfs or
+// made on a TinixFS disk, read and write the device registered with their numbers.
+// FIFOs read and write their pipe without waiting, an `OpenFile` holds an end
+// of it and waits, reporting a read or write that could only wait as "Deadlock".
+
+fn read_node(path : &str, meta : &Metadata, offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+    if meta.is_device() {
+        devfs::read_device(meta.device, offset, buffer)
+    } else if meta.is_fifo() {
+        pipe::read_fifo(&resolve(path, true)?, buffer)
+    } else {
+        with_fs(path, |fs, path| fs.read(path, offset, buffer))
+    }
+}
+
+fn write_node(path : &str, meta : &Metadata, offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+    if meta.is_device() {
+        devfs::write_device(meta.device, offset, buffer)
+    } else if meta.is_fifo() {
+        pipe::write_fifo(&resolve(path, true)?, buffer)
+    } else {
+        with_fs(path, |fs, path| fs.write(path, offset, buffer))
+    }
+}
+
+pub fn read(path : &str, offset : usize, buffer : &mut [u8]) -> Result<usize, &'static str> {
+    let meta = access(path, ACCESS_READ)?;
+    read_node(path, &meta, offset, buffer)
+}
+
+pub fn write(path : &str, offset : usize, buffer : &[u8]) -> Result<usize, &'static str> {
+    let meta = access(path, ACCESS_WRITE)?;
+    write_node(path, &meta, offset, buffer)
+}
+
+pub fn read_file(path : &str) -> Result<Vec<u8>, &'static str> {
+    access(path, ACCESS_READ)?;
+    with_fs(path, |fs, path| {
+        let meta = fs.metadata(path)?;
+        if meta.is_dir() {
+            return Err("Is A Directory");
+        }
+        let mut data = alloc::vec![0; meta.size];
+        let count = fs.read(path, 0, &mut data)?;
+        data.truncate(count);
+        Ok(data)
+    })
+}
+
+/// Creates `path` if needed and replaces its contents with `data`.
+pub fn write_file(path : &str, data : &[u8]) -> Result<(), &'static str> {
+    if exists(path) {
+        access(path, ACCESS_WRITE)?;
+    } else {
+        create(path, NodeKind::File)?;
+    }
+    with_fs(path, |fs, path| {
+        fs.truncate(path, 0)?;
+        fs.write(path, 0, data)?;
+        Ok(())
+    })
+}
+
+/// Creates a node owned by the current task.
+pub fn create(path : &str, kind : NodeKind) -> Result<(), &'static str> {
+    let path = resolve(path, false)?;
+    check_modify_parent(&path)?;
+    let credentials = task::credentials();
+    with_mount(&path, |fs, path| {
+        fs.create(path, kind)?;
+        give_to(fs, path, &credentials)
+    })
+}
+
+/// Makes the node just created at `path` owned by `credentials`, removing it
+/// again if the filesystem keeps owners but can't store these.
+fn give_to(fs : &mut dyn FileSystem, path : &str, credentials : &Credentials) -> Result<(), &'static str> {
+    match fs.set_owner(path, credentials.uid, credentials.gid) {
+        Ok(()) | Err("Operation Not Supported") => Ok(()),
+        Err(error) => {
+            let _ = fs.remove(path);
+            Err(error)
+        },
+    }
+}
+
+pub fn create_file(path : &str) -> Result<(), &'static str> {
+    create(path, NodeKind::File)
+}
+
+pub fn create_dir(path : &str) -> Result<(), &'static str> {
+    create(path, NodeKind::Directory)
+}
+
+/// Creates a named pipe. Data written to it stays until it's read, even when
+/// no reader has it open yet.
+pub fn mkfifo(path : &str) -> Result<(), &'static str> {
+    create(path, NodeKind::Fifo)
+}
+
+pub fn remove(path : &str) -> Result<(), &'static str> {
+    let path = resolve(path, false)?;
+    if mounts().iter().any(|(mount, _)| *mount == path) {
+        return Err("Filesystem Is Busy");
+    }
+    check_modify_parent(&path)?;
+
+    // In a sticky directory only the owner of a file, or of the directory, may remove it.
+    let credentials = task::credentials();
+    let parent = metadata(dirname(&path))?;
+    let meta = with_mount(&path, |fs, path| fs.metadata(path))?;
+    if parent.mode & MODE_STICKY != 0 && !credentials.is_root() {
+        if credentials.uid != meta.uid && credentials.uid != parent.uid {
+            return Err("Permission Denied");
+        }
+    }
+    with_mount(&path, |fs, path| fs.remove(path))?;
+    if meta.is_fifo() {
+        pipe::remove_fifo(&path);
+    }
+    Ok(())
+}
+
+/// Creates a symlink at `path`. The target is stored as given and may not exist.
+pub fn symlink(target : &str, path : &str) -> Result<(), &'static str> {
+    let path = resolve(path, false)?;
+    check_modify_parent(&path)?;
+    let credentials = task::credentials();
+    with_mount(&path, |fs, path| {
+        fs.symlink(target, path)?;
+        give_to(fs, path, &credentials)
+    })
+}
+
+pub fn read_link(path : &str) -> Result<String, &'static str> {
+    let path = resolve(path, false)?;
+    search_parents(&path, &task::credentials())?;
+    with_mount(&path, |fs, path| fs.read_link(path))
+}
+
+/// Adds `path` as a hard link to `target`. Both must be on the same filesystem.
+pub fn link(target : &str, path : &str) -> Result<(), &'static str> {
+    let (target, path) = (resolve(target, false)?, resolve(path, false)?);
+    search_parents(&target, &task::credentials())?;
+    check_modify_parent(&path)?;
+    let mount = mount_point(&target);
+    if mount != mount_point(&path) {
+        return Err("Cross-Device Link");
+    }
+    let relative_target = mount.as_deref().and_then(|mount| relative(mount, &target)).map(String::from);
+    let relative_target = relative_target.ok_or("No Filesystem Mounted")?;
+    with_mount(&path, |fs, path| fs.link(&relative_target, path))
+}
+
+/// Changes the current task's working directory to the directory at `path`.
+pub fn chdir(path : &str) -> Result<(), &'static str> {
+    let path = resolve(path, true)?;
+    let meta = access(&path, ACCESS_EXEC)?;
+    if !meta.is_dir() {
+        return Err("Not A Directory");
+    }
+    task::set_cwd(&path);
+    Ok(())
+}
+
+pub fn getcwd() -> String {
+    task::cwd()
+}
+
+/// Changes the permission bits of `path`. Only its owner or root may.
+pub fn chmod(path : &str, mode : u32) -> Result<(), &'static str> {
+    let meta = access(path, 0)?;
+    let credentials = task::credentials();
+    if !credentials.is_root() && credentials.uid != meta.uid {
+        return Err("Permission Denied");
+    }
+    with_fs(path, |fs, path| fs.set_mode(path, mode & 0o7777))
+}
+
+/// Changes the owner and group of `path`. Only root may give a file away, but
+/// its owner may change the group to one they belong to.
+pub fn chown(path : &str, uid : Uid, gid : Gid) -> Result<(), &'static str> {
+    let meta = access(path, 0)?;
+    let credentials = task::credentials();
+    if !credentials.is_root() && (uid != meta.uid || credentials.uid != meta.uid || !credentials.in_group(gid)) {
+        return Err("Permission Denied");
+    }
+    with_fs(path, |fs, path| fs.set_owner(path, uid, gid))
+}
+
+pub const OPEN_READ     : u32 = 1 << 0;
+pub const OPEN_WRITE    : u32 = 1 << 1;
+pub const OPEN_CREATE   : u32 = 1 << 2;
+pub const OPEN_TRUNCATE : u32 = 1 << 3;
+pub const OPEN_APPEND   : u32 = 1 << 4;
+
+/// A file opened with [open]; permission checks happen once, when it's opened.
+#[derive(Debug, Clone)]
+pub struct OpenFile {
+    path   : String,
+    meta   : Metadata,
+    offset : usize,
+    flags  : u32,
+    pipe   : Option<PipeEnd>, // Set for FIFOs.
+}
+
+pub fn open(path : &str, flags : u32) -> Result<OpenFile, &'static str> {
+    let path = resolve(path, true)?;
+    if !exists(&path) {
+        if flags & OPEN_CREATE == 0 {
+            return Err("No Such File Or Directory");
+        }
+        create(&path, NodeKind::File)?;
+    }
+
+    let mut wanted = 0;
+    if flags & OPEN_READ != 0 {
+        wanted |= ACCESS_READ;
+    }
+    if flags & (OPEN_WRITE | OPEN_TRUNCATE | OPEN_APPEND) != 0 {
+        wanted |= ACCESS_WRITE;
+    }
+    let meta = access(&path, wanted)?;
+    if meta.is_dir() && wanted & ACCESS_WRITE != 0 {
+        return Err("Is A Directory");
+    }
+    if flags & OPEN_TRUNCATE != 0 && !meta.is_device() && !meta.is_fifo() {
+        with_fs(&path, |fs, path| fs.truncate(path, 0))?;
+    }
+    let offset = if flags & OPEN_APPEND != 0 { meta.size } else { 0 };
+
+    // A FIFO opened for writing is the write end, otherwise the read end.
+    let pipe = if meta.is_fifo() {
+        Some(pipe::open_fifo(&path, wanted & ACCESS_WRITE != 0))
+    } else {
+        None
+    };
+    Ok(OpenFile { path, meta, offset, flags, pipe })
+}
+
+impl OpenFile {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn seek(&mut self, offset : usize) {
+        self.offset = offset;
+    }
+
+    pub fn metadata(&self) -> Result<Metadata, &'static str> {
+        metadata(&self.path)
+    }
+
+    pub fn read(&mut self, buffer : &mut [u8]) -> Result<usize, &'static str> {
+        if self.flags & OPEN_READ == 0 {
+            return Err("File Not Open For Reading");
+        }
+        match &mut self.pipe {
+            Some(PipeEnd::Reader(reader)) => return reader.read(buffer),
+            Some(PipeEnd::Writer(_)) => return Err("File Not Open For Reading"),
+            None => {},
+        }
+        let count = read_node(&self.path, &self.meta, self.offset, buffer)?;
+        self.offset += count;
+        Ok(count)
+    }
+
+    pub fn write(&mut self, buffer : &[u8]) -> Result<usize, &'static str> {
+        if self.flags & (OPEN_WRITE | OPEN_APPEND) == 0 {
+            return Err("File Not Open For Writing");
+        }
+        if let Some(PipeEnd::Writer(writer)) = &mut self.pipe {
+            return writer.write(buffer);
+        }
+        if self.flags & OPEN_APPEND != 0 && !self.meta.is_device() {
+            self.offset = metadata(&self.path)?.size;
+        }
+        let count = write_node(&self.path, &self.meta, self.offset, buffer)?;
+        self.offset += count;
+        Ok(count)
+    }
+
+    pub fn read_to_end(&mut self) -> Result<Vec<u8>, &'static str> {
+        let mut data = Vec::new();
+        let mut buffer = [0; 512];
+        loop {
+            let count = self.read(&mut buffer)?;
+            if count == 0 {
+                return Ok(data);
+            }
+            data.extend_from_slice(&buffer[..count]);
+        }
+    }
+}
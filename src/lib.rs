@@ -6,6 +6,8 @@
 #![feature(asm)]
 #![feature(custom_test_frameworks)]
 #![test_runner(crate::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+#![cfg_attr(test, no_main)]
 
 
 #[warn(missing_docs)]
@@ -29,8 +31,9 @@ fn on_panic(_info : &PanicInfo) -> ! {
     without_interrupts(|| {
         crate::io::devices::console::clear();
         crate::io::devices::console::home();
-        log!("== PANIC ==\nInfo: \n{}", _info);
-        log!("\n");
+        elog!("== PANIC ==\nInfo: \n{}", _info);
+        elog!("\n");
+        kernel::arch::x64::backtrace::print_here();
     });
     loop {user::time::sleep_ticks(1000)}
 }
@@ -48,11 +51,15 @@ pub macro entry_point($path : path) {
 
 
 
-        let main : fn(&'static bootloader::BootInfo, args : &$crate::user::Arguments) -> (usize) = $path;
+        let _main : fn(&'static bootloader::BootInfo, args : &$crate::user::Arguments) -> (usize) = $path;
         
         $crate::kernel::boot(boot_info);
 
-        main(boot_info, &$crate::user::Arguments::empty());
+        let args = $crate::user::Arguments::empty();
+        let status = $crate::kernel::task::run("main",
+            |start : &(&'static bootloader::BootInfo, &$crate::user::Arguments)| $path(start.0, start.1),
+            &(boot_info, &args));
+        $crate::log!("[main] Exited With Status {}\n", status);
         
         loop {user::time::sleep_ticks(100)}
     }
@@ -107,6 +114,18 @@ pub fn test_runner(tests: &[&dyn Fn()]) {
     }
 }
 
+// `cargo test --lib` builds the library as its own kernel, which boots and runs the tests.
+#[cfg(test)]
+bootloader::entry_point!(test_kernel_main);
+
+#[cfg(test)]
+fn test_kernel_main(boot_info : &'static bootloader::BootInfo) -> ! {
+    kernel::boot(boot_info);
+    test_main();
+    println!("All Tests Passed");
+    loop {user::time::sleep_ticks(100)}
+}
+
 #[macro_export]
 macro_rules! size_of {
     ($item : ty) => {
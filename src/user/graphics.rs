@@ -1,76 +1,147 @@
 pub mod widgets;
+pub mod framebuffer;
+pub mod raster;
+pub mod font;
+pub mod image;
 
 pub use crate::kernel::drivers::vga_dr as vga;
 
 pub use crate::kernel::drivers::vga_dr::Color16 as Color;
 
-use crate::user::math::*;
+use lazy_static::lazy_static;
+use spin::RwLock;
+
+use font::{Font, Style};
+use raster::Screen;
 
 pub use crate::kernel::drivers::vga_dr::draw_line as draw_line;
 pub use crate::kernel::drivers::vga_dr::draw as draw;
-pub use crate::kernel::drivers::vga_dr::draw_str as draw_str;
-pub use crate::kernel::drivers::vga_dr::draw_chr as draw_chr;
 pub use crate::kernel::drivers::vga_dr::clear_screen as clear_screen;
 
 
 pub const WIDTH  : usize = 640;
 pub const HEIGHT : usize = 480;
 
+/// A rectangle on screen, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub x      : isize,
+    pub y      : isize,
+    pub width  : usize,
+    pub height : usize,
+}
+
+impl Rect {
+    pub const fn new(x : isize, y : isize, width : usize, height : usize) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn screen() -> Self {
+        Self::new(0, 0, WIDTH, HEIGHT)
+    }
+
+    pub fn right(&self) -> isize {
+        self.x + self.width as isize
+    }
+
+    pub fn bottom(&self) -> isize {
+        self.y + self.height as isize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn contains(&self, x : isize, y : isize) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    pub fn intersection(&self, other : &Rect) -> Rect {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let (right, bottom) = (self.right().min(other.right()), self.bottom().min(other.bottom()));
+        Rect::new(x, y, (right - x).max(0) as usize, (bottom - y).max(0) as usize)
+    }
+
+    /// The smallest rectangle covering both.
+    pub fn union(&self, other : &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let (right, bottom) = (self.right().max(other.right()), self.bottom().max(other.bottom()));
+        Rect::new(x, y, (right - x) as usize, (bottom - y) as usize)
+    }
+
+    /// Shrinks the rectangle by `amount` on every side.
+    pub fn inset(&self, amount : usize) -> Rect {
+        Rect::new(
+            self.x + amount as isize, self.y + amount as isize,
+            self.width.saturating_sub(amount * 2), self.height.saturating_sub(amount * 2),
+        )
+    }
+}
+
 
 pub use core::fmt::*;
 
+lazy_static! {
+    /// The font text is drawn with, the card's 8x8 one until [set_font].
+    static ref FONT : RwLock<Font> = RwLock::new(Font::builtin_8x8());
+}
+
+/// Draws text with `font` from now on, widgets included.
+pub fn set_font(font : Font) {
+    *FONT.write() = font;
+}
+
+pub fn with_font<T, F : FnOnce(&Font) -> T>(f : F) -> T {
+    f(&FONT.read())
+}
+
 pub fn font_height() -> usize {
-    vga::fonts::TEXT_8X8_FONT.character_height as usize
+    with_font(|font| font.height())
 }
 
 pub fn font_width() -> usize {
-    8
+    with_font(|font| font.width())
+}
+
+pub fn draw_chr(x : usize, y : usize, chr : char, color : Color) {
+    with_font(|font| font.draw_char(&mut Screen::new(), x as isize, y as isize, chr, color, Style::new()));
+}
+
+/// Draws `text` from (`x`, `y`), wrapping back to `x` on a newline or at the right edge.
+pub fn draw_str(x : usize, y : usize, text : &str, color : Color) {
+    with_font(|font| font.draw_text(&mut Screen::new(), x as isize, y as isize, text, color, Style::new()));
 }
 
 pub fn draw_rect(pos : (isize, isize), dim : (isize, isize), color : Color) {
-    vga::draw_line((pos.0, pos.1), (pos.0 + dim.0, pos.1), color);
-    vga::draw_line((pos.0 + dim.0, pos.1), (pos.0 + dim.0, pos.1 + dim.1), color);
-    vga::draw_line((pos.0 + dim.0, pos.1 + dim.1), (pos.0, pos.1 + dim.1), color);
-    vga::draw_line((pos.0, pos.1 + dim.1), (pos.0, pos.1), color);
+    let (right, bottom) = (pos.0 + dim.0, pos.1 + dim.1);
+    raster::draw_polygon(&mut Screen::new(), &[pos, (right, pos.1), (right, bottom), (pos.0, bottom)], color);
 }
 
 pub fn draw_filled_rect(pos : (isize, isize), dim : (isize, isize), color : Color) {
-    for y in pos.1 ..= dim.1 {
-        vga::draw_line((pos.0, y),(pos.0 + dim.0, y),color);
+    if dim.0 > 0 && dim.1 > 0 {
+        raster::fill_rect(&mut Screen::new(), Rect::new(pos.0, pos.1, dim.0 as usize, dim.1 as usize), color);
     }
 }
 
 pub fn draw_triangle(p0 : (isize, isize), p1 : (isize, isize), p2 : (isize, isize), color : Color) {
-    vga::draw_line(p0, p1, color);
-    vga::draw_line(p1, p2, color);
-    vga::draw_line(p2, p0, color);
+    raster::draw_triangle(&mut Screen::new(), p0, p1, p2, color);
 }
 
+/// Fills a triangle, edges and corners included.
 pub fn fill_triangle(p0 : (isize, isize), p1 : (isize, isize), p2 : (isize, isize), color : Color) {
-    let min_y = mini(mini(p0.1, p1.1),p2.1);
-    let max_y = maxi(maxi(p0.1, p1.1),p2.1);
-
-    let min_x = mini(mini(p0.0, p1.0),p2.0);
-    let max_x = maxi(maxi(p0.0, p1.0),p2.0);
-
-    // Debugging
-    draw_rect((min_x, min_y),(max_x, max_y),Color::Blue);
-    draw_triangle(p0,p1,p2,color);
+    let mut screen = Screen::new();
+    raster::fill_triangle(&mut screen, p0, p1, p2, color);
+    raster::draw_triangle(&mut screen, p0, p1, p2, color);
 }
 
 pub fn cross(pos : (isize, isize), size : isize, color : Color) {
-    let half_size = size / 2;
-    draw_line(
-        (pos.0 - half_size, pos.1 - half_size),
-        (pos.0 + half_size, pos.1 + half_size),
-        color
-    );
-
-    draw_line(
-        (pos.0 - half_size, pos.1 - half_size),
-        (pos.0 + half_size, pos.1 + half_size),
-        color
-    );
+    raster::cross(&mut Screen::new(), pos, size, color);
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -91,6 +162,19 @@ impl<'a> Image16<'a> {
         }
     }
 
+    pub fn width(&self) -> usize {
+        self.width as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.height as usize
+    }
+
+    /// The palette index of a pixel, 0 to 15.
+    pub fn color_index(&self, x : usize, y : usize) -> u8 {
+        self.data[y * self.width as usize + x] % 16
+    }
+
     pub fn draw(&self, ox : usize, oy : usize) {
         for y in 0..self.height as u16 {
             for x in 0..self.width {
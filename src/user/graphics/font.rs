@@ -0,0 +1,262 @@
+use alloc::{borrow::Cow, collections::BTreeMap, vec::Vec};
+
+use super::raster::Canvas;
+use super::{vga, Color};
+use crate::kernel::vfs;
+
+const PSF1_MAGIC : [u8; 2] = [0x36, 0x04];
+const PSF1_MODE_512 : u8 = 0x01;
+const PSF1_MODE_HAS_TABLE : u8 = 0x06;
+const PSF1_SEPARATOR : u16 = 0xFFFF;
+const PSF1_SEQUENCE : u16 = 0xFFFE;
+
+const PSF2_MAGIC : [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+const PSF2_HAS_TABLE : u32 = 0x01;
+const PSF2_HEADER_SIZE : usize = 32;
+const PSF2_SEPARATOR : u8 = 0xFF;
+const PSF2_SEQUENCE : u8 = 0xFE;
+
+/// How text is drawn: each pixel of a glyph becomes a `scale` x `scale`
+/// square, and bold glyphs are drawn twice a pixel apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub scale : usize,
+    pub bold  : bool,
+}
+
+impl Style {
+    pub const fn new() -> Self {
+        Self { scale : 1, bold : false }
+    }
+
+    pub const fn scaled(self, scale : usize) -> Self {
+        Self { scale, bold : self.bold }
+    }
+
+    pub const fn bold(self) -> Self {
+        Self { scale : self.scale, bold : true }
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A monospaced bitmap font, either one of the card's own or a PC Screen Font
+/// (PSF1 or PSF2) file. Rows are padded to whole bytes, leftmost pixel in the top bit.
+#[derive(Debug, Clone)]
+pub struct Font {
+    width   : usize,
+    height  : usize,
+    count   : usize,
+    glyphs  : Cow<'static, [u8]>,
+    /// Which glyph draws each character, `None` if glyphs are in character order.
+    unicode : Option<BTreeMap<char, usize>>,
+}
+
+impl Font {
+    pub fn builtin_8x8() -> Self {
+        Self::from_vga(&vga::fonts::TEXT_8X8_FONT)
+    }
+
+    pub fn builtin_8x16() -> Self {
+        Self::from_vga(&vga::fonts::TEXT_8X16_FONT)
+    }
+
+    fn from_vga(font : &'static vga::fonts::VgaFont) -> Self {
+        let height = font.character_height as usize;
+        Self { width : 8, height, count : font.font_data.len() / height, glyphs : Cow::Borrowed(font.font_data), unicode : None }
+    }
+
+    /// Reads a PSF1 or PSF2 file.
+    pub fn load(path : &str) -> Result<Self, &'static str> {
+        Self::parse(&vfs::read_file(path)?)
+    }
+
+    pub fn parse(data : &[u8]) -> Result<Self, &'static str> {
+        if data.starts_with(&PSF2_MAGIC) {
+            Self::parse_psf2(data)
+        } else if data.starts_with(&PSF1_MAGIC) {
+            Self::parse_psf1(data)
+        } else {
+            Err("Not A PSF Font")
+        }
+    }
+
+    fn parse_psf1(data : &[u8]) -> Result<Self, &'static str> {
+        if data.len() < 4 {
+            return Err("Truncated Font");
+        }
+        let (mode, height) = (data[2], data[3] as usize);
+        let count = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+        let glyphs = glyph_data(data, 4, count, height)?;
+
+        let unicode = if mode & PSF1_MODE_HAS_TABLE != 0 {
+            let mut map = BTreeMap::new();
+            let mut entries = data[4 + glyphs.len()..].chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+            for glyph in 0..count {
+                let mut in_sequence = false;
+                for entry in &mut entries {
+                    match entry {
+                        PSF1_SEPARATOR => break,
+                        PSF1_SEQUENCE => in_sequence = true,
+                        _ if !in_sequence => if let Some(c) = core::char::from_u32(entry as u32) {
+                            map.entry(c).or_insert(glyph);
+                        },
+                        _ => {},
+                    }
+                }
+            }
+            Some(map)
+        } else {
+            None
+        };
+
+        Ok(Self { width : 8, height, count, glyphs : Cow::Owned(glyphs.to_vec()), unicode })
+    }
+
+    fn parse_psf2(data : &[u8]) -> Result<Self, &'static str> {
+        if data.len() < PSF2_HEADER_SIZE {
+            return Err("Truncated Font");
+        }
+        let field = |index : usize| {
+            let offset = 4 + index * 4;
+            u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+        };
+        let (header_size, flags, count) = (field(1) as usize, field(2), field(3) as usize);
+        let (glyph_size, height, width) = (field(4) as usize, field(5) as usize, field(6) as usize);
+        if width == 0 || glyph_size != height * ((width + 7) / 8) || header_size < PSF2_HEADER_SIZE {
+            return Err("Invalid Font Header");
+        }
+        let glyphs = glyph_data(data, header_size, count, glyph_size)?;
+
+        let unicode = if flags & PSF2_HAS_TABLE != 0 {
+            let mut map = BTreeMap::new();
+            let mut entries = data[header_size + glyphs.len()..].split(|byte| *byte == PSF2_SEPARATOR);
+            for glyph in 0..count {
+                let entry = match entries.next() {
+                    Some(entry) => entry,
+                    None => break,
+                };
+                // Characters on their own come first, then any sequences.
+                let singles = entry.split(|byte| *byte == PSF2_SEQUENCE).next().unwrap_or(&[]);
+                for c in core::str::from_utf8(singles).map_err(|_| "Invalid Font Unicode Table")?.chars() {
+                    map.entry(c).or_insert(glyph);
+                }
+            }
+            Some(map)
+        } else {
+            None
+        };
+
+        Ok(Self { width, height, count, glyphs : Cow::Owned(glyphs.to_vec()), unicode })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn glyph_count(&self) -> usize {
+        self.count
+    }
+
+    fn bytes_per_row(&self) -> usize {
+        (self.width + 7) / 8
+    }
+
+    fn index(&self, c : char) -> Option<usize> {
+        match &self.unicode {
+            Some(map) => map.get(&c).copied(),
+            None => Some(c as usize).filter(|index| *index < self.count),
+        }
+    }
+
+    pub fn has_glyph(&self, c : char) -> bool {
+        self.index(c).is_some()
+    }
+
+    /// The rows of the glyph for `c`, or of `?` if the font has none.
+    pub fn glyph(&self, c : char) -> &[u8] {
+        let index = self.index(c).or_else(|| self.index('?')).unwrap_or(0);
+        let size = self.height * self.bytes_per_row();
+        &self.glyphs[index * size..(index + 1) * size]
+    }
+
+    /// How far apart characters are drawn.
+    pub fn advance(&self, style : Style) -> usize {
+        self.width * style.scale + style.bold as usize
+    }
+
+    pub fn line_height(&self, style : Style) -> usize {
+        self.height * style.scale
+    }
+
+    /// The width and height `text` takes, lines being split at `\n`.
+    pub fn measure(&self, text : &str, style : Style) -> (usize, usize) {
+        let longest = text.split('\n').map(|line| line.chars().count()).max().unwrap_or(0);
+        (longest * self.advance(style), text.split('\n').count() * self.line_height(style))
+    }
+
+    pub fn draw_char<C : Canvas + ?Sized>(&self, canvas : &mut C, x : isize, y : isize, c : char, color : Color, style : Style) {
+        let (scale, bytes_per_row) = (style.scale.max(1) as isize, self.bytes_per_row());
+        for (row, bits) in self.glyph(c).chunks(bytes_per_row).enumerate() {
+            for column in 0..self.width {
+                if bits[column / 8] & (0x80 >> (column % 8)) == 0 {
+                    continue;
+                }
+                let (left, top) = (x + column as isize * scale, y + row as isize * scale);
+                for offset in 0..scale {
+                    canvas.span(left, left + scale - 1 + style.bold as isize, top + offset, color);
+                }
+            }
+        }
+    }
+
+    /// Draws `text` from (`x`, `y`), starting again at `x` on a newline or
+    /// when a character would go past the right of the canvas's clip.
+    pub fn draw_text<C : Canvas + ?Sized>(&self, canvas : &mut C, x : isize, y : isize, text : &str, color : Color, style : Style) {
+        let (advance, line_height) = (self.advance(style) as isize, self.line_height(style) as isize);
+        let right = canvas.clip().right();
+        let (mut column, mut row) = (x, y);
+        for c in text.chars() {
+            if c == '\n' || (column + advance > right && column > x) {
+                column = x;
+                row += line_height;
+            }
+            if c != '\n' {
+                self.draw_char(canvas, column, row, c, color, style);
+                column += advance;
+            }
+        }
+    }
+
+    /// Makes this the text mode font. It has to be 8 pixels wide; the first
+    /// 128 characters are taken by code point, the rest by glyph order.
+    pub fn load_text_mode(&self) -> Result<(), &'static str> {
+        if self.width != 8 {
+            return Err("Text Mode Fonts Must Be 8 Pixels Wide");
+        }
+        let mut glyphs = Vec::with_capacity(vga::TEXT_GLYPHS * self.height);
+        for slot in 0..vga::TEXT_GLYPHS {
+            let index = if slot < 128 { self.index(slot as u8 as char) } else { None };
+            let index = index.unwrap_or(slot % self.count);
+            glyphs.extend_from_slice(&self.glyphs[index * self.height..(index + 1) * self.height]);
+        }
+        vga::load_text_font(&glyphs, self.height)
+    }
+}
+
+/// The `count` glyphs of `size` bytes starting at `offset`.
+fn glyph_data(data : &[u8], offset : usize, count : usize, size : usize) -> Result<&[u8], &'static str> {
+    if size == 0 || count == 0 {
+        return Err("Invalid Font Header");
+    }
+    let end = count.checked_mul(size).and_then(|length| length.checked_add(offset)).ok_or("Invalid Font Header")?;
+    data.get(offset..end).ok_or("Truncated Font")
+}
@@ -0,0 +1,223 @@
+use alloc::{vec, vec::Vec};
+
+use super::font::Style;
+use super::{vga, Color, Image16, Rect, COLORS, HEIGHT, WIDTH};
+
+/// A picture with a color key: pixels of the `transparent` color are skipped
+/// when it's blitted.
+#[derive(Debug, Clone)]
+pub struct Sprite {
+    width  : usize,
+    height : usize,
+    pixels : Vec<u8>,
+    transparent : Option<u8>,
+}
+
+impl Sprite {
+    pub fn new(width : usize, height : usize, fill : Color) -> Self {
+        Self { width, height, pixels : vec![u8::from(fill); width * height], transparent : None }
+    }
+
+    pub fn from_image(image : &Image16, transparent : Option<Color>) -> Self {
+        let mut pixels = Vec::with_capacity(image.width() * image.height());
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                pixels.push(image.color_index(x, y));
+            }
+        }
+        Self { width : image.width(), height : image.height(), pixels, transparent : transparent.map(u8::from) }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn set_transparent(&mut self, color : Option<Color>) {
+        self.transparent = color.map(u8::from);
+    }
+
+    pub fn set_pixel(&mut self, x : usize, y : usize, color : Color) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = u8::from(color);
+        }
+    }
+}
+
+/// The 640x480x16 screen kept in RAM, one byte per pixel. Drawing here is
+/// cheap and never flickers; [FrameBuffer::present] copies what changed to
+/// the card, converting it to planes a byte at a time.
+pub struct FrameBuffer {
+    pixels : Vec<u8>,
+    clip   : Rect,
+    dirty  : Rect, // Drawn to since the last `present`.
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        Self { pixels : vec![u8::from(Color::Black); WIDTH * HEIGHT], clip : Rect::screen(), dirty : Rect::screen() }
+    }
+
+    pub fn clip(&self) -> Rect {
+        self.clip
+    }
+
+    /// Limits drawing to `clip`, or the whole screen for `None`.
+    pub fn set_clip(&mut self, clip : Option<Rect>) {
+        self.clip = clip.map_or(Rect::screen(), |clip| clip.intersection(&Rect::screen()));
+    }
+
+    /// Clips `rect` and marks what's left as needing to be presented.
+    fn damage(&mut self, rect : Rect) -> Rect {
+        let rect = self.clip.intersection(&rect);
+        self.dirty = self.dirty.union(&rect);
+        rect
+    }
+
+    pub fn pixel(&self, x : usize, y : usize) -> Color {
+        COLORS[self.pixels[y * WIDTH + x] as usize % 16]
+    }
+
+    pub fn set_pixel(&mut self, x : isize, y : isize, color : Color) {
+        let rect = self.damage(Rect::new(x, y, 1, 1));
+        if !rect.is_empty() {
+            self.pixels[y as usize * WIDTH + x as usize] = u8::from(color);
+        }
+    }
+
+    pub fn clear(&mut self, color : Color) {
+        self.fill_rect(Rect::screen(), color);
+    }
+
+    pub fn fill_rect(&mut self, rect : Rect, color : Color) {
+        let rect = self.damage(rect);
+        let color = u8::from(color);
+        for y in rect.y..rect.bottom() {
+            let start = y as usize * WIDTH + rect.x as usize;
+            self.pixels[start..start + rect.width].fill(color);
+        }
+    }
+
+    /// Draws the outline of `rect`, one pixel wide.
+    pub fn draw_rect(&mut self, rect : Rect, color : Color) {
+        self.fill_rect(Rect::new(rect.x, rect.y, rect.width, 1), color);
+        self.fill_rect(Rect::new(rect.x, rect.bottom() - 1, rect.width, 1), color);
+        self.fill_rect(Rect::new(rect.x, rect.y, 1, rect.height), color);
+        self.fill_rect(Rect::new(rect.right() - 1, rect.y, 1, rect.height), color);
+    }
+
+    /// Copies `source` of `from` so its top left lands at (`x`, `y`).
+    pub fn blit(&mut self, from : &FrameBuffer, source : Rect, x : isize, y : isize) {
+        let source = source.intersection(&Rect::screen());
+        let target = self.damage(Rect::new(x, y, source.width, source.height));
+        for row in target.y..target.bottom() {
+            let source_y = (source.y + row - y) as usize;
+            let source_x = (source.x + target.x - x) as usize;
+            let start = row as usize * WIDTH + target.x as usize;
+            let source_start = source_y * WIDTH + source_x;
+            self.pixels[start..start + target.width].copy_from_slice(&from.pixels[source_start..source_start + target.width]);
+        }
+    }
+
+    /// Moves `source` within this buffer, as when scrolling. Overlap is fine.
+    pub fn copy_rect(&mut self, source : Rect, x : isize, y : isize) {
+        let source = source.intersection(&Rect::screen());
+        let target = self.damage(Rect::new(x, y, source.width, source.height));
+        let rows : Vec<isize> = if y > source.y { (target.y..target.bottom()).rev().collect() } else { (target.y..target.bottom()).collect() };
+        for row in rows {
+            let source_start = (source.y + row - y) as usize * WIDTH + (source.x + target.x - x) as usize;
+            let start = row as usize * WIDTH + target.x as usize;
+            self.pixels.copy_within(source_start..source_start + target.width, start);
+        }
+    }
+
+    pub fn blit_sprite(&mut self, sprite : &Sprite, x : isize, y : isize) {
+        let target = self.damage(Rect::new(x, y, sprite.width, sprite.height));
+        for row in target.y..target.bottom() {
+            let sprite_row = (row - y) as usize * sprite.width;
+            for column in target.x..target.right() {
+                let color = sprite.pixels[sprite_row + (column - x) as usize];
+                if Some(color) != sprite.transparent {
+                    self.pixels[row as usize * WIDTH + column as usize] = color;
+                }
+            }
+        }
+    }
+
+    /// Draws an [Image16], skipping pixels of the `transparent` color.
+    pub fn blit_image(&mut self, image : &Image16, x : isize, y : isize, transparent : Option<Color>) {
+        let transparent = transparent.map(u8::from);
+        let target = self.damage(Rect::new(x, y, image.width(), image.height()));
+        for row in target.y..target.bottom() {
+            for column in target.x..target.right() {
+                let color = image.color_index((column - x) as usize, (row - y) as usize);
+                if Some(color) != transparent {
+                    self.pixels[row as usize * WIDTH + column as usize] = color;
+                }
+            }
+        }
+    }
+
+    /// Draws `c` with the font set by [super::set_font].
+    pub fn draw_char(&mut self, x : isize, y : isize, c : char, color : Color) {
+        super::with_font(|font| font.draw_char(self, x, y, c, color, Style::new()));
+    }
+
+    pub fn draw_str(&mut self, x : isize, y : isize, text : &str, color : Color) {
+        super::with_font(|font| font.draw_text(self, x, y, text, color, Style::new()));
+    }
+
+    /// Copies what changed since the last call to the screen.
+    pub fn present(&mut self) {
+        let dirty = self.dirty;
+        self.dirty = Rect::default();
+        self.present_rect(dirty);
+    }
+
+    /// Copies `rect` to the screen, widened to whole bytes of planar memory.
+    pub fn present_rect(&self, rect : Rect) {
+        let rect = rect.intersection(&Rect::screen());
+        if rect.is_empty() {
+            return;
+        }
+        let first_byte = rect.x as usize / 8;
+        let last_byte = (rect.right() as usize + 7) / 8;
+        let mut planes = [[0u8; vga::PLANE_PITCH]; 4];
+        for y in rect.y as usize..rect.bottom() as usize {
+            let row = &self.pixels[y * WIDTH..(y + 1) * WIDTH];
+            for byte in first_byte..last_byte {
+                let mut bits = [0u8; 4];
+                for (bit, color) in row[byte * 8..byte * 8 + 8].iter().enumerate() {
+                    let mask = 0x80 >> bit;
+                    for (plane, bits) in bits.iter_mut().enumerate() {
+                        if color & (1 << plane) != 0 {
+                            *bits |= mask;
+                        }
+                    }
+                }
+                for plane in 0..4 {
+                    planes[plane][byte] = bits[plane];
+                }
+            }
+            vga::write_planes(y, first_byte, [
+                &planes[0][first_byte..last_byte], &planes[1][first_byte..last_byte],
+                &planes[2][first_byte..last_byte], &planes[3][first_byte..last_byte],
+            ]);
+        }
+    }
+
+    /// Copies the whole buffer to the screen.
+    pub fn present_all(&mut self) {
+        self.dirty = Rect::default();
+        self.present_rect(Rect::screen());
+    }
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
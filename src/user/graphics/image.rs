@@ -0,0 +1,329 @@
+use alloc::{vec, vec::Vec};
+
+use super::raster::Canvas;
+use super::{Color, Image16, Rect, COLORS};
+use crate::kernel::vfs;
+
+/// What each of [COLORS] looks like with the card's default palette.
+pub const PALETTE_RGB : [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (0, 0, 170),
+    (0, 170, 0),
+    (0, 170, 170),
+    (170, 0, 0),
+    (170, 0, 170),
+    (170, 85, 0),
+    (170, 170, 170),
+    (85, 85, 85),
+    (85, 85, 255),
+    (85, 255, 85),
+    (85, 255, 255),
+    (255, 85, 85),
+    (255, 85, 255),
+    (255, 255, 85),
+    (255, 255, 255),
+];
+
+/// How colors outside the palette are made up when quantizing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    /// Every pixel becomes the nearest palette color.
+    None,
+    /// Floyd-Steinberg: each pixel's error is spread over the ones to its right and below.
+    FloydSteinberg,
+}
+
+/// An image of any size in palette indices, as decoded from a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    width  : usize,
+    height : usize,
+    pixels : Vec<u8>,
+}
+
+impl Bitmap {
+    pub fn new(width : usize, height : usize, fill : Color) -> Self {
+        Self { width, height, pixels : vec![u8::from(fill); width * height] }
+    }
+
+    /// Reads a BMP, PPM or PGM file, telling them apart by their first bytes.
+    pub fn load(path : &str, dither : Dither) -> Result<Self, &'static str> {
+        let data = vfs::read_file(path)?;
+        match data.get(..2) {
+            Some(b"BM") => decode_bmp(&data, dither),
+            Some([b'P', _]) => decode_pnm(&data, dither),
+            _ => Err("Unknown Image Format"),
+        }
+    }
+
+    /// Turns 8-bit RGB triples, row by row from the top, into palette colors.
+    pub fn quantize(width : usize, height : usize, rgb : &[u8], dither : Dither) -> Result<Self, &'static str> {
+        let count = width.checked_mul(height).ok_or("Image Too Large")?;
+        if count.checked_mul(3) != Some(rgb.len()) {
+            return Err("RGB Data Does Not Match Image Size");
+        }
+        let mut pixels = Vec::with_capacity(count);
+        // Error carried into this row and the next, per channel, one pixel of padding either side.
+        let mut errors = vec![[0i16; 3]; width + 2];
+        let mut next_errors = vec![[0i16; 3]; width + 2];
+        for y in 0..height {
+            for x in 0..width {
+                let source = &rgb[(y * width + x) * 3..(y * width + x) * 3 + 3];
+                let mut wanted = [0i16; 3];
+                for channel in 0..3 {
+                    wanted[channel] = (source[channel] as i16 + errors[x + 1][channel] / 16).max(0).min(255);
+                }
+                let index = nearest(wanted[0] as u8, wanted[1] as u8, wanted[2] as u8);
+                pixels.push(index);
+
+                if dither == Dither::FloydSteinberg {
+                    let (r, g, b) = PALETTE_RGB[index as usize];
+                    let got = [r as i16, g as i16, b as i16];
+                    for channel in 0..3 {
+                        let error = wanted[channel] - got[channel];
+                        errors[x + 2][channel] += error * 7;
+                        next_errors[x][channel] += error * 3;
+                        next_errors[x + 1][channel] += error * 5;
+                        next_errors[x + 2][channel] += error;
+                    }
+                }
+            }
+            core::mem::swap(&mut errors, &mut next_errors);
+            for error in next_errors.iter_mut() {
+                *error = [0; 3];
+            }
+        }
+        Ok(Self { width, height, pixels })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The palette index of a pixel, 0 to 15.
+    pub fn color_index(&self, x : usize, y : usize) -> u8 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn pixel(&self, x : usize, y : usize) -> Color {
+        COLORS[self.color_index(x, y) as usize]
+    }
+
+    pub fn set_pixel(&mut self, x : usize, y : usize, color : Color) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = u8::from(color);
+        }
+    }
+
+    /// Draws the image at its own size, skipping pixels of the `transparent` color.
+    pub fn draw<C : Canvas + ?Sized>(&self, canvas : &mut C, x : isize, y : isize, transparent : Option<Color>) {
+        self.draw_scaled(canvas, Rect::new(x, y, self.width, self.height), transparent);
+    }
+
+    /// Stretches or shrinks the image to fill `target`, taking the nearest pixel.
+    pub fn draw_scaled<C : Canvas + ?Sized>(&self, canvas : &mut C, target : Rect, transparent : Option<Color>) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+        let transparent = transparent.map(u8::from);
+        let visible = canvas.clip().intersection(&target);
+        for row in visible.y..visible.bottom() {
+            let source_y = (row - target.y) as usize * self.height / target.height;
+            let source_x = |column : isize| (column - target.x) as usize * self.width / target.width;
+            // Runs of one color are drawn as a single span.
+            let mut start = visible.x;
+            while start < visible.right() {
+                let color = self.color_index(source_x(start), source_y);
+                let mut end = start + 1;
+                while end < visible.right() && self.color_index(source_x(end), source_y) == color {
+                    end += 1;
+                }
+                if Some(color) != transparent {
+                    canvas.span(start, end - 1, row, COLORS[color as usize]);
+                }
+                start = end;
+            }
+        }
+    }
+}
+
+impl From<&Image16<'_>> for Bitmap {
+    fn from(image : &Image16<'_>) -> Self {
+        let mut pixels = Vec::with_capacity(image.width() * image.height());
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                pixels.push(image.color_index(x, y));
+            }
+        }
+        Self { width : image.width(), height : image.height(), pixels }
+    }
+}
+
+/// The palette index closest to a color.
+pub fn nearest(r : u8, g : u8, b : u8) -> u8 {
+    let distance = |(pr, pg, pb) : (u8, u8, u8)| {
+        let (dr, dg, db) = (r as i32 - pr as i32, g as i32 - pg as i32, b as i32 - pb as i32);
+        dr * dr + dg * dg + db * db
+    };
+    (0..PALETTE_RGB.len()).min_by_key(|index| distance(PALETTE_RGB[*index])).unwrap() as u8
+}
+
+const BMP_FILE_HEADER_SIZE : usize = 14;
+const BMP_INFO_HEADER_SIZE : usize = 40;
+const BMP_UNCOMPRESSED : u32 = 0;
+
+/// Decodes an uncompressed Windows BMP of 1, 4, 8 or 24 bits per pixel.
+pub fn decode_bmp(data : &[u8], dither : Dither) -> Result<Bitmap, &'static str> {
+    let u16_at = |offset : usize| data.get(offset..offset + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])).ok_or("Truncated BMP");
+    let u32_at = |offset : usize| data.get(offset..offset + 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).ok_or("Truncated BMP");
+
+    if !data.starts_with(b"BM") {
+        return Err("Not A BMP File");
+    }
+    let pixel_offset = u32_at(10)? as usize;
+    let header_size = u32_at(14)? as usize;
+    if header_size < BMP_INFO_HEADER_SIZE {
+        return Err("Unsupported BMP Header");
+    }
+    let width = u32_at(18)? as i32;
+    let height = u32_at(22)? as i32;
+    let bits = u16_at(28)? as usize;
+    if u32_at(30)? != BMP_UNCOMPRESSED {
+        return Err("Compressed BMPs Are Not Supported");
+    }
+    if width <= 0 || height == 0 {
+        return Err("Invalid BMP Size");
+    }
+    // Rows are stored from the bottom up unless the height is negative.
+    let (width, bottom_up, height) = (width as usize, height > 0, (height as i64).abs() as usize);
+
+    let palette : Vec<[u8; 3]> = match bits {
+        1 | 4 | 8 => {
+            let used = u32_at(46)? as usize;
+            let count = if used == 0 { 1 << bits } else { used.min(1 << bits) };
+            let start = BMP_FILE_HEADER_SIZE + header_size;
+            let entries = data.get(start..start + count * 4).ok_or("Truncated BMP")?;
+            entries.chunks(4).map(|entry| [entry[2], entry[1], entry[0]]).collect()
+        },
+        24 => Vec::new(),
+        _ => return Err("Unsupported BMP Bit Depth"),
+    };
+
+    let stride = width.checked_mul(bits).and_then(|row_bits| row_bits.checked_add(31)).ok_or("Invalid BMP Size")? / 32 * 4;
+    let end = stride.checked_mul(height).and_then(|size| size.checked_add(pixel_offset)).ok_or("Invalid BMP Size")?;
+    if end > data.len() {
+        return Err("Truncated BMP");
+    }
+    let mut rgb = Vec::with_capacity(rgb_size(width, height)?);
+    for y in 0..height {
+        let stored = if bottom_up { height - 1 - y } else { y };
+        let start = pixel_offset + stored * stride;
+        let row = data.get(start..start + stride).ok_or("Truncated BMP")?;
+        for x in 0..width {
+            let color = match bits {
+                24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3]],
+                _ => {
+                    let bit = x * bits;
+                    let index = (row[bit / 8] >> (8 - bits - bit % 8)) & ((1 << bits) - 1) as u8;
+                    *palette.get(index as usize).ok_or("Invalid BMP Palette Index")?
+                },
+            };
+            rgb.extend_from_slice(&color);
+        }
+    }
+    Bitmap::quantize(width, height, &rgb, dither)
+}
+
+/// Decodes a PPM (`P3`, `P6`) or PGM (`P2`, `P5`) image, in text or binary form.
+pub fn decode_pnm(data : &[u8], dither : Dither) -> Result<Bitmap, &'static str> {
+    let (channels, binary) = match data.get(..2) {
+        Some(b"P2") => (1, false),
+        Some(b"P3") => (3, false),
+        Some(b"P5") => (1, true),
+        Some(b"P6") => (3, true),
+        _ => return Err("Not A PPM Or PGM File"),
+    };
+    let mut reader = PnmReader { data, position : 2 };
+    let width = reader.number()?;
+    let height = reader.number()?;
+    let max = reader.number()?;
+    if width == 0 || height == 0 || max == 0 || max > u16::MAX as usize {
+        return Err("Invalid PNM Header");
+    }
+    // One whitespace byte separates the header from binary samples.
+    reader.position += 1;
+
+    // Binary samples take one or two bytes, text ones at least a digit each.
+    let samples = width.checked_mul(height).and_then(|pixels| pixels.checked_mul(channels)).ok_or("Invalid PNM Header")?;
+    let sample_size = if binary && max >= 256 { 2 } else { 1 };
+    if samples.checked_mul(sample_size).map_or(true, |size| size > data.len().saturating_sub(reader.position)) {
+        return Err("Truncated PNM");
+    }
+    let mut rgb = Vec::with_capacity(rgb_size(width, height)?);
+    let mut pixel = [0u8; 3];
+    for sample in 0..samples {
+        let value = if !binary {
+            reader.number()?
+        } else if max < 256 {
+            reader.byte()? as usize
+        } else {
+            (reader.byte()? as usize) << 8 | reader.byte()? as usize
+        };
+        let value = (value.min(max) * 255 / max) as u8;
+        if channels == 1 {
+            rgb.extend_from_slice(&[value; 3]);
+        } else {
+            pixel[sample % 3] = value;
+            if sample % 3 == 2 {
+                rgb.extend_from_slice(&pixel);
+            }
+        }
+    }
+    Bitmap::quantize(width, height, &rgb, dither)
+}
+
+/// Bytes of RGB a `width` x `height` image decodes to.
+fn rgb_size(width : usize, height : usize) -> Result<usize, &'static str> {
+    width.checked_mul(height).and_then(|pixels| pixels.checked_mul(3)).ok_or("Image Too Large")
+}
+
+struct PnmReader<'a> {
+    data     : &'a [u8],
+    position : usize,
+}
+
+impl PnmReader<'_> {
+    fn byte(&mut self) -> Result<u8, &'static str> {
+        let byte = *self.data.get(self.position).ok_or("Truncated PNM")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    /// Skips whitespace and `#` comments, then reads a decimal number.
+    fn number(&mut self) -> Result<usize, &'static str> {
+        loop {
+            match self.data.get(self.position) {
+                Some(byte) if byte.is_ascii_whitespace() => self.position += 1,
+                Some(b'#') => while self.data.get(self.position).map_or(false, |byte| *byte != b'\n') {
+                    self.position += 1;
+                },
+                Some(_) => break,
+                None => return Err("Truncated PNM"),
+            }
+        }
+        let start = self.position;
+        let mut value : usize = 0;
+        while let Some(byte) = self.data.get(self.position).filter(|byte| byte.is_ascii_digit()) {
+            value = value.checked_mul(10).and_then(|value| value.checked_add((byte - b'0') as usize)).ok_or("Invalid PNM Number")?;
+            self.position += 1;
+        }
+        if self.position == start {
+            return Err("Invalid PNM Number");
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,533 @@
+use alloc::vec::Vec;
+
+use super::framebuffer::FrameBuffer;
+use super::{vga, Color, Rect, COLORS};
+use crate::user::math::isqrt;
+
+pub type Point = (isize, isize);
+
+/// Something the primitives here can draw on. Everything is clipped to
+/// [Canvas::clip], so shapes may hang off the edge.
+pub trait Canvas {
+    fn clip(&self) -> Rect;
+
+    /// Sets a pixel, doing nothing outside the clip.
+    fn plot(&mut self, x : isize, y : isize, color : Color);
+
+    /// Reads a pixel back, `None` outside the clip.
+    fn point(&self, x : isize, y : isize) -> Option<Color>;
+
+    /// Fills row `y` from `x0` to `x1`, both included.
+    fn span(&mut self, x0 : isize, x1 : isize, y : isize, color : Color) {
+        for x in x0.min(x1)..=x0.max(x1) {
+            self.plot(x, y, color);
+        }
+    }
+}
+
+impl Canvas for FrameBuffer {
+    fn clip(&self) -> Rect {
+        FrameBuffer::clip(self)
+    }
+
+    fn plot(&mut self, x : isize, y : isize, color : Color) {
+        self.set_pixel(x, y, color);
+    }
+
+    fn point(&self, x : isize, y : isize) -> Option<Color> {
+        if FrameBuffer::clip(self).contains(x, y) { Some(self.pixel(x as usize, y as usize)) } else { None }
+    }
+
+    fn span(&mut self, x0 : isize, x1 : isize, y : isize, color : Color) {
+        let (left, right) = (x0.min(x1), x0.max(x1));
+        self.fill_rect(Rect::new(left, y, (right - left + 1) as usize, 1), color);
+    }
+}
+
+/// Draws straight to the card.
+#[derive(Debug, Clone, Copy)]
+pub struct Screen {
+    clip : Rect,
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self { clip : Rect::screen() }
+    }
+
+    pub fn with_clip(clip : Rect) -> Self {
+        Self { clip : clip.intersection(&Rect::screen()) }
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Canvas for Screen {
+    fn clip(&self) -> Rect {
+        self.clip
+    }
+
+    fn plot(&mut self, x : isize, y : isize, color : Color) {
+        if self.clip.contains(x, y) {
+            vga::draw(x as usize, y as usize, color);
+        }
+    }
+
+    fn point(&self, x : isize, y : isize) -> Option<Color> {
+        if self.clip.contains(x, y) { Some(COLORS[vga::read_pixel(x as usize, y as usize) as usize]) } else { None }
+    }
+
+    fn span(&mut self, x0 : isize, x1 : isize, y : isize, color : Color) {
+        let (left, right) = (x0.min(x1).max(self.clip.x), x0.max(x1).min(self.clip.right() - 1));
+        if y >= self.clip.y && y < self.clip.bottom() && left <= right {
+            vga::draw_line((left, y), (right, y), color);
+        }
+    }
+}
+
+/// A one pixel wide line, both ends included.
+pub fn line<C : Canvas + ?Sized>(canvas : &mut C, from : Point, to : Point, color : Color) {
+    if from.1 == to.1 {
+        canvas.span(from.0, to.0, from.1, color);
+        return;
+    }
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (step_x, step_y) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let (mut x, mut y) = from;
+    let mut error = dx + dy;
+    loop {
+        canvas.plot(x, y, color);
+        if x == to.0 && y == to.1 {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// A line `width` pixels across with square ends, drawn as a filled quadrilateral.
+pub fn thick_line<C : Canvas + ?Sized>(canvas : &mut C, from : Point, to : Point, width : usize, color : Color) {
+    if width <= 1 {
+        line(canvas, from, to, color);
+        return;
+    }
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = isqrt((dx * dx + dy * dy) as u64) as isize;
+    if length == 0 {
+        fill_circle(canvas, from, width / 2, color);
+        return;
+    }
+    // Half the width along the normal, rounded to the nearest pixel.
+    let width = width as isize;
+    let offset_x = div_round(-dy * width, 2 * length);
+    let offset_y = div_round(dx * width, 2 * length);
+    fill_polygon(canvas, &[
+        (from.0 + offset_x, from.1 + offset_y),
+        (to.0 + offset_x, to.1 + offset_y),
+        (to.0 - offset_x, to.1 - offset_y),
+        (from.0 - offset_x, from.1 - offset_y),
+    ], color);
+}
+
+/// The outline of a closed polygon.
+pub fn draw_polygon<C : Canvas + ?Sized>(canvas : &mut C, points : &[Point], color : Color) {
+    for (index, point) in points.iter().enumerate() {
+        line(canvas, *point, points[(index + 1) % points.len()], color);
+    }
+}
+
+/// Fills a polygon with the even-odd rule, sampling pixel centres. Pixels on
+/// the right and bottom edges are left out so shapes sharing an edge don't
+/// overlap; draw the outline as well to cover them.
+pub fn fill_polygon<C : Canvas + ?Sized>(canvas : &mut C, points : &[Point], color : Color) {
+    if points.len() < 3 {
+        draw_polygon(canvas, points, color);
+        return;
+    }
+    let clip = canvas.clip();
+    let top = points.iter().map(|point| point.1).min().unwrap().max(clip.y);
+    let bottom = points.iter().map(|point| point.1).max().unwrap().min(clip.bottom());
+
+    let mut crossings = Vec::new();
+    for y in top..bottom {
+        crossings.clear();
+        for (index, &start) in points.iter().enumerate() {
+            let end = points[(index + 1) % points.len()];
+            let (upper, lower) = if start.1 < end.1 { (start, end) } else { (end, start) };
+            // Half open, so a vertex shared by two edges is only counted once.
+            if y >= upper.1 && y < lower.1 {
+                crossings.push(upper.0 + div_ceil((y - upper.1) * (lower.0 - upper.0), lower.1 - upper.1));
+            }
+        }
+        crossings.sort_unstable();
+        for pair in crossings.chunks(2) {
+            if pair.len() == 2 && pair[0] < pair[1] {
+                canvas.span(pair[0], pair[1] - 1, y, color);
+            }
+        }
+    }
+}
+
+pub fn draw_triangle<C : Canvas + ?Sized>(canvas : &mut C, p0 : Point, p1 : Point, p2 : Point, color : Color) {
+    draw_polygon(canvas, &[p0, p1, p2], color);
+}
+
+/// Fills a triangle following the same edge rule as [fill_polygon].
+pub fn fill_triangle<C : Canvas + ?Sized>(canvas : &mut C, p0 : Point, p1 : Point, p2 : Point, color : Color) {
+    fill_polygon(canvas, &[p0, p1, p2], color);
+}
+
+pub fn draw_rect<C : Canvas + ?Sized>(canvas : &mut C, rect : Rect, color : Color) {
+    if rect.is_empty() {
+        return;
+    }
+    let (right, bottom) = (rect.right() - 1, rect.bottom() - 1);
+    canvas.span(rect.x, right, rect.y, color);
+    canvas.span(rect.x, right, bottom, color);
+    for y in rect.y + 1..bottom {
+        canvas.plot(rect.x, y, color);
+        canvas.plot(right, y, color);
+    }
+}
+
+pub fn fill_rect<C : Canvas + ?Sized>(canvas : &mut C, rect : Rect, color : Color) {
+    let rect = canvas.clip().intersection(&rect);
+    for y in rect.y..rect.bottom() {
+        canvas.span(rect.x, rect.right() - 1, y, color);
+    }
+}
+
+/// Both diagonals of the `size` wide square around `center`.
+pub fn cross<C : Canvas + ?Sized>(canvas : &mut C, center : Point, size : isize, color : Color) {
+    let half_size = size / 2;
+    line(canvas, (center.0 - half_size, center.1 - half_size), (center.0 + half_size, center.1 + half_size), color);
+    line(canvas, (center.0 - half_size, center.1 + half_size), (center.0 + half_size, center.1 - half_size), color);
+}
+
+pub fn draw_circle<C : Canvas + ?Sized>(canvas : &mut C, center : Point, radius : usize, color : Color) {
+    draw_ellipse(canvas, center, radius, radius, color);
+}
+
+pub fn fill_circle<C : Canvas + ?Sized>(canvas : &mut C, center : Point, radius : usize, color : Color) {
+    fill_ellipse(canvas, center, radius, radius, color);
+}
+
+/// An axis aligned ellipse through `center` ± the radii.
+pub fn draw_ellipse<C : Canvas + ?Sized>(canvas : &mut C, center : Point, radius_x : usize, radius_y : usize, color : Color) {
+    let (cx, cy) = center;
+    quadrant(radius_x, radius_y, |x, y| {
+        canvas.plot(cx + x, cy + y, color);
+        canvas.plot(cx - x, cy + y, color);
+        canvas.plot(cx + x, cy - y, color);
+        canvas.plot(cx - x, cy - y, color);
+    });
+}
+
+pub fn fill_ellipse<C : Canvas + ?Sized>(canvas : &mut C, center : Point, radius_x : usize, radius_y : usize, color : Color) {
+    let (cx, cy) = center;
+    quadrant(radius_x, radius_y, |x, y| {
+        canvas.span(cx - x, cx + x, cy + y, color);
+        canvas.span(cx - x, cx + x, cy - y, color);
+    });
+}
+
+/// The outline of `rect` with its corners rounded off by `radius`, at most half its shorter side.
+pub fn draw_rounded_rect<C : Canvas + ?Sized>(canvas : &mut C, rect : Rect, radius : usize, color : Color) {
+    if rect.is_empty() {
+        return;
+    }
+    let (left, top, right, bottom, radius) = corners(rect, radius);
+    let (x0, y0, x1, y1) = (rect.x, rect.y, rect.right() - 1, rect.bottom() - 1);
+    canvas.span(left, right, y0, color);
+    canvas.span(left, right, y1, color);
+    for y in top..=bottom {
+        canvas.plot(x0, y, color);
+        canvas.plot(x1, y, color);
+    }
+    quadrant(radius, radius, |x, y| {
+        canvas.plot(left - x, top - y, color);
+        canvas.plot(right + x, top - y, color);
+        canvas.plot(left - x, bottom + y, color);
+        canvas.plot(right + x, bottom + y, color);
+    });
+}
+
+pub fn fill_rounded_rect<C : Canvas + ?Sized>(canvas : &mut C, rect : Rect, radius : usize, color : Color) {
+    if rect.is_empty() {
+        return;
+    }
+    let (left, top, right, bottom, radius) = corners(rect, radius);
+    for y in top..=bottom {
+        canvas.span(rect.x, rect.right() - 1, y, color);
+    }
+    quadrant(radius, radius, |x, y| {
+        canvas.span(left - x, right + x, top - y, color);
+        canvas.span(left - x, right + x, bottom + y, color);
+    });
+}
+
+/// Replaces the area of one color around (`x`, `y`), joined through its four
+/// neighbours, with `color`. Stays within the clip.
+pub fn flood_fill<C : Canvas + ?Sized>(canvas : &mut C, x : isize, y : isize, color : Color) {
+    let target = match canvas.point(x, y) {
+        Some(target) if target != color => target,
+        _ => return,
+    };
+    let mut seeds = Vec::new();
+    seeds.push((x, y));
+    while let Some((x, y)) = seeds.pop() {
+        if canvas.point(x, y) != Some(target) {
+            continue;
+        }
+        let (mut left, mut right) = (x, x);
+        while canvas.point(left - 1, y) == Some(target) {
+            left -= 1;
+        }
+        while canvas.point(right + 1, y) == Some(target) {
+            right += 1;
+        }
+        canvas.span(left, right, y, color);
+
+        // One seed for every run of the target color above and below.
+        for row in [y - 1, y + 1].iter().copied() {
+            let mut in_run = false;
+            for column in left..=right {
+                let matches = canvas.point(column, row) == Some(target);
+                if matches && !in_run {
+                    seeds.push((column, row));
+                }
+                in_run = matches;
+            }
+        }
+    }
+}
+
+/// The centres of the corner arcs of a rounded `rect` and the radius that fits.
+fn corners(rect : Rect, radius : usize) -> (isize, isize, isize, isize, usize) {
+    let radius = radius.min((rect.width - 1) / 2).min((rect.height - 1) / 2);
+    let offset = radius as isize;
+    (rect.x + offset, rect.y + offset, rect.right() - 1 - offset, rect.bottom() - 1 - offset, radius)
+}
+
+/// Walks the quarter ellipse from (0, `radius_y`) to (`radius_x`, 0) with the
+/// midpoint algorithm, calling `point` with offsets from the centre.
+fn quadrant<F : FnMut(isize, isize)>(radius_x : usize, radius_y : usize, mut point : F) {
+    let (rx, ry) = (radius_x as i64, radius_y as i64);
+    if rx == 0 || ry == 0 {
+        for x in 0..=rx {
+            point(x as isize, 0);
+        }
+        for y in 0..=ry {
+            point(0, y as isize);
+        }
+        return;
+    }
+    let (rx2, ry2) = (rx * rx, ry * ry);
+    let (mut x, mut y) = (0, ry);
+    let (mut px, mut py) = (0, 2 * rx2 * y);
+
+    // Decision values are kept four times over so the halves stay whole.
+    let mut decision = 4 * ry2 - 4 * rx2 * ry + rx2;
+    while px < py {
+        point(x as isize, y as isize);
+        x += 1;
+        px += 2 * ry2;
+        if decision < 0 {
+            decision += 4 * (ry2 + px);
+        } else {
+            y -= 1;
+            py -= 2 * rx2;
+            decision += 4 * (ry2 + px - py);
+        }
+    }
+
+    decision = ry2 * (2 * x + 1) * (2 * x + 1) + 4 * rx2 * (y - 1) * (y - 1) - 4 * rx2 * ry2;
+    while y >= 0 {
+        point(x as isize, y as isize);
+        y -= 1;
+        py -= 2 * rx2;
+        if decision > 0 {
+            decision += 4 * (rx2 - py);
+        } else {
+            x += 1;
+            px += 2 * ry2;
+            decision += 4 * (rx2 - py + px);
+        }
+    }
+}
+
+fn div_ceil(numerator : isize, denominator : isize) -> isize {
+    -((-numerator).div_euclid(denominator))
+}
+
+fn div_round(numerator : isize, denominator : isize) -> isize {
+    (2 * numerator + denominator).div_euclid(2 * denominator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::graphics::{HEIGHT, WIDTH};
+
+    fn lit(canvas : &FrameBuffer) -> Vec<Point> {
+        let mut points = Vec::new();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                if canvas.pixel(x, y) != Color::Black {
+                    points.push((x as isize, y as isize));
+                }
+            }
+        }
+        points
+    }
+
+    #[test_case]
+    fn fill_triangle_leaves_out_right_and_bottom_edges() {
+        let mut canvas = FrameBuffer::new();
+        fill_triangle(&mut canvas, (0, 0), (4, 0), (0, 4), Color::White);
+        // Row y runs from 0 up to, not including, the hypotenuse at 4 - y.
+        let mut expected = Vec::new();
+        for y in 0..4 {
+            for x in 0..4 - y {
+                expected.push((x, y));
+            }
+        }
+        assert_eq!(lit(&canvas), expected);
+    }
+
+    #[test_case]
+    fn fill_polygon_shared_edges_do_not_overlap() {
+        let mut canvas = FrameBuffer::new();
+        fill_polygon(&mut canvas, &[(0, 0), (4, 0), (4, 4), (0, 4)], Color::Red);
+        fill_polygon(&mut canvas, &[(4, 0), (8, 0), (8, 4), (4, 4)], Color::Blue);
+        fill_polygon(&mut canvas, &[(0, 4), (8, 4), (8, 8), (0, 8)], Color::Green);
+        let count = |color| lit(&canvas).iter().filter(|(x, y)| canvas.pixel(*x as usize, *y as usize) == color).count();
+        assert_eq!(count(Color::Red), 16);
+        assert_eq!(count(Color::Blue), 16);
+        assert_eq!(count(Color::Green), 32);
+        assert_eq!(lit(&canvas).len(), 64);
+    }
+
+    #[test_case]
+    fn fill_polygon_concave_uses_even_odd() {
+        let mut canvas = FrameBuffer::new();
+        // A U shape, the notch from x 2 to 4 down to y 3 stays empty.
+        fill_polygon(&mut canvas, &[(0, 0), (2, 0), (2, 3), (4, 3), (4, 0), (6, 0), (6, 5), (0, 5)], Color::White);
+        assert_eq!(canvas.pixel(1, 0), Color::White);
+        assert_eq!(canvas.pixel(2, 0), Color::Black);
+        assert_eq!(canvas.pixel(3, 2), Color::Black);
+        assert_eq!(canvas.pixel(4, 0), Color::White);
+        assert_eq!(canvas.pixel(3, 3), Color::White);
+        assert_eq!(lit(&canvas).len(), 6 * 5 - 2 * 3);
+    }
+
+    #[test_case]
+    fn drawing_is_clipped_at_every_edge() {
+        let clip = Rect::new(10, 10, 20, 20);
+        let centers = [(10, 20), (29, 20), (20, 10), (20, 29)];
+        for center in centers.iter().copied() {
+            let mut canvas = FrameBuffer::new();
+            canvas.set_clip(Some(clip));
+            fill_circle(&mut canvas, center, 5, Color::White);
+            line(&mut canvas, (center.0 - 40, center.1 - 40), (center.0 + 40, center.1 + 40), Color::White);
+            let points = lit(&canvas);
+            assert!(points.contains(&center));
+            assert!(points.iter().all(|(x, y)| clip.contains(*x, *y)));
+        }
+
+        let mut canvas = FrameBuffer::new();
+        canvas.set_clip(Some(clip));
+        fill_rect(&mut canvas, Rect::new(-100, -100, 1000, 1000), Color::White);
+        assert_eq!(lit(&canvas).len(), 20 * 20);
+    }
+
+    #[test_case]
+    fn drawing_is_clipped_at_the_screen_edges() {
+        let (right, bottom) = (WIDTH as isize - 1, HEIGHT as isize - 1);
+        let mut canvas = FrameBuffer::new();
+        for center in [(0, 0), (right, 0), (0, bottom), (right, bottom)].iter().copied() {
+            fill_circle(&mut canvas, center, 8, Color::White);
+            draw_ellipse(&mut canvas, center, 12, 6, Color::White);
+            fill_triangle(&mut canvas, center, (center.0 + 30, center.1 - 30), (center.0 - 30, center.1 + 30), Color::White);
+            assert_eq!(canvas.pixel(center.0 as usize, center.1 as usize), Color::White);
+        }
+    }
+
+    #[test_case]
+    fn ellipse_is_symmetric() {
+        let (cx, cy) = (50, 40);
+        for radii in [(7, 4), (4, 7), (10, 10), (1, 5), (0, 3)].iter().copied() {
+            let mut canvas = FrameBuffer::new();
+            draw_ellipse(&mut canvas, (cx, cy), radii.0, radii.1, Color::White);
+            let points = lit(&canvas);
+            for (x, y) in points.iter().copied() {
+                assert!(points.contains(&(2 * cx - x, y)));
+                assert!(points.contains(&(x, 2 * cy - y)));
+                assert!((x - cx).abs() <= radii.0 as isize && (y - cy).abs() <= radii.1 as isize);
+            }
+            let (rx, ry) = (radii.0 as isize, radii.1 as isize);
+            for end in [(cx + rx, cy), (cx - rx, cy), (cx, cy + ry), (cx, cy - ry)].iter() {
+                assert!(points.contains(end));
+            }
+        }
+    }
+
+    #[test_case]
+    fn flood_fill_stays_inside_outline() {
+        let mut canvas = FrameBuffer::new();
+        draw_rect(&mut canvas, Rect::new(10, 10, 10, 10), Color::White);
+        flood_fill(&mut canvas, 15, 15, Color::Red);
+        let points = lit(&canvas);
+        let red = points.iter().filter(|(x, y)| canvas.pixel(*x as usize, *y as usize) == Color::Red);
+        assert!(red.clone().all(|(x, y)| (11..19).contains(x) && (11..19).contains(y)));
+        assert_eq!(red.count(), 8 * 8);
+        assert_eq!(points.len(), 10 * 10);
+    }
+
+    #[test_case]
+    fn flood_fill_stays_inside_clip() {
+        let mut canvas = FrameBuffer::new();
+        canvas.set_clip(Some(Rect::new(0, 0, 30, 20)));
+        flood_fill(&mut canvas, 5, 5, Color::Red);
+        assert_eq!(lit(&canvas).len(), 30 * 20);
+
+        // Starting outside the clip does nothing.
+        flood_fill(&mut canvas, 40, 40, Color::Blue);
+        canvas.set_clip(None);
+        flood_fill(&mut canvas, 5, 5, Color::Red);
+        assert_eq!(lit(&canvas).len(), 30 * 20);
+    }
+
+    #[test_case]
+    fn fill_rect_covers_exactly_its_size() {
+        // What `draw_filled_rect` draws, it used to go a pixel past the right edge.
+        let mut canvas = FrameBuffer::new();
+        fill_rect(&mut canvas, Rect::new(5, 5, 3, 2), Color::White);
+        assert_eq!(lit(&canvas), [(5, 5), (6, 5), (7, 5), (5, 6), (6, 6), (7, 6)]);
+        fill_rect(&mut canvas, Rect::new(20, 20, 0, 4), Color::White);
+        assert_eq!(lit(&canvas).len(), 6);
+    }
+
+    #[test_case]
+    fn cross_draws_both_diagonals() {
+        let mut canvas = FrameBuffer::new();
+        cross(&mut canvas, (20, 20), 6, Color::White);
+        let points = lit(&canvas);
+        for offset in -3..=3 {
+            assert!(points.contains(&(20 + offset, 20 + offset)));
+            assert!(points.contains(&(20 + offset, 20 - offset)));
+        }
+        assert_eq!(points.len(), 13);
+    }
+}
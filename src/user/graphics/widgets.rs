@@ -1,5 +1,219 @@
 pub mod label;
 pub mod progress_bar;
+pub mod button;
+pub mod checkbox;
+pub mod text_input;
+pub mod list;
+pub mod container;
+pub mod scroll_view;
+pub mod ui;
+
+use alloc::boxed::Box;
+use core::any::Any;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::graphics::{self, Color};
+pub use crate::graphics::Rect;
+use crate::input::{KeyEvent, MouseEvent};
+
+pub use label::Label;
+pub use progress_bar::ProgressBar;
+pub use button::Button;
+pub use checkbox::Checkbox;
+pub use text_input::TextInput;
+pub use list::List;
+pub use container::{Container, Direction};
+pub use scroll_view::ScrollView;
+pub use ui::Ui;
+
+pub const BACKGROUND : Color = Color::LightGrey;
+pub const FOREGROUND : Color = Color::Black;
+pub const ACCENT     : Color = Color::Blue;
+pub const HIGHLIGHT  : Color = Color::White;
+pub const FOCUS      : Color = Color::Yellow;
+
+/// Draws itself at an absolute position, for one-off drawing outside a [Ui].
 pub trait Renderable {
     fn draw(&self, x : usize, y : usize);
-}
\ No newline at end of file
+}
+
+pub type WidgetId = usize;
+
+static NEXT_ID : AtomicUsize = AtomicUsize::new(1);
+
+/// Draws on the screen, clipped to a rectangle.
+#[derive(Debug, Clone, Copy)]
+pub struct Painter {
+    clip : Rect,
+}
+
+impl Painter {
+    pub fn screen() -> Self {
+        Self { clip : Rect::screen() }
+    }
+
+    pub fn clip(&self) -> Rect {
+        self.clip
+    }
+
+    /// A painter that only draws where both it and `rect` reach.
+    pub fn clipped(&self, rect : Rect) -> Painter {
+        Painter { clip : self.clip.intersection(&rect) }
+    }
+
+    pub fn fill(&self, rect : Rect, color : Color) {
+        let rect = self.clip.intersection(&rect);
+        if rect.is_empty() {
+            return;
+        }
+        for y in rect.y..rect.bottom() {
+            graphics::draw_line((rect.x, y), (rect.right() - 1, y), color);
+        }
+    }
+
+    /// Draws the outline of `rect`, one pixel wide.
+    pub fn frame(&self, rect : Rect, color : Color) {
+        self.fill(Rect::new(rect.x, rect.y, rect.width, 1), color);
+        self.fill(Rect::new(rect.x, rect.bottom() - 1, rect.width, 1), color);
+        self.fill(Rect::new(rect.x, rect.y, 1, rect.height), color);
+        self.fill(Rect::new(rect.right() - 1, rect.y, 1, rect.height), color);
+    }
+
+    /// Draws one line of text, leaving out characters that don't fit.
+    pub fn text(&self, x : isize, y : isize, text : &str, color : Color) {
+        let (width, height) = (graphics::font_width(), graphics::font_height());
+        for (index, c) in text.chars().enumerate() {
+            let cell = Rect::new(x + (index * width) as isize, y, width, height);
+            if self.clip.intersection(&cell) == cell {
+                graphics::draw_chr(cell.x as usize, cell.y as usize, c, color);
+            }
+        }
+    }
+}
+
+/// Input delivered to widgets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Key(KeyEvent),
+    Mouse(MouseEvent),
+}
+
+/// Something a widget reports to the program running the [Ui].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Clicked(WidgetId),
+    Changed(WidgetId),
+    Submitted(WidgetId),
+    Selected(WidgetId, usize),
+}
+
+/// What a widget did with an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    Ignored,
+    Handled,
+    Action(Action),
+}
+
+/// State every widget has: an id, where it was laid out and whether it needs redrawing.
+#[derive(Debug, Clone, Copy)]
+pub struct Base {
+    pub id     : WidgetId,
+    pub bounds : Rect,
+    pub dirty  : bool,
+}
+
+impl Base {
+    pub fn new() -> Self {
+        Self { id : NEXT_ID.fetch_add(1, Ordering::Relaxed), bounds : Rect::default(), dirty : true }
+    }
+}
+
+impl Default for Base {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lets a [Ui] hand back widgets as their concrete types.
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T : Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A node in a [Ui]'s widget tree. Widgets draw themselves inside their
+/// bounds, the [Ui] draws their children after them.
+pub trait Widget : AsAny {
+    fn base(&self) -> &Base;
+    fn base_mut(&mut self) -> &mut Base;
+
+    /// The size the widget would like, which its container may not give it.
+    fn preferred_size(&self) -> (usize, usize);
+
+    fn draw(&self, painter : &Painter);
+
+    fn handle(&mut self, _event : &Event) -> Response {
+        Response::Ignored
+    }
+
+    fn focusable(&self) -> bool {
+        false
+    }
+
+    fn set_focused(&mut self, _focused : bool) {}
+
+    /// Places the widget, containers lay their children out here too.
+    fn set_bounds(&mut self, bounds : Rect) {
+        let base = self.base_mut();
+        base.bounds = bounds;
+        base.dirty = true;
+    }
+
+    /// The color drawn behind the widget and its children when they change,
+    /// `None` to use the parent's.
+    fn background(&self) -> Option<Color> {
+        None
+    }
+
+    fn children(&self) -> &[Box<dyn Widget>] {
+        &[]
+    }
+
+    fn children_mut(&mut self) -> &mut [Box<dyn Widget>] {
+        &mut []
+    }
+
+    fn id(&self) -> WidgetId {
+        self.base().id
+    }
+
+    fn bounds(&self) -> Rect {
+        self.base().bounds
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.base().dirty
+    }
+
+    fn mark_dirty(&mut self) {
+        self.base_mut().dirty = true;
+    }
+}
+
+fn char_size() -> (usize, usize) {
+    (graphics::font_width(), graphics::font_height())
+}
+
+fn text_width(text : &str) -> usize {
+    text.chars().count() * graphics::font_width()
+}
@@ -0,0 +1,89 @@
+use alloc::string::String;
+
+use super::*;
+use crate::input::{KeyCode, MouseButton};
+
+/// A push button, reporting [Action::Clicked] when released over itself or
+/// when Enter or Space is pressed while it has focus.
+pub struct Button {
+    base : Base,
+    text : String,
+    focused : bool,
+    pressed : bool,
+}
+
+impl Button {
+    pub fn new<S : Into<String>>(text : S) -> Self {
+        Self { base : Base::new(), text : text.into(), focused : false, pressed : false }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text<S : Into<String>>(&mut self, text : S) {
+        self.text = text.into();
+        self.base.dirty = true;
+    }
+
+    fn set_pressed(&mut self, pressed : bool) {
+        if self.pressed != pressed {
+            self.pressed = pressed;
+            self.base.dirty = true;
+        }
+    }
+}
+
+impl Widget for Button {
+    fn base(&self) -> &Base {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut Base {
+        &mut self.base
+    }
+
+    fn preferred_size(&self) -> (usize, usize) {
+        (text_width(&self.text) + 16, char_size().1 + 8)
+    }
+
+    fn draw(&self, painter : &Painter) {
+        let bounds = self.base.bounds;
+        let (face, text) = if self.pressed { (ACCENT, HIGHLIGHT) } else { (HIGHLIGHT, FOREGROUND) };
+        painter.fill(bounds, face);
+        painter.frame(bounds, if self.focused { FOCUS } else { FOREGROUND });
+        let x = bounds.x + (bounds.width.saturating_sub(text_width(&self.text)) / 2) as isize;
+        let y = bounds.y + (bounds.height.saturating_sub(char_size().1) / 2) as isize;
+        painter.clipped(bounds.inset(1)).text(x, y, &self.text, text);
+    }
+
+    fn handle(&mut self, event : &Event) -> Response {
+        match event {
+            Event::Mouse(MouseEvent::Press { button : MouseButton::Left, .. }) => {
+                self.set_pressed(true);
+                Response::Handled
+            },
+            Event::Mouse(MouseEvent::Release { button : MouseButton::Left, x, y }) if self.pressed => {
+                self.set_pressed(false);
+                if self.base.bounds.contains(*x as isize, *y as isize) {
+                    Response::Action(Action::Clicked(self.base.id))
+                } else {
+                    Response::Handled
+                }
+            },
+            Event::Key(key) if key.pressed && (key.code == KeyCode::Enter || key.code == KeyCode::Spacebar) => {
+                Response::Action(Action::Clicked(self.base.id))
+            },
+            _ => Response::Ignored,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    fn set_focused(&mut self, focused : bool) {
+        self.focused = focused;
+        self.base.dirty = true;
+    }
+}
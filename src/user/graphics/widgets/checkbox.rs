@@ -0,0 +1,77 @@
+use alloc::string::String;
+
+use super::*;
+use crate::input::{KeyCode, MouseButton};
+
+/// A box that is ticked or not, reporting [Action::Changed] when toggled.
+pub struct Checkbox {
+    base : Base,
+    text : String,
+    checked : bool,
+    focused : bool,
+}
+
+impl Checkbox {
+    pub fn new<S : Into<String>>(text : S, checked : bool) -> Self {
+        Self { base : Base::new(), text : text.into(), checked, focused : false }
+    }
+
+    pub fn is_checked(&self) -> bool {
+        self.checked
+    }
+
+    pub fn set_checked(&mut self, checked : bool) {
+        self.checked = checked;
+        self.base.dirty = true;
+    }
+
+    fn toggle(&mut self) -> Response {
+        self.set_checked(!self.checked);
+        Response::Action(Action::Changed(self.base.id))
+    }
+}
+
+impl Widget for Checkbox {
+    fn base(&self) -> &Base {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut Base {
+        &mut self.base
+    }
+
+    fn preferred_size(&self) -> (usize, usize) {
+        let (width, height) = char_size();
+        (height + 4 + width + text_width(&self.text), height + 4)
+    }
+
+    fn draw(&self, painter : &Painter) {
+        let bounds = self.base.bounds;
+        let painter = painter.clipped(bounds);
+        let size = char_size().1;
+        let tick_box = Rect::new(bounds.x + 2, bounds.y + 2, size, size);
+        painter.fill(tick_box, HIGHLIGHT);
+        painter.frame(tick_box, if self.focused { FOCUS } else { FOREGROUND });
+        if self.checked {
+            painter.fill(tick_box.inset(2), ACCENT);
+        }
+        painter.text(tick_box.right() + char_size().0 as isize, bounds.y + 2, &self.text, FOREGROUND);
+    }
+
+    fn handle(&mut self, event : &Event) -> Response {
+        match event {
+            Event::Mouse(MouseEvent::Press { button : MouseButton::Left, .. }) => self.toggle(),
+            Event::Key(key) if key.pressed && key.code == KeyCode::Spacebar => self.toggle(),
+            _ => Response::Ignored,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    fn set_focused(&mut self, focused : bool) {
+        self.focused = focused;
+        self.base.dirty = true;
+    }
+}
@@ -0,0 +1,125 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Vertical,
+    Horizontal,
+}
+
+/// Lays its children out in a row or column. Children get their preferred
+/// length along the direction and are stretched across it.
+pub struct Container {
+    base : Base,
+    children : Vec<Box<dyn Widget>>,
+    direction : Direction,
+    spacing : usize,
+    padding : usize,
+    background : Option<Color>,
+}
+
+impl Container {
+    pub fn new(direction : Direction) -> Self {
+        Self { base : Base::new(), children : Vec::new(), direction, spacing : 4, padding : 4, background : None }
+    }
+
+    pub fn vertical() -> Self {
+        Self::new(Direction::Vertical)
+    }
+
+    pub fn horizontal() -> Self {
+        Self::new(Direction::Horizontal)
+    }
+
+    pub fn with<W : Widget + 'static>(mut self, child : W) -> Self {
+        self.push(child);
+        self
+    }
+
+    pub fn with_spacing(mut self, spacing : usize, padding : usize) -> Self {
+        self.spacing = spacing;
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_background(mut self, color : Color) -> Self {
+        self.background = Some(color);
+        self
+    }
+
+    /// Adds a child, returning its id. Call `set_bounds` again to lay it out.
+    pub fn push<W : Widget + 'static>(&mut self, child : W) -> WidgetId {
+        let id = child.id();
+        self.children.push(Box::new(child));
+        self.base.dirty = true;
+        id
+    }
+
+    pub fn remove(&mut self, id : WidgetId) -> Option<Box<dyn Widget>> {
+        let index = self.children.iter().position(|child| child.id() == id)?;
+        self.base.dirty = true;
+        Some(self.children.remove(index))
+    }
+
+    fn layout(&mut self) {
+        let inner = self.base.bounds.inset(self.padding);
+        let mut offset = 0;
+        for child in self.children.iter_mut() {
+            let (width, height) = child.preferred_size();
+            let bounds = match self.direction {
+                Direction::Vertical => Rect::new(inner.x, inner.y + offset as isize, inner.width, height),
+                Direction::Horizontal => Rect::new(inner.x + offset as isize, inner.y, width, inner.height),
+            };
+            child.set_bounds(bounds);
+            offset += match self.direction {
+                Direction::Vertical => height,
+                Direction::Horizontal => width,
+            } + self.spacing;
+        }
+    }
+}
+
+impl Widget for Container {
+    fn base(&self) -> &Base {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut Base {
+        &mut self.base
+    }
+
+    fn preferred_size(&self) -> (usize, usize) {
+        let sizes = self.children.iter().map(|child| child.preferred_size());
+        let gaps = self.spacing * self.children.len().saturating_sub(1);
+        let (width, height) = match self.direction {
+            Direction::Vertical => sizes.fold((0, gaps), |(w, h), (cw, ch)| (w.max(cw), h + ch)),
+            Direction::Horizontal => sizes.fold((gaps, 0), |(w, h), (cw, ch)| (w + cw, h.max(ch))),
+        };
+        (width + self.padding * 2, height + self.padding * 2)
+    }
+
+    fn draw(&self, painter : &Painter) {
+        if let Some(color) = self.background {
+            painter.fill(self.base.bounds, color);
+        }
+    }
+
+    fn set_bounds(&mut self, bounds : Rect) {
+        self.base.bounds = bounds;
+        self.base.dirty = true;
+        self.layout();
+    }
+
+    fn background(&self) -> Option<Color> {
+        self.background
+    }
+
+    fn children(&self) -> &[Box<dyn Widget>] {
+        &self.children
+    }
+
+    fn children_mut(&mut self) -> &mut [Box<dyn Widget>] {
+        &mut self.children
+    }
+}
@@ -1,34 +1,64 @@
+use alloc::string::String;
+
 use super::*;
-use crate::graphics::{*, self};
+use crate::graphics::*;
+
 pub struct Label {
+    base : Base,
+
     foreground : Color,
     background : Color,
 
-    text_width  : usize,
-    text_height : usize,
-
-    text : &'static str
+    text : String,
 }
 
 impl Label {
-    pub fn new(text : &'static str, foreground : Color, background : Color) -> Label {
-        Label {
-            text, foreground, background,
+    pub fn new<S : Into<String>>(text : S, foreground : Color, background : Color) -> Label {
+        Label { base : Base::new(), text : text.into(), foreground, background }
+    }
 
-            text_height : graphics::font_height(),
-            text_width : text.len() * graphics::font_width(),
-        }
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text<S : Into<String>>(&mut self, text : S) {
+        self.text = text.into();
+        self.base.dirty = true;
     }
 
     pub fn dimensions(&self) -> (usize, usize) {
-        (self.text_width + 8, self.text_height + 4)
+        (text_width(&self.text) + 8, font_height() + 4)
     }
 }
 
 impl Renderable for Label {
     fn draw(&self, x : usize, y : usize) {
-        draw_filled_rect((x as isize, y as isize), ((self.text_width + 8) as isize, (self.text_height + 4) as isize), self.background);
-        draw_str(x + 4, y + 2, self.text, self.foreground)
+        let (width, height) = self.dimensions();
+        draw_filled_rect((x as isize, y as isize), (width as isize, height as isize), self.background);
+        draw_str(x + 4, y + 2, &self.text, self.foreground)
     }
 }
 
+impl Widget for Label {
+    fn base(&self) -> &Base {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut Base {
+        &mut self.base
+    }
+
+    fn preferred_size(&self) -> (usize, usize) {
+        self.dimensions()
+    }
+
+    fn background(&self) -> Option<Color> {
+        Some(self.background)
+    }
+
+    fn draw(&self, painter : &Painter) {
+        let bounds = self.base.bounds;
+        painter.fill(bounds, self.background);
+        painter.clipped(bounds).text(bounds.x + 4, bounds.y + 2, &self.text, self.foreground);
+    }
+}
@@ -0,0 +1,136 @@
+use alloc::{string::String, vec::Vec};
+
+use super::*;
+use crate::input::{KeyCode, MouseButton};
+
+/// A list of lines to pick from, reporting [Action::Selected] when the
+/// selection moves. The wheel scrolls it.
+pub struct List {
+    base : Base,
+    items : Vec<String>,
+    selected : Option<usize>,
+    top : usize, // First item shown.
+    rows : usize,
+    focused : bool,
+}
+
+impl List {
+    /// A list tall enough for `rows` items.
+    pub fn new(items : Vec<String>, rows : usize) -> Self {
+        Self { base : Base::new(), items, selected : None, top : 0, rows, focused : false }
+    }
+
+    pub fn items(&self) -> &[String] {
+        &self.items
+    }
+
+    pub fn set_items(&mut self, items : Vec<String>) {
+        self.items = items;
+        self.selected = self.selected.filter(|index| *index < self.items.len());
+        self.top = 0;
+        self.base.dirty = true;
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    fn row_height() -> usize {
+        char_size().1 + 2
+    }
+
+    fn visible_rows(&self) -> usize {
+        (self.base.bounds.height.saturating_sub(2) / Self::row_height()).max(1)
+    }
+
+    fn scroll(&mut self, top : isize) {
+        let max_top = self.items.len().saturating_sub(self.visible_rows());
+        let top = (top.max(0) as usize).min(max_top);
+        if top != self.top {
+            self.top = top;
+            self.base.dirty = true;
+        }
+    }
+
+    pub fn select(&mut self, index : usize) -> Response {
+        if index >= self.items.len() {
+            return Response::Handled;
+        }
+        if index < self.top {
+            self.scroll(index as isize);
+        } else if index >= self.top + self.visible_rows() {
+            self.scroll((index + 1 - self.visible_rows()) as isize);
+        }
+        self.selected = Some(index);
+        self.base.dirty = true;
+        Response::Action(Action::Selected(self.base.id, index))
+    }
+}
+
+impl Widget for List {
+    fn base(&self) -> &Base {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut Base {
+        &mut self.base
+    }
+
+    fn preferred_size(&self) -> (usize, usize) {
+        let longest = self.items.iter().map(|item| text_width(item)).max().unwrap_or(0);
+        (longest + 8, self.rows * Self::row_height() + 2)
+    }
+
+    fn draw(&self, painter : &Painter) {
+        let bounds = self.base.bounds;
+        painter.fill(bounds, HIGHLIGHT);
+        painter.frame(bounds, if self.focused { FOCUS } else { FOREGROUND });
+
+        let inner = painter.clipped(bounds.inset(1));
+        for (row, index) in (self.top..self.items.len()).take(self.visible_rows()).enumerate() {
+            let y = bounds.y + 1 + (row * Self::row_height()) as isize;
+            let color = if self.selected == Some(index) {
+                inner.fill(Rect::new(bounds.x + 1, y, bounds.width, Self::row_height()), ACCENT);
+                HIGHLIGHT
+            } else {
+                FOREGROUND
+            };
+            inner.text(bounds.x + 4, y + 1, &self.items[index], color);
+        }
+    }
+
+    fn handle(&mut self, event : &Event) -> Response {
+        match event {
+            Event::Mouse(MouseEvent::Press { button : MouseButton::Left, y, .. }) => {
+                let row = (*y as isize - self.base.bounds.y - 1).max(0) as usize / Self::row_height();
+                self.select(self.top + row)
+            },
+            Event::Mouse(MouseEvent::Scroll { delta, .. }) => {
+                self.scroll(self.top as isize + delta);
+                Response::Handled
+            },
+            Event::Key(key) if key.pressed => match key.code {
+                KeyCode::ArrowUp => self.select(self.selected.map_or(0, |index| index.saturating_sub(1))),
+                KeyCode::ArrowDown => self.select(self.selected.map_or(0, |index| index + 1)),
+                KeyCode::Home => self.select(0),
+                KeyCode::End => self.select(self.items.len().saturating_sub(1)),
+                KeyCode::PageUp => self.select(self.selected.unwrap_or(0).saturating_sub(self.visible_rows())),
+                KeyCode::PageDown => {
+                    let last = self.items.len().saturating_sub(1);
+                    self.select((self.selected.unwrap_or(0) + self.visible_rows()).min(last))
+                },
+                _ => Response::Ignored,
+            },
+            _ => Response::Ignored,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    fn set_focused(&mut self, focused : bool) {
+        self.focused = focused;
+        self.base.dirty = true;
+    }
+}
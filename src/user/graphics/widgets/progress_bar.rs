@@ -1,8 +1,12 @@
+use alloc::string::String;
+
 use super::*;
 use crate::{graphics::*};
 
 pub struct ProgressBar {
-    text : &'static str,
+    base : Base,
+
+    text : String,
     min : usize,
     max : usize,
     fill : f32,
@@ -14,8 +18,8 @@ pub struct ProgressBar {
 
 impl Renderable for ProgressBar {
     fn draw(&self, x : usize, y : usize) {
-        let lbl = label::Label::new(self.text, Color::White, Color::Blue);
-        lbl.draw(x, y);
+        let lbl = label::Label::new(self.text.as_str(), Color::White, Color::Blue);
+        Renderable::draw(&lbl, x, y);
         let (pb_ox, pb_oy) = lbl.dimensions();
         draw_filled_rect(
             ((pb_ox + x) as isize, (y) as isize),
@@ -34,24 +38,64 @@ impl Renderable for ProgressBar {
 }
 
 impl ProgressBar {
-    pub fn new(text : &'static str, max : usize, fill : Color, base : Color, scale : usize) -> Self {
+    pub fn new<S : Into<String>>(text : S, max : usize, fill : Color, base : Color, scale : usize) -> Self {
         Self {
+            base : Base::new(),
             filled_color : fill,
             empty_color : base,
             max,
             scale,
-            text,
+            text : text.into(),
             min : 0,
             fill : 0.0
 
         }
     }
 
+    /// Sets the values shown as empty and full.
+    pub fn set_range(&mut self, min : usize, max : usize) {
+        self.min = min;
+        self.max = max.max(min + 1);
+    }
+
     pub fn set_value(&mut self, value : usize) {
-        self.fill = (value as f32 / self.max as f32) as f32
+        let value = value.clamp(self.min, self.max);
+        self.fill = (value - self.min) as f32 / (self.max - self.min) as f32;
+        self.base.dirty = true;
     }
 
     pub fn min(&self) -> usize {
         self.min
     }
-}
\ No newline at end of file
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+
+impl Widget for ProgressBar {
+    fn base(&self) -> &Base {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut Base {
+        &mut self.base
+    }
+
+    fn preferred_size(&self) -> (usize, usize) {
+        (text_width(&self.text) + 8 + self.scale, font_height() + 4)
+    }
+
+    fn draw(&self, painter : &Painter) {
+        let bounds = self.base.bounds;
+        let label_width = (text_width(&self.text) + 8).min(bounds.width);
+        let label = Rect::new(bounds.x, bounds.y, label_width, bounds.height);
+        painter.fill(label, Color::Blue);
+        painter.clipped(label).text(label.x + 4, label.y + 2, &self.text, Color::White);
+
+        let bar = Rect::new(label.right(), bounds.y, bounds.width - label_width, bounds.height);
+        painter.fill(bar, self.empty_color);
+        let filled = (bar.width as f32 * self.fill) as usize;
+        painter.fill(Rect::new(bar.x, bar.y, filled, bar.height), self.filled_color);
+    }
+}
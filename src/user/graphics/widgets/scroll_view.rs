@@ -0,0 +1,122 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use super::*;
+
+const SCROLLBAR_WIDTH : usize = 4;
+/// Pixels moved per notch of the wheel.
+const SCROLL_STEP : usize = 16;
+
+/// Shows part of a child taller than itself, scrolled with the wheel.
+pub struct ScrollView {
+    base : Base,
+    child : Vec<Box<dyn Widget>>, // Always one, kept in a Vec to hand out as `children`.
+    offset : usize,
+    height : Option<usize>,
+    background : Color,
+}
+
+impl ScrollView {
+    pub fn new<W : Widget + 'static>(child : W) -> Self {
+        Self { base : Base::new(), child : vec![Box::new(child) as Box<dyn Widget>], offset : 0, height : None, background : BACKGROUND }
+    }
+
+    /// Asks for `height` pixels rather than the child's whole height.
+    pub fn with_height(mut self, height : usize) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    pub fn child(&self) -> &dyn Widget {
+        self.child[0].as_ref()
+    }
+
+    pub fn child_mut(&mut self) -> &mut dyn Widget {
+        self.child[0].as_mut()
+    }
+
+    fn content_height(&self) -> usize {
+        self.child[0].preferred_size().1
+    }
+
+    fn max_offset(&self) -> usize {
+        self.content_height().saturating_sub(self.base.bounds.height)
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Scrolls so the child's row `offset` is at the top.
+    pub fn scroll_to(&mut self, offset : usize) {
+        let offset = offset.min(self.max_offset());
+        if offset != self.offset {
+            self.offset = offset;
+            self.layout();
+        }
+    }
+
+    fn layout(&mut self) {
+        let bounds = self.base.bounds;
+        let height = self.content_height().max(bounds.height);
+        let content = Rect::new(bounds.x, bounds.y - self.offset as isize, bounds.width.saturating_sub(SCROLLBAR_WIDTH), height);
+        self.child[0].set_bounds(content);
+        self.base.dirty = true;
+    }
+}
+
+impl Widget for ScrollView {
+    fn base(&self) -> &Base {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut Base {
+        &mut self.base
+    }
+
+    fn preferred_size(&self) -> (usize, usize) {
+        let (width, height) = self.child[0].preferred_size();
+        (width + SCROLLBAR_WIDTH, self.height.unwrap_or(height))
+    }
+
+    fn draw(&self, painter : &Painter) {
+        let bounds = self.base.bounds;
+        painter.fill(bounds, self.background);
+        let track = Rect::new(bounds.right() - SCROLLBAR_WIDTH as isize, bounds.y, SCROLLBAR_WIDTH, bounds.height);
+        painter.fill(track, HIGHLIGHT);
+        let content = self.content_height().max(1);
+        if content > bounds.height {
+            let thumb_height = (bounds.height * bounds.height / content).max(SCROLLBAR_WIDTH);
+            let thumb_y = bounds.y + (self.offset * bounds.height / content) as isize;
+            painter.fill(Rect::new(track.x, thumb_y, SCROLLBAR_WIDTH, thumb_height), ACCENT);
+        }
+    }
+
+    fn handle(&mut self, event : &Event) -> Response {
+        match event {
+            Event::Mouse(MouseEvent::Scroll { delta, .. }) => {
+                let offset = self.offset as isize + delta * SCROLL_STEP as isize;
+                self.scroll_to(offset.max(0) as usize);
+                Response::Handled
+            },
+            _ => Response::Ignored,
+        }
+    }
+
+    fn set_bounds(&mut self, bounds : Rect) {
+        self.base.bounds = bounds;
+        self.offset = self.offset.min(self.max_offset());
+        self.layout();
+    }
+
+    fn background(&self) -> Option<Color> {
+        Some(self.background)
+    }
+
+    fn children(&self) -> &[Box<dyn Widget>] {
+        &self.child
+    }
+
+    fn children_mut(&mut self) -> &mut [Box<dyn Widget>] {
+        &mut self.child
+    }
+}
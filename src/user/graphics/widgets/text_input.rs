@@ -0,0 +1,138 @@
+use alloc::{string::String, vec::Vec};
+
+use super::*;
+use crate::input::{KeyCode, MouseButton};
+
+/// A single line of editable text, reporting [Action::Changed] on every edit
+/// and [Action::Submitted] on Enter.
+pub struct TextInput {
+    base : Base,
+    text : Vec<char>,
+    cursor : usize,
+    scroll : usize, // First character shown.
+    columns : usize,
+    focused : bool,
+}
+
+impl TextInput {
+    /// A text input wide enough for `columns` characters.
+    pub fn new(columns : usize) -> Self {
+        Self { base : Base::new(), text : Vec::new(), cursor : 0, scroll : 0, columns, focused : false }
+    }
+
+    pub fn text(&self) -> String {
+        self.text.iter().collect()
+    }
+
+    pub fn set_text(&mut self, text : &str) {
+        self.text = text.chars().collect();
+        self.cursor = self.text.len();
+        self.scroll_to_cursor();
+        self.base.dirty = true;
+    }
+
+    /// Characters that fit in the bounds it was given.
+    fn visible_columns(&self) -> usize {
+        (self.base.bounds.width.saturating_sub(8) / char_size().0).max(1)
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        let columns = self.visible_columns();
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + columns {
+            self.scroll = self.cursor + 1 - columns;
+        }
+    }
+
+    fn edited(&mut self) -> Response {
+        self.scroll_to_cursor();
+        self.base.dirty = true;
+        Response::Action(Action::Changed(self.base.id))
+    }
+
+    fn moved(&mut self, cursor : usize) -> Response {
+        self.cursor = cursor.min(self.text.len());
+        self.scroll_to_cursor();
+        self.base.dirty = true;
+        Response::Handled
+    }
+}
+
+impl Widget for TextInput {
+    fn base(&self) -> &Base {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut Base {
+        &mut self.base
+    }
+
+    fn preferred_size(&self) -> (usize, usize) {
+        let (width, height) = char_size();
+        (self.columns * width + 8, height + 8)
+    }
+
+    fn draw(&self, painter : &Painter) {
+        let bounds = self.base.bounds;
+        let (width, height) = char_size();
+        painter.fill(bounds, HIGHLIGHT);
+        painter.frame(bounds, if self.focused { FOCUS } else { FOREGROUND });
+
+        let inner = painter.clipped(bounds.inset(2));
+        let visible : String = self.text.iter().skip(self.scroll).take(self.visible_columns()).collect();
+        let (x, y) = (bounds.x + 4, bounds.y + 4);
+        inner.text(x, y, &visible, FOREGROUND);
+        if self.focused {
+            let cursor_x = x + ((self.cursor - self.scroll) * width) as isize;
+            inner.fill(Rect::new(cursor_x, y + height as isize, width, 1), ACCENT);
+        }
+    }
+
+    fn handle(&mut self, event : &Event) -> Response {
+        match event {
+            Event::Mouse(MouseEvent::Press { button : MouseButton::Left, x, .. }) => {
+                let column = (*x as isize - self.base.bounds.x - 4).max(0) as usize / char_size().0;
+                self.moved(self.scroll + column)
+            },
+            Event::Key(key) if key.pressed => match (key.unicode, key.code) {
+                (Some('\n'), _) => Response::Action(Action::Submitted(self.base.id)),
+                (_, KeyCode::Backspace) => {
+                    if self.cursor == 0 {
+                        return Response::Handled;
+                    }
+                    self.cursor -= 1;
+                    self.text.remove(self.cursor);
+                    self.edited()
+                },
+                (_, KeyCode::Delete) => {
+                    if self.cursor == self.text.len() {
+                        return Response::Handled;
+                    }
+                    self.text.remove(self.cursor);
+                    self.edited()
+                },
+                (_, KeyCode::ArrowLeft) => self.moved(self.cursor.saturating_sub(1)),
+                (_, KeyCode::ArrowRight) => self.moved(self.cursor + 1),
+                (_, KeyCode::Home) => self.moved(0),
+                (_, KeyCode::End) => self.moved(self.text.len()),
+                (Some(c), _) if !c.is_control() => {
+                    self.text.insert(self.cursor, c);
+                    self.cursor += 1;
+                    self.edited()
+                },
+                _ => Response::Ignored,
+            },
+            _ => Response::Ignored,
+        }
+    }
+
+    fn focusable(&self) -> bool {
+        true
+    }
+
+    fn set_focused(&mut self, focused : bool) {
+        self.focused = focused;
+        self.base.dirty = true;
+    }
+}
@@ -0,0 +1,247 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use super::*;
+use crate::input::{self, mouse, KeyCode};
+use crate::kernel::task;
+
+/// The root of a widget tree. It lays the tree out, keeps track of keyboard
+/// focus, routes input to widgets and redraws only widgets that changed.
+pub struct Ui {
+    root : Box<dyn Widget>,
+    focus : Option<WidgetId>,
+    captured : Option<WidgetId>, // Gets mouse events until the button that was pressed on it is released.
+    background : Color,
+}
+
+fn find(widget : &dyn Widget, id : WidgetId) -> Option<&dyn Widget> {
+    if widget.id() == id {
+        return Some(widget);
+    }
+    widget.children().iter().find_map(|child| find(child.as_ref(), id))
+}
+
+fn find_mut(widget : &mut dyn Widget, id : WidgetId) -> Option<&mut dyn Widget> {
+    if widget.id() == id {
+        return Some(widget);
+    }
+    for child in widget.children_mut() {
+        if let Some(found) = find_mut(child.as_mut(), id) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+fn focusable(widget : &dyn Widget, ids : &mut Vec<WidgetId>) {
+    if widget.focusable() {
+        ids.push(widget.id());
+    }
+    for child in widget.children() {
+        focusable(child.as_ref(), ids);
+    }
+}
+
+/// The widgets under a point, from `widget` down to the innermost one.
+fn hit_path(widget : &dyn Widget, x : isize, y : isize, path : &mut Vec<WidgetId>) {
+    if !widget.bounds().contains(x, y) {
+        return;
+    }
+    path.push(widget.id());
+    if let Some(child) = widget.children().iter().rev().find(|child| child.bounds().contains(x, y)) {
+        hit_path(child.as_ref(), x, y, path);
+    }
+}
+
+/// Draws the dirty parts of the tree. A dirty widget is drawn with everything
+/// under it, over its parent's background.
+fn redraw(widget : &mut dyn Widget, painter : &Painter, background : Color, force : bool) {
+    let force = force || widget.is_dirty();
+    if force {
+        if widget.background().is_none() {
+            painter.fill(widget.bounds(), background);
+        }
+        widget.draw(painter);
+        widget.base_mut().dirty = false;
+    }
+    let background = widget.background().unwrap_or(background);
+    let painter = painter.clipped(widget.bounds());
+    for child in widget.children_mut() {
+        redraw(child.as_mut(), &painter, background, force);
+    }
+}
+
+impl Ui {
+    /// A UI filling the screen.
+    pub fn new<W : Widget + 'static>(root : W) -> Self {
+        let mut ui = Ui { root : Box::new(root), focus : None, captured : None, background : BACKGROUND };
+        ui.set_bounds(Rect::screen());
+        ui.focus_next();
+        ui
+    }
+
+    pub fn set_bounds(&mut self, bounds : Rect) {
+        self.root.set_bounds(bounds);
+    }
+
+    /// Lays the tree out again, after widgets were added or resized.
+    pub fn layout(&mut self) {
+        let bounds = self.root.bounds();
+        self.root.set_bounds(bounds);
+    }
+
+    pub fn root(&self) -> &dyn Widget {
+        self.root.as_ref()
+    }
+
+    pub fn root_mut(&mut self) -> &mut dyn Widget {
+        self.root.as_mut()
+    }
+
+    pub fn get<T : Widget + 'static>(&self, id : WidgetId) -> Option<&T> {
+        find(self.root.as_ref(), id)?.as_any().downcast_ref()
+    }
+
+    pub fn get_mut<T : Widget + 'static>(&mut self, id : WidgetId) -> Option<&mut T> {
+        find_mut(self.root.as_mut(), id)?.as_any_mut().downcast_mut()
+    }
+
+    pub fn focus(&self) -> Option<WidgetId> {
+        self.focus
+    }
+
+    pub fn set_focus(&mut self, id : Option<WidgetId>) {
+        if id == self.focus {
+            return;
+        }
+        if let Some(widget) = self.focus.and_then(|old| find_mut(self.root.as_mut(), old)) {
+            widget.set_focused(false);
+        }
+        self.focus = id.filter(|id| find(self.root.as_ref(), *id).map_or(false, |widget| widget.focusable()));
+        if let Some(widget) = self.focus.and_then(|new| find_mut(self.root.as_mut(), new)) {
+            widget.set_focused(true);
+        }
+    }
+
+    fn move_focus(&mut self, forwards : bool) {
+        let mut ids = Vec::new();
+        focusable(self.root.as_ref(), &mut ids);
+        if ids.is_empty() {
+            return;
+        }
+        let next = match self.focus.and_then(|id| ids.iter().position(|other| *other == id)) {
+            Some(index) if forwards => (index + 1) % ids.len(),
+            Some(index) => (index + ids.len() - 1) % ids.len(),
+            None if forwards => 0,
+            None => ids.len() - 1,
+        };
+        self.set_focus(Some(ids[next]));
+    }
+
+    /// Moves focus to the next focusable widget, in tree order, as Tab does.
+    pub fn focus_next(&mut self) {
+        self.move_focus(true);
+    }
+
+    /// Moves focus back, as Shift+Tab does.
+    pub fn focus_previous(&mut self) {
+        self.move_focus(false);
+    }
+
+    fn send(&mut self, id : WidgetId, event : &Event) -> Response {
+        find_mut(self.root.as_mut(), id).map_or(Response::Ignored, |widget| widget.handle(event))
+    }
+
+    /// Offers `event` to the widgets in `path`, innermost first, until one takes it.
+    fn bubble(&mut self, path : &[WidgetId], event : &Event) -> (Option<WidgetId>, Response) {
+        for id in path.iter().rev() {
+            match self.send(*id, event) {
+                Response::Ignored => continue,
+                response => return (Some(*id), response),
+            }
+        }
+        (None, Response::Ignored)
+    }
+
+    /// Routes an event: keys go to the focused widget, mouse events to the
+    /// widget under the cursor. Returns what the widget reported, if anything.
+    pub fn dispatch(&mut self, event : Event) -> Option<Action> {
+        let response = match event {
+            Event::Key(key) if key.pressed && key.code == KeyCode::Tab => {
+                if key.modifiers.shift { self.focus_previous() } else { self.focus_next() }
+                Response::Handled
+            },
+            Event::Key(_) => match self.focus {
+                Some(id) => self.send(id, &event),
+                None => Response::Ignored,
+            },
+            Event::Mouse(MouseEvent::Press { x, y, .. }) => {
+                let mut path = Vec::new();
+                hit_path(self.root.as_ref(), x as isize, y as isize, &mut path);
+                let target = path.iter().rev().copied()
+                    .find(|id| find(self.root.as_ref(), *id).map_or(false, |widget| widget.focusable()));
+                if target.is_some() {
+                    self.set_focus(target);
+                }
+                let (handler, response) = self.bubble(&path, &event);
+                self.captured = handler;
+                response
+            },
+            Event::Mouse(MouseEvent::Release { .. }) | Event::Mouse(MouseEvent::Move { .. }) => match self.captured {
+                Some(id) => {
+                    if let Event::Mouse(MouseEvent::Release { .. }) = event {
+                        self.captured = None;
+                    }
+                    self.send(id, &event)
+                },
+                None => Response::Ignored,
+            },
+            Event::Mouse(MouseEvent::Scroll { x, y, .. }) => {
+                let mut path = Vec::new();
+                hit_path(self.root.as_ref(), x as isize, y as isize, &mut path);
+                self.bubble(&path, &event).1
+            },
+        };
+        match response {
+            Response::Action(action) => Some(action),
+            _ => None,
+        }
+    }
+
+    /// Draws whatever changed since the last redraw.
+    pub fn redraw(&mut self) {
+        redraw(self.root.as_mut(), &Painter::screen(), self.background, false);
+    }
+
+    /// Draws the whole tree.
+    pub fn redraw_all(&mut self) {
+        redraw(self.root.as_mut(), &Painter::screen(), self.background, true);
+    }
+
+    /// Handles the keyboard and mouse events waiting, then redraws. Returns
+    /// the first action a widget reported.
+    pub fn poll(&mut self) -> Option<Action> {
+        let mut action = None;
+        while action.is_none() {
+            let event = match input::event() {
+                Some(key) => Event::Key(key),
+                None => match mouse::event() {
+                    Some(mouse) => Event::Mouse(mouse),
+                    None => break,
+                },
+            };
+            action = self.dispatch(event);
+        }
+        self.redraw();
+        action
+    }
+
+    /// Waits for a widget to report an action. Ctrl+C ends the waiting task.
+    pub fn wait(&mut self) -> Action {
+        let mut action = None;
+        task::block_until(|| {
+            action = self.poll();
+            action.is_some()
+        });
+        action.unwrap()
+    }
+}
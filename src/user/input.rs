@@ -1,17 +1,22 @@
 use core::{fmt::Arguments, num::ParseIntError, ops::Range};
-use alloc::{collections::VecDeque, string::String};
+use alloc::{boxed::Box, collections::VecDeque, string::String, vec::Vec};
 use x86_64::instructions::interrupts::without_interrupts;
-use pc_keyboard::*;
+use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, KeyboardLayout, KeyState, ScancodeSet1};
+pub use pc_keyboard::KeyCode;
+pub use crate::kernel::hardware::mouse::{self, MouseButton, MouseEvent};
 use x86_64::instructions::port::*;
 use lazy_static::lazy_static;
 use spin::Mutex;
 
-use crate::{clear_row, io::devices::console::BACKSPACE, kernel::arch::enable_irq, log, time};
+use crate::{clear_row, io::devices::console, kernel::arch::enable_irq, readline::{Editor, FileCompleter}, warn};
 
 pub fn init() -> crate::kernel::InitResult<()> {
     enable_irq(1);
-    crate::kernel::arch::set_interrupt(1, on_key_pressed)
+    crate::kernel::arch::set_named_interrupt(1, "input::on_key_pressed", on_key_pressed)
     .expect("Unable To Setup Keyboard Interrupt");
+    if let Err(error) = without_interrupts(|| KEYBOARD.lock().update_leds()) {
+        warn!("Couldn't Set Keyboard LEDs: {}", error);
+    }
     Ok(())
 }
 
@@ -22,7 +27,7 @@ fn on_key_pressed(_irq : u8) {
 }
 
 pub fn serial_read() -> Option<u8> {
-    Some(crate::kernel::hardware::uart::read_u8())
+    crate::kernel::hardware::uart::try_read_u8()
 }
 
 pub fn serial_write(byte : u8){
@@ -52,51 +57,347 @@ pub fn reset_text_color() {
 }
     
 
+/// Keys kept for programs that haven't read them yet, the oldest are dropped first.
+pub const KEY_BUFFER_SIZE : usize = 128;
+
+const DATA_PORT : u16 = 0x60;
+const STATUS_PORT : u16 = 0x64;
+const SET_LEDS : u8 = 0xED;
+const STATUS_OUTPUT_FULL : u8 = 1 << 0;
+const STATUS_INPUT_FULL : u8 = 1 << 1;
+const STATUS_AUX_DATA : u8 = 1 << 5;
+const TIMEOUT : usize = 100_000;
+const RETRIES : usize = 3;
+const ACK : u8 = 0xFA;
+const RESEND : u8 = 0xFE;
+
+/// Ctrl+C as `HandleControl::MapLettersToUnicode` decodes it.
+const END_OF_TEXT : char = '\u{3}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Us,
+    Uk,
+    De,
+    Azerty,
+    Dvorak,
+    Jis,
+}
+
+impl Layout {
+    pub const ALL : [Layout; 6] = [Layout::Us, Layout::Uk, Layout::De, Layout::Azerty, Layout::Dvorak, Layout::Jis];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::Us => "us",
+            Layout::Uk => "uk",
+            Layout::De => "de",
+            Layout::Azerty => "azerty",
+            Layout::Dvorak => "dvorak",
+            Layout::Jis => "jis",
+        }
+    }
+
+    pub fn from_name(name : &str) -> Option<Layout> {
+        Self::ALL.iter().copied().find(|layout| layout.name().eq_ignore_ascii_case(name))
+    }
+
+    fn map(&self, code : KeyCode, modifiers : &pc_keyboard::Modifiers) -> DecodedKey {
+        let handle = HandleControl::MapLettersToUnicode;
+        match self {
+            Layout::Us => layouts::Us104Key::map_keycode(code, modifiers, handle),
+            Layout::Uk => layouts::Uk105Key::map_keycode(code, modifiers, handle),
+            Layout::De => layouts::De105Key::map_keycode(code, modifiers, handle),
+            Layout::Azerty => layouts::Azerty::map_keycode(code, modifiers, handle),
+            Layout::Dvorak => layouts::Dvorak104Key::map_keycode(code, modifiers, handle),
+            Layout::Jis => layouts::Jis109Key::map_keycode(code, modifiers, handle),
+        }
+    }
+}
+
+/// Which modifier and lock keys were active when a key event happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    pub shift       : bool,
+    pub ctrl        : bool,
+    pub alt         : bool,
+    pub alt_gr      : bool,
+    pub caps_lock   : bool,
+    pub num_lock    : bool,
+    pub scroll_lock : bool,
+}
+
+/// A key going down or up. `unicode` is what the key types with the current
+/// layout and modifiers, only for presses; Ctrl+letter gives a control character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub code      : KeyCode,
+    pub pressed   : bool,
+    pub modifiers : Modifiers,
+    pub unicode   : Option<char>,
+}
+
 lazy_static! {
     static ref KEYBOARD : Mutex<KeyBoard> = Mutex::new(KeyBoard::new());
+    static ref HISTORY : Mutex<Vec<String>> = Mutex::new(Vec::new());
 }
 
 
 struct KeyBoard {
     //IO Ports
     data    : Port<u8>, //Port 0x60
-    //pc-keyboard
-    kb      : Keyboard<layouts::Uk105Key, ScancodeSet1>,
+    status  : PortReadOnly<u8>, //Port 0x64
+    //pc-keyboard, only used to turn scancodes into key codes.
+    kb      : Keyboard<layouts::Us104Key, ScancodeSet1>,
 
+    layout    : Layout,
+    modifiers : pc_keyboard::Modifiers,
+    alt       : bool,
+    scroll_lock : bool,
 
-    buffer : VecDeque<char>,
+    events : VecDeque<KeyEvent>,
 }
 
 impl KeyBoard {
     pub fn new() -> Self {
         KeyBoard {
-            data : Port::new(0x60),
-            kb : Keyboard::new(layouts::Uk105Key, ScancodeSet1, HandleControl::Ignore),
-            buffer : VecDeque::new(),
+            data : Port::new(DATA_PORT),
+            status : PortReadOnly::new(STATUS_PORT),
+            kb : Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::MapLettersToUnicode),
+            layout : Layout::Uk,
+            modifiers : pc_keyboard::Modifiers {
+                lshift : false, rshift : false,
+                lctrl : false, rctrl : false,
+                numlock : true, capslock : false, alt_gr : false,
+            },
+            alt : false,
+            scroll_lock : false,
+            events : VecDeque::new(),
+        }
+    }
+
+    fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            shift : self.modifiers.lshift || self.modifiers.rshift,
+            ctrl : self.modifiers.lctrl || self.modifiers.rctrl,
+            alt : self.alt,
+            alt_gr : self.modifiers.alt_gr,
+            caps_lock : self.modifiers.capslock,
+            num_lock : self.modifiers.numlock,
+            scroll_lock : self.scroll_lock,
+        }
+    }
+
+    /// Sends a byte to the keyboard once the controller can take it.
+    fn send(&mut self, byte : u8) -> Result<(), &'static str> {
+        for _ in 0..TIMEOUT {
+            if unsafe { self.status.read() } & STATUS_INPUT_FULL == 0 {
+                unsafe { self.data.write(byte) };
+                return Ok(());
+            }
+        }
+        Err("PS/2 Controller Timed Out")
+    }
+
+    /// Sends a byte to the keyboard and waits for it to acknowledge,
+    /// sending it again when the keyboard asks.
+    fn write_keyboard(&mut self, byte : u8) -> Result<(), &'static str> {
+        for _ in 0..RETRIES {
+            self.send(byte)?;
+            match self.read_reply()? {
+                ACK => return Ok(()),
+                RESEND => continue,
+                _ => return Err("Keyboard Refused Command"),
+            }
+        }
+        Err("Keyboard Refused Command")
+    }
+
+    fn read_reply(&mut self) -> Result<u8, &'static str> {
+        for _ in 0..TIMEOUT {
+            let status = unsafe { self.status.read() };
+            if status & STATUS_OUTPUT_FULL != 0 {
+                // A mouse byte is in the way, reading it would break up its packet.
+                if status & STATUS_AUX_DATA != 0 {
+                    return Err("Keyboard Reply Blocked By Mouse");
+                }
+                return Ok(unsafe { self.data.read() });
+            }
+        }
+        Err("No Response From Keyboard")
+    }
+
+    fn update_leds(&mut self) -> Result<(), &'static str> {
+        let leds = (self.scroll_lock as u8) | (self.modifiers.numlock as u8) << 1 | (self.modifiers.capslock as u8) << 2;
+        self.write_keyboard(SET_LEDS)?;
+        self.write_keyboard(leds)
+    }
+
+    /// Keeps track of modifier and lock keys, returning whether `event` was one.
+    fn update_modifiers(&mut self, event : &pc_keyboard::KeyEvent) -> bool {
+        let down = event.state == KeyState::Down;
+        match event.code {
+            KeyCode::ShiftLeft => self.modifiers.lshift = down,
+            KeyCode::ShiftRight => self.modifiers.rshift = down,
+            KeyCode::ControlLeft => self.modifiers.lctrl = down,
+            KeyCode::ControlRight => self.modifiers.rctrl = down,
+            KeyCode::AltLeft => self.alt = down,
+            KeyCode::AltRight => self.modifiers.alt_gr = down,
+            KeyCode::CapsLock | KeyCode::NumpadLock | KeyCode::ScrollLock => {
+                if down {
+                    match event.code {
+                        KeyCode::CapsLock => self.modifiers.capslock = !self.modifiers.capslock,
+                        KeyCode::NumpadLock => self.modifiers.numlock = !self.modifiers.numlock,
+                        _ => self.scroll_lock = !self.scroll_lock,
+                    }
+                    // The lock state is kept either way, the LEDs are only a hint.
+                    let _ = self.update_leds();
+                }
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// Handles the keys the console itself owns, Alt+F1..F6 to switch virtual
+    /// console and Shift+PgUp/PgDn to scroll back. Returns whether `event` was one.
+    fn console_key(&mut self, event : &pc_keyboard::KeyEvent) -> bool {
+        let down = event.state == KeyState::Down;
+        let shift = self.modifiers.lshift || self.modifiers.rshift;
+        match event.code {
+            KeyCode::F1 | KeyCode::F2 | KeyCode::F3 |
+            KeyCode::F4 | KeyCode::F5 | KeyCode::F6 if self.alt => {
+                if down {
+                    let index = match event.code {
+                        KeyCode::F1 => 0, KeyCode::F2 => 1, KeyCode::F3 => 2,
+                        KeyCode::F4 => 3, KeyCode::F5 => 4, _ => 5,
+                    };
+                    console::switch_to(index);
+                }
+                true
+            },
+            KeyCode::PageUp if shift => {
+                if down { console::scroll_back(console::HEIGHT / 2) }
+                true
+            },
+            KeyCode::PageDown if shift => {
+                if down { console::scroll_forward(console::HEIGHT / 2) }
+                true
+            },
+            _ => false,
         }
     }
 
     pub fn process_scancode(&mut self) {
-        let result : Option<char> = {
-                if let Ok(Some(event)) = self.kb.add_byte(unsafe {self.data.read()}) {
-                    if let Some(key) = self.kb.process_keyevent(event) {
-                        match key {
-                            DecodedKey::Unicode(c) => Some(c),
-                            _ => None
-                        }
-                    } else {None}
-                } else {None}
-            };
-        if result.is_some() {
-             self.buffer.push_back(result.unwrap());
+        // Mouse bytes are left for the IRQ12 handler.
+        if unsafe { self.status.read() } & STATUS_AUX_DATA != 0 {
+            return;
+        }
+        let byte = unsafe { self.data.read() };
+        if byte == ACK || byte == RESEND {
+            return;
         }
+        let event = match self.kb.add_byte(byte) {
+            Ok(Some(event)) => event,
+            _ => return,
+        };
+        let is_modifier = self.update_modifiers(&event);
+        if !is_modifier && self.console_key(&event) {
+            return;
+        }
+
+        let pressed = event.state == KeyState::Down;
+        let unicode = match self.layout.map(event.code, &self.modifiers) {
+            DecodedKey::Unicode(c) if pressed && !is_modifier => Some(c),
+            _ => None,
+        };
+        if unicode == Some(END_OF_TEXT) {
+            crate::kernel::task::interrupt();
+            return;
+        }
+        if self.events.len() == KEY_BUFFER_SIZE {
+            self.events.pop_front();
+        }
+        self.events.push_back(KeyEvent { code : event.code, pressed, modifiers : self.modifiers(), unicode });
     }
 
+    /// The next typed character, skipping events that didn't type one.
     pub fn last_key(&mut self) -> Option<char> {
-        self.buffer.pop_front()
+        while let Some(event) = self.events.pop_front() {
+            if event.unicode.is_some() {
+                return event.unicode;
+            }
+        }
+        None
+    }
+
+    /// The next typed character without taking it, dropping events before it that didn't type one.
+    fn peek_key(&mut self) -> Option<char> {
+        while let Some(event) = self.events.front() {
+            if event.unicode.is_some() {
+                return event.unicode;
+            }
+            self.events.pop_front();
+        }
+        None
     }
 }
 
+/// The next key press or release, with its raw key code and modifiers.
+pub fn event() -> Option<KeyEvent> {
+    without_interrupts(|| KEYBOARD.lock().events.pop_front())
+}
+
+/// Waits for the next key event. Ctrl+C ends the waiting task.
+pub fn wait_event() -> KeyEvent {
+    let mut next = None;
+    crate::kernel::task::block_until(|| {
+        next = event();
+        next.is_some()
+    });
+    next.unwrap()
+}
+
+/// Waits for the next key event, `None` if Ctrl+C was pressed first.
+pub fn wait_event_or_interrupt() -> Option<KeyEvent> {
+    let mut next = None;
+    crate::kernel::task::block_until_or_interrupt(|| {
+        next = event();
+        next.is_some()
+    });
+    next
+}
+
+pub fn modifiers() -> Modifiers {
+    without_interrupts(|| KEYBOARD.lock().modifiers())
+}
+
+pub fn layout() -> Layout {
+    without_interrupts(|| KEYBOARD.lock().layout)
+}
+
+pub fn set_layout(layout : Layout) {
+    without_interrupts(|| KEYBOARD.lock().layout = layout);
+}
+
+/// Sets the lock keys, and their LEDs, as if they had been toggled.
+pub fn set_leds(caps_lock : bool, num_lock : bool, scroll_lock : bool) {
+    without_interrupts(|| {
+        let mut keyboard = KEYBOARD.lock();
+        keyboard.modifiers.capslock = caps_lock;
+        keyboard.modifiers.numlock = num_lock;
+        keyboard.scroll_lock = scroll_lock;
+        let _ = keyboard.update_leds();
+    });
+}
+
+/// Drops keys and events nobody has read.
+pub fn flush() {
+    without_interrupts(|| {
+        KEYBOARD.lock().events.clear();
+    });
+}
+
 pub fn key() -> Option<char> {
     let mut lk : Option<char> = None;
     without_interrupts(|| {
@@ -105,22 +406,20 @@ pub fn key() -> Option<char> {
     lk
 } 
 
+/// The character [key] would return next, left in the buffer.
+pub fn peek_key() -> Option<char> {
+    without_interrupts(|| KEYBOARD.lock().peek_key())
+}
+
+/// Reads a line with the line editor, sharing one history between calls.
 pub fn string(prompt : &str) -> String {
-    let mut s = String::new();
+    // The editor works on a copy, as Ctrl+C ends the task without unlocking anything.
+    let mut editor = Editor::with_completer(Box::new(FileCompleter));
+    editor.set_history(without_interrupts(|| HISTORY.lock().clone()));
     clear_row!();
-    log!("{}{}\r",prompt, s);
-    'consume_chars: loop {
-        if let Some(key) = key() {
-            if key == '\n' {break 'consume_chars}
-            if key == BACKSPACE as char {s.pop();}
-            s.push(key);
-            clear_row!();
-            log!("{}{}\r",prompt, s);
-        }
-        
-        time::sleep_ticks(10); //Check 100 Times per second
-    }
-    s
+    let line = editor.read_line(prompt);
+    without_interrupts(|| *HISTORY.lock() = editor.history().cloned().collect());
+    line
 }
 
 pub fn number(prompt : &str, range : Range<usize>) -> usize {
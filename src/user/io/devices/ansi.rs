@@ -0,0 +1,144 @@
+pub const ESCAPE : u8 = 0x1B;
+pub const BELL : u8 = 0x07;
+
+pub const MAX_PARAMS : usize = 8;
+
+/// A complete Control Sequence Introducer sequence, `ESC [ <private> <params> <final>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Csi {
+    params         : [u16; MAX_PARAMS],
+    count          : usize,
+    pub private    : Option<u8>, // `?`, `>`, `<` or `=` straight after the `[`.
+    pub final_byte : u8,
+}
+
+impl Csi {
+    pub fn params(&self) -> &[u16] {
+        &self.params[..self.count]
+    }
+
+    /// Parameter `index`, with a missing or zero value meaning `default`.
+    pub fn param(&self, index : usize, default : u16) -> u16 {
+        match self.params().get(index) {
+            Some(0) | None => default,
+            Some(value) => *value,
+        }
+    }
+
+    /// Parameter `index` where 0 is meaningful, as in erase and SGR.
+    pub fn param_or_zero(&self, index : usize) -> u16 {
+        self.params().get(index).copied().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// A byte to draw.
+    Print(u8),
+    /// A C0 control byte, like `\n`, `\r`, backspace or tab.
+    Control(u8),
+    /// `ESC` followed by a single final byte, like `ESC 7`.
+    Escape(u8),
+    Csi(Csi),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
+    Escape,
+    Csi,
+    Osc,
+    OscEscape,
+}
+
+/// Parses the ANSI/VT100 escape sequences a terminal receives, one byte at a
+/// time. It knows nothing about screens, so any terminal can share it.
+#[derive(Debug, Clone, Copy)]
+pub struct Parser {
+    state : State,
+    csi   : Csi,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Self {
+            state : State::Ground,
+            csi : Csi { params : [0; MAX_PARAMS], count : 0, private : None, final_byte : 0 },
+        }
+    }
+
+    /// Whether a sequence has started but not finished.
+    pub fn in_sequence(&self) -> bool {
+        self.state != State::Ground
+    }
+
+    /// Feeds one byte, returning what to do once a character or sequence is complete.
+    pub fn advance(&mut self, byte : u8) -> Option<Action> {
+        match self.state {
+            State::Ground => match byte {
+                ESCAPE => { self.state = State::Escape; None },
+                0x00..=0x1F | 0x7F => Some(Action::Control(byte)),
+                _ => Some(Action::Print(byte)),
+            },
+            State::Escape => match byte {
+                b'[' => {
+                    self.csi = Csi::default();
+                    self.state = State::Csi;
+                    None
+                },
+                b']' => { self.state = State::Osc; None },
+                ESCAPE => None,
+                _ => { self.state = State::Ground; Some(Action::Escape(byte)) },
+            },
+            State::Csi => match byte {
+                b'0'..=b'9' => {
+                    if self.csi.count == 0 {
+                        self.csi.count = 1;
+                    }
+                    if let Some(param) = self.csi.params.get_mut(self.csi.count - 1) {
+                        *param = param.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                    }
+                    None
+                },
+                b';' => {
+                    // An empty first parameter still counts, as in `ESC [ ; 5 H`.
+                    self.csi.count = (self.csi.count.max(1) + 1).min(MAX_PARAMS);
+                    None
+                },
+                b'?' | b'>' | b'<' | b'=' if self.csi.count == 0 && self.csi.private.is_none() => {
+                    self.csi.private = Some(byte);
+                    None
+                },
+                0x20..=0x2F => None, // Intermediate bytes, none of which are supported.
+                0x40..=0x7E => {
+                    self.csi.final_byte = byte;
+                    self.state = State::Ground;
+                    Some(Action::Csi(self.csi))
+                },
+                // Controls inside a sequence still take effect.
+                0x00..=0x1F if byte != ESCAPE => Some(Action::Control(byte)),
+                ESCAPE => { self.state = State::Escape; None },
+                _ => { self.state = State::Ground; None },
+            },
+            // Operating system commands, like window titles, are read and ignored.
+            State::Osc => {
+                match byte {
+                    BELL => self.state = State::Ground,
+                    ESCAPE => self.state = State::OscEscape,
+                    _ => {},
+                }
+                None
+            },
+            State::OscEscape => {
+                self.state = if byte == b'\\' { State::Ground } else { State::Osc };
+                None
+            },
+        }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
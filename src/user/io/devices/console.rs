@@ -1,3 +1,4 @@
+use alloc::{collections::VecDeque, vec, vec::Vec};
 use core::ops::Range;
 
 use spin::Mutex;
@@ -5,6 +6,7 @@ use ::vga::{colors::TextModeColor, writers::{ScreenCharacter, Text80x25, TextWri
 use x86_64::instructions::interrupts::without_interrupts;
 
 use crate::{graphics::*, io::IoWriter};
+use super::ansi::{self, Action, Csi, Parser};
 use lazy_static::lazy_static;
 
 pub const PRINTABLE_RANGE : Range<u8> = 0x20..0x7E;
@@ -14,39 +16,114 @@ pub const DELETE : u8 = 0x7F;
 pub const NEW_LINE : u8 = b'\n';
 pub const CARRIAGE_RETURN : u8 = b'\r';
 pub const TAB : u8 = b'\t';
-pub const ESCAPE : u8 = 0x1B;
+pub const ESCAPE : u8 = ansi::ESCAPE;
 pub const SPACE : u8 = b' ';
 
 pub const TAB_STOP : usize = 4;
 pub const WIDTH : usize = 80;
 pub const HEIGHT : usize = 25;
 
-pub const DEFAULT_TEXT_COLOR : TextModeColor = TextModeColor::new(Color::White, Color::Blue);
+/// Virtual consoles, switched between with Alt+F1 to Alt+F6.
+pub const CONSOLE_COUNT : usize = 6;
+/// Lines kept above the screen of each console, seen with Shift+PgUp.
+pub const SCROLLBACK_LINES : usize = 256;
+
+pub const DEFAULT_FOREGROUND : Color = Color::White;
+pub const DEFAULT_BACKGROUND : Color = Color::Blue;
+
+pub const DEFAULT_TEXT_COLOR : TextModeColor = TextModeColor::new(DEFAULT_FOREGROUND, DEFAULT_BACKGROUND);
 pub const ERROR_TEXT_COLOR : TextModeColor = TextModeColor::new(Color::Black, Color::Red);
 
 pub const BLANK : ScreenCharacter = ScreenCharacter::new(b' ', DEFAULT_TEXT_COLOR);
 
+/// The 16 VGA colors in ANSI order: black, red, green, yellow, blue, magenta,
+/// cyan and white, then their bright versions.
+pub const ANSI_COLORS : [Color; 16] = [
+    Color::Black, Color::Red, Color::Green, Color::Brown,
+    Color::Blue, Color::Magenta, Color::Cyan, Color::LightGrey,
+    Color::DarkGrey, Color::LightRed, Color::LightGreen, Color::Yellow,
+    Color::LightBlue, Color::Pink, Color::LightCyan, Color::White,
+];
+
+type Row = [ScreenCharacter; WIDTH];
+
+/// What a console keeps off screen. It needs the heap, so consoles start
+/// without one and draw straight to VGA until `init` gives them theirs.
+struct Screen {
+    cells   : Vec<ScreenCharacter>,
+    history : VecDeque<Row>,
+}
+
+struct Terminals {
+    consoles : [Console; CONSOLE_COUNT],
+    active   : usize, // Shown on screen.
+    output   : usize, // Written to by `_print`.
+}
+
+impl Terminals {
+    fn output(&mut self) -> &mut Console {
+        let output = self.output;
+        &mut self.consoles[output]
+    }
+}
+
 lazy_static! {
-    static ref STDOUT : Mutex<Console> = Mutex::new(Console::new(DEFAULT_TEXT_COLOR));
-    static ref STDERR : Mutex<Console> = Mutex::new(Console::new(ERROR_TEXT_COLOR));
+    static ref TERMINALS : Mutex<Terminals> = Mutex::new({
+        let mut consoles = [(); CONSOLE_COUNT].map(|_| Console::new(DEFAULT_FOREGROUND, DEFAULT_BACKGROUND));
+        consoles[0].set_active(true);
+        Terminals { consoles, active : 0, output : 0 }
+    });
 }
 
+/// A VT100-style terminal on the VGA text screen. Bytes go through an ANSI
+/// parser, so the same stream can be sent here and to a serial terminal.
+/// Each console keeps its own screen, only the active one draws to VGA.
 pub struct Console {
     writer : Text80x25,
     x : usize,
     y : usize,
     foreground : TextModeColor,
+    parser : Parser,
+
+    screen : Option<Screen>,
+    view : usize, // Lines scrolled back into the history.
+    active : bool,
+    cursor_visible : bool,
+
+    // Colors as set by SGR, combined into `foreground` by `update_color`.
+    default_colors : (Color, Color),
+    fg : Color,
+    bg : Color,
+    bright : bool,
+    reverse : bool,
+
+    saved : (usize, usize),
+    scroll_top : usize,
+    scroll_bottom : usize, // Inclusive.
 }
 
 impl Console {
-    pub fn new(color : TextModeColor) -> Console {
+    pub fn new(foreground : Color, background : Color) -> Console {
         let writer = Text80x25::new();
-        writer.set_mode();
+        let foreground_color = TextModeColor::new(foreground, background);
         Console {
             x : 0,
             y : 0,
             writer,
-            foreground : color
+            foreground : foreground_color,
+            parser : Parser::new(),
+            screen : None,
+            view : 0,
+            active : false,
+            cursor_visible : true,
+            default_colors : (foreground, background),
+            fg : foreground,
+            bg : background,
+            bright : false,
+            reverse : false,
+            saved : (0, 0),
+            scroll_top : 0,
+            scroll_bottom : HEIGHT - 1,
         }
     }
 
@@ -58,51 +135,349 @@ impl Console {
     }
 
     pub fn write_byte(&mut self, byte : u8) {
+        // New output brings a console scrolled back into its history to the bottom.
+        if self.view != 0 {
+            self.view = 0;
+            self.redraw();
+        }
+        match self.parser.advance(byte) {
+            Some(Action::Print(byte)) => self.print(byte),
+            Some(Action::Control(byte)) => self.control(byte),
+            Some(Action::Escape(byte)) => self.escape(byte),
+            Some(Action::Csi(csi)) => self.csi(&csi),
+            None => {},
+        }
+        self.update_cursor();
+    }
+
+    fn print(&mut self, byte : u8) {
+        // Wrapping waits for the next character, so text can end on the last column.
+        if self.x >= WIDTH {
+            self.new_line();
+        }
+        let byte = if PRINTABLE_RANGE.contains(&byte) || byte == 0x7E { byte } else { UNPRINTABLE_BLOCK };
+        self.put(self.x, self.y, ScreenCharacter::new(byte, self.foreground));
+        self.x += 1;
+    }
+
+    fn control(&mut self, byte : u8) {
         match byte {
-            0x20..=0x7E => {
-                self.writer.write_character(self.x, self.y, ScreenCharacter::new(byte, self.foreground));
-                self.x += 1;
-            },
             NEW_LINE  => {self.new_line()},
             CARRIAGE_RETURN => {self.carriage_return()},
-            DELETE => {self.write_byte(BACKSPACE)},
+            DELETE => {self.control(BACKSPACE)},
             BACKSPACE => {if self.x > 0 {self.x -= 1;} self.erase_current() },
-            _ => {self.write_byte(UNPRINTABLE_BLOCK)}
+            TAB => {self._tab()},
+            ansi::BELL => {},
+            _ => {self.print(UNPRINTABLE_BLOCK)}
+        }
+    }
+
+    fn escape(&mut self, byte : u8) {
+        match byte {
+            b'7' => self.save_cursor(),
+            b'8' => self.restore_cursor(),
+            b'D' => self.line_feed(),
+            b'E' => self.new_line(),
+            b'M' => self.reverse_line_feed(),
+            b'c' => self.reset(),
+            _ => {},
+        }
+    }
+
+    fn csi(&mut self, csi : &Csi) {
+        let n = csi.param(0, 1) as usize;
+        match (csi.private, csi.final_byte) {
+            (None, b'A') => self.y = self.y.saturating_sub(n).max(self.top_limit()),
+            (None, b'B') => self.y = (self.y + n).min(self.bottom_limit()),
+            (None, b'C') => self.x = (self.x + n).min(WIDTH - 1),
+            (None, b'D') => self.x = self.x.min(WIDTH - 1).saturating_sub(n),
+            (None, b'E') => { self.y = (self.y + n).min(self.bottom_limit()); self.x = 0; },
+            (None, b'F') => { self.y = self.y.saturating_sub(n).max(self.top_limit()); self.x = 0; },
+            (None, b'G') => self.x = (n - 1).min(WIDTH - 1),
+            (None, b'd') => self.y = (n - 1).min(HEIGHT - 1),
+            (None, b'H') | (None, b'f') => {
+                self.y = (csi.param(0, 1) as usize - 1).min(HEIGHT - 1);
+                self.x = (csi.param(1, 1) as usize - 1).min(WIDTH - 1);
+            },
+            (None, b'J') => self.erase_display(csi.param_or_zero(0)),
+            (None, b'K') => self.erase_line(csi.param_or_zero(0)),
+            (None, b'S') => self.scroll_up(n),
+            (None, b'T') => self.scroll_down(n),
+            (None, b'm') => self.select_graphic_rendition(csi),
+            (None, b'r') => {
+                let top = csi.param(0, 1) as usize - 1;
+                let bottom = (csi.param(1, HEIGHT as u16) as usize - 1).min(HEIGHT - 1);
+                if top < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.home();
+                }
+            },
+            (None, b's') => self.save_cursor(),
+            (None, b'u') => self.restore_cursor(),
+            (Some(b'?'), b'h') if csi.param_or_zero(0) == 25 => self.enable_cursor(),
+            (Some(b'?'), b'l') if csi.param_or_zero(0) == 25 => self.disable_cursor(),
+            _ => {},
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, csi : &Csi) {
+        if csi.params().is_empty() {
+            self.reset_colors();
+        }
+        for &param in csi.params() {
+            match param {
+                0 => self.reset_colors(),
+                1 => self.bright = true,
+                22 => self.bright = false,
+                7 => self.reverse = true,
+                27 => self.reverse = false,
+                30..=37 => self.fg = ANSI_COLORS[(param - 30) as usize],
+                39 => self.fg = self.default_colors.0,
+                40..=47 => self.bg = ANSI_COLORS[(param - 40) as usize],
+                49 => self.bg = self.default_colors.1,
+                90..=97 => self.fg = ANSI_COLORS[(param - 90 + 8) as usize],
+                100..=107 => self.bg = ANSI_COLORS[(param - 100 + 8) as usize],
+                _ => {},
+            }
+        }
+        self.update_color();
+    }
+
+    fn reset_colors(&mut self) {
+        self.fg = self.default_colors.0;
+        self.bg = self.default_colors.1;
+        self.bright = false;
+        self.reverse = false;
+    }
+
+    /// Bold shows as the bright version of one of the first 8 colors.
+    fn update_color(&mut self) {
+        let mut fg = self.fg;
+        if self.bright {
+            if let Some(index) = ANSI_COLORS[..8].iter().position(|color| *color == fg) {
+                fg = ANSI_COLORS[index + 8];
+            }
+        }
+        let (fg, bg) = if self.reverse { (self.bg, fg) } else { (fg, self.bg) };
+        self.foreground = TextModeColor::new(fg, bg);
+    }
+
+    fn reset(&mut self) {
+        self.parser = Parser::new();
+        self.reset_colors();
+        self.update_color();
+        self.scroll_top = 0;
+        self.scroll_bottom = HEIGHT - 1;
+        self.clear();
+        self.home();
+    }
+
+    // Cursor movement stops at the scroll region when the cursor is inside it.
+    fn top_limit(&self) -> usize {
+        if self.y >= self.scroll_top { self.scroll_top } else { 0 }
+    }
+
+    fn bottom_limit(&self) -> usize {
+        if self.y <= self.scroll_bottom { self.scroll_bottom } else { HEIGHT - 1 }
+    }
+
+    fn save_cursor(&mut self) {
+        self.saved = (self.x, self.y);
+    }
+
+    fn restore_cursor(&mut self) {
+        self.x = self.saved.0;
+        self.y = self.saved.1;
+    }
+
+    /// Sets a cell, drawing it when this console is on screen.
+    fn put(&mut self, x : usize, y : usize, character : ScreenCharacter) {
+        if let Some(screen) = &mut self.screen {
+            screen.cells[y * WIDTH + x] = character;
+        }
+        if self.active && self.view == 0 {
+            self.writer.write_character(x, y, character);
         }
-        
     }
 
     fn erase_current(&mut self) {
-        self.writer.write_character(self.x, self.y, ScreenCharacter::new(b' ', self.foreground))
+        self.put(self.x.min(WIDTH - 1), self.y, ScreenCharacter::new(b' ', self.foreground))
     }
 
-    fn new_line(&mut self) {
-        self.y += 1;
-        if self.y >= HEIGHT {
-            unsafe {
-                self.shift_contents_up();
+    fn blank_cells(&mut self, y : usize, columns : Range<usize>) {
+        for x in columns {
+            self.put(x, y, ScreenCharacter::new(SPACE, self.foreground));
+        }
+    }
+
+    fn row(&self, y : usize) -> Row {
+        let mut row = [BLANK; WIDTH];
+        match &self.screen {
+            Some(screen) => row.copy_from_slice(&screen.cells[y * WIDTH..(y + 1) * WIDTH]),
+            None if self.active => {
+                let buffer = self.writer.get_frame_buffer().1;
+                for (x, character) in row.iter_mut().enumerate() {
+                    *character = unsafe { *buffer.add(y * WIDTH + x) };
+                }
+            },
+            None => {},
+        }
+        row
+    }
+
+    fn history_len(&self) -> usize {
+        self.screen.as_ref().map_or(0, |screen| screen.history.len())
+    }
+
+    /// Gives the console somewhere to keep its screen and history, starting
+    /// from what it has drawn so far.
+    fn allocate(&mut self) {
+        if self.screen.is_some() {
+            return;
+        }
+        let mut cells = vec![ScreenCharacter::new(SPACE, self.foreground); WIDTH * HEIGHT];
+        for y in 0..HEIGHT {
+            cells[y * WIDTH..(y + 1) * WIDTH].copy_from_slice(&self.row(y));
+        }
+        self.screen = Some(Screen { cells, history : VecDeque::with_capacity(SCROLLBACK_LINES) });
+    }
+
+    /// Draws the whole screen, or the part of the history being looked at.
+    fn redraw(&mut self) {
+        let screen = match &self.screen {
+            Some(screen) if self.active => screen,
+            _ => return,
+        };
+        let history = &screen.history;
+        let start = history.len() - self.view;
+        for y in 0..HEIGHT {
+            let line = start + y;
+            let row = if line < history.len() { &history[line][..] } else {
+                let y = line - history.len();
+                &screen.cells[y * WIDTH..(y + 1) * WIDTH]
+            };
+            for (x, character) in row.iter().enumerate() {
+                self.writer.write_character(x, y, *character);
             }
-            self.y = HEIGHT - 1;
         }
+    }
+
+    /// Scrolls the view `lines` into the history, or back towards the bottom when negative.
+    pub fn scroll_view(&mut self, lines : isize) {
+        let view = (self.view as isize + lines).max(0) as usize;
+        let view = view.min(self.history_len());
+        if view != self.view {
+            self.view = view;
+            self.redraw();
+            self.update_cursor();
+        }
+    }
+
+    pub fn set_active(&mut self, active : bool) {
+        self.active = active;
+        if active {
+            self.writer.set_mode();
+            crate::graphics::vga::reload_text_font();
+            if self.cursor_visible { self.writer.enable_cursor() } else { self.writer.disable_cursor() }
+            self.redraw();
+            self.update_cursor();
+        }
+    }
+
+    /// 0 erases from the cursor to the end, 1 from the start to the cursor, 2 everything.
+    fn erase_line(&mut self, mode : u16) {
+        let x = self.x.min(WIDTH - 1);
+        match mode {
+            0 => self.blank_cells(self.y, x..WIDTH),
+            1 => self.blank_cells(self.y, 0..x + 1),
+            2 => self.blank_cells(self.y, 0..WIDTH),
+            _ => {},
+        }
+    }
+
+    fn erase_display(&mut self, mode : u16) {
+        let rows = match mode {
+            0 => { self.erase_line(0); self.y + 1..HEIGHT },
+            1 => { self.erase_line(1); 0..self.y },
+            2 | 3 => 0..HEIGHT,
+            _ => 0..0,
+        };
+        for y in rows {
+            self.blank_cells(y, 0..WIDTH);
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.line_feed();
         self.carriage_return()
     }
 
+    fn line_feed(&mut self) {
+        if self.y == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.y < HEIGHT - 1 {
+            self.y += 1;
+        }
+    }
+
+    fn reverse_line_feed(&mut self) {
+        if self.y == self.scroll_top {
+            self.scroll_down(1);
+        } else if self.y > 0 {
+            self.y -= 1;
+        }
+    }
+
     fn clear(&mut self) {
-        self.writer.fill_screen(ScreenCharacter::new(b' ', self.foreground));
-    }
-    /// [WARNING] This function performs potentially risky raw pointer operations,
-    /// it must ONLY be called by newline(), and must be acknowledged by an unsafe call.
-     unsafe fn shift_contents_up(&mut self) {
-            let buffer = self.writer.get_frame_buffer().1;
-            for y in 1..HEIGHT {
-                for x in 0..WIDTH {
-                    let above : isize = (y as isize - 1) * WIDTH as isize + x as isize;
-                    let current_offset : isize = (y as isize) * WIDTH as isize + x as isize;
-                    *buffer.offset(above) = *buffer.offset(current_offset);
+        let blank = ScreenCharacter::new(b' ', self.foreground);
+        if let Some(screen) = &mut self.screen {
+            screen.cells.fill(blank);
+        }
+        if self.active && self.view == 0 {
+            self.writer.fill_screen(blank);
+        }
+    }
+
+    /// Moves the rows of the scroll region up by `lines`, blanking the ones left
+    /// at the bottom. Rows leaving the top of the screen go into the history.
+    fn scroll_up(&mut self, lines : usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let lines = lines.min(bottom - top + 1);
+        if top == 0 {
+            for y in 0..lines {
+                let row = self.row(y);
+                if let Some(screen) = &mut self.screen {
+                    if screen.history.len() == SCROLLBACK_LINES {
+                        screen.history.pop_front();
+                    }
+                    screen.history.push_back(row);
                 }
             }
+        }
+        for y in top..bottom + 1 - lines {
+            self.copy_row(y + lines, y);
+        }
+        for y in bottom + 1 - lines..=bottom {
+            self.blank_cells(y, 0..WIDTH);
+        }
+    }
 
-            self.clear_last_row();
+    fn scroll_down(&mut self, lines : usize) {
+        let (top, bottom) = (self.scroll_top, self.scroll_bottom);
+        let lines = lines.min(bottom - top + 1);
+        for y in (top + lines..=bottom).rev() {
+            self.copy_row(y - lines, y);
+        }
+        for y in top..top + lines {
+            self.blank_cells(y, 0..WIDTH);
+        }
+    }
+
+    fn copy_row(&mut self, from : usize, to : usize) {
+        for (x, character) in self.row(from).iter().enumerate() {
+            self.put(x, to, *character);
+        }
     }
 
     fn carriage_return(&mut self) {
@@ -115,49 +490,156 @@ impl Console {
     }
 
     fn _tab(&mut self) {
-        for _ in 0..=TAB_STOP {
-            self.write_byte(SPACE);
-        }
+        let next = (self.x / TAB_STOP + 1) * TAB_STOP;
+        self.x = next.min(WIDTH - 1);
     }
 
     pub fn enable_cursor(&mut self) {
-        self.writer.enable_cursor();
+        self.cursor_visible = true;
+        if self.active {
+            self.writer.enable_cursor();
+        }
     }
 
     pub fn disable_cursor(&mut self) {
-        self.writer.disable_cursor();
+        self.cursor_visible = false;
+        if self.active {
+            self.writer.disable_cursor();
+        }
     }
 
+    /// Moves the cursor to (`x`, `y`), as CSI H does.
     pub fn position_cursor(&mut self, x : usize, y : usize) {
-        self.writer.set_cursor_position(x, y)
+        self.x = x.min(WIDTH - 1);
+        self.y = y.min(HEIGHT - 1);
+        self.update_cursor();
+    }
+
+    /// Puts the hardware cursor where the next character goes, while this
+    /// console is on screen and not scrolled back.
+    fn update_cursor(&mut self) {
+        if self.active && self.view == 0 {
+            self.writer.set_cursor_position(self.x.min(WIDTH - 1), self.y);
+        }
     }
 
     pub fn clear_current_row(&mut self) {
         self.carriage_return();
-        for _ in 0..WIDTH {
-            self.write_byte(SPACE);
-        }
+        self.blank_cells(self.y, 0..WIDTH);
     }
 
     pub fn clear_last_row(&mut self) {
         self.carriage_return();
-        for x in 0..WIDTH {
-            self.write_byte_at_pos(SPACE, x, 24);
-        }
+        self.blank_cells(HEIGHT - 1, 0..WIDTH);
     }
 
     pub fn set_foreground_color(&mut self, color : Color) {
-        self.foreground.set_foreground(color);
+        self.fg = color;
+        self.update_color();
     }
 
     pub fn set_background_color(&mut self, color : Color) {
-        self.foreground.set_background(color);
+        self.bg = color;
+        self.update_color();
     }
 }
 
+fn with_output<F : FnOnce(&mut Console)>(f : F) {
+    without_interrupts(|| {
+        let mut terminals = TERMINALS.lock();
+        let console = terminals.output();
+        f(console);
+        console.update_cursor();
+    })
+}
+
+/// Gives every console its scrollback, once the heap is up.
+pub fn init() -> crate::kernel::InitResult<()> {
+    without_interrupts(|| {
+        for console in TERMINALS.lock().consoles.iter_mut() {
+            console.allocate();
+        }
+    });
+    Ok(())
+}
+
 pub fn _print(args : Arguments) {
-    without_interrupts(||{
-        STDOUT.lock().write_fmt(args).expect("Error Writing to STDOUT");
+    with_output(|console| console.write_fmt(args).expect("Error Writing to STDOUT"))
+}
+
+/// Prints in the error colors, then goes back to the colors set before.
+pub fn _eprint(args : Arguments) {
+    with_output(|console| {
+        let colors = (console.fg, console.bg, console.bright, console.reverse);
+        console.foreground = ERROR_TEXT_COLOR;
+        let _ = console.write_fmt(args);
+        console.fg = colors.0;
+        console.bg = colors.1;
+        console.bright = colors.2;
+        console.reverse = colors.3;
+        console.update_color();
+    })
+}
+
+/// Prints to console `index`, whether or not it's the one on screen.
+pub fn print_to(index : usize, args : Arguments) {
+    without_interrupts(|| {
+        if let Some(console) = TERMINALS.lock().consoles.get_mut(index) {
+            let _ = console.write_fmt(args);
+        }
+    })
+}
+
+/// Shows console `index`, the first being 0 for Alt+F1. Does nothing while the
+/// screen is in graphics mode, as showing a console would set text mode.
+pub fn switch_to(index : usize) {
+    if !crate::graphics::vga::is_text_mode() {
+        return;
+    }
+    without_interrupts(|| {
+        let mut terminals = TERMINALS.lock();
+        if index >= terminals.consoles.len() || index == terminals.active {
+            return;
+        }
+        let active = terminals.active;
+        terminals.consoles[active].set_active(false);
+        terminals.consoles[index].set_active(true);
+        terminals.active = index;
+    })
+}
+
+pub fn active() -> usize {
+    without_interrupts(|| TERMINALS.lock().active)
+}
+
+/// Sends `_print`, and so `log!`, to console `index`.
+pub fn set_output(index : usize) {
+    without_interrupts(|| {
+        let mut terminals = TERMINALS.lock();
+        if index < terminals.consoles.len() {
+            terminals.output = index;
+        }
+    })
+}
+
+pub fn output() -> usize {
+    without_interrupts(|| TERMINALS.lock().output)
+}
+
+/// Scrolls the console on screen back through its history, as Shift+PgUp does.
+pub fn scroll_back(lines : usize) {
+    without_interrupts(|| {
+        let mut terminals = TERMINALS.lock();
+        let active = terminals.active;
+        terminals.consoles[active].scroll_view(lines as isize);
+    })
+}
+
+pub fn scroll_forward(lines : usize) {
+    without_interrupts(|| {
+        let mut terminals = TERMINALS.lock();
+        let active = terminals.active;
+        terminals.consoles[active].scroll_view(-(lines as isize));
     })
 }
 
@@ -165,7 +647,6 @@ impl IoWriter<&str> for Console {
     fn write(&mut self, item : &str) {
         for byte in item.as_bytes() {
             if *byte == 0  { return }; 
-            if self.x >= WIDTH {self.write_byte(NEW_LINE); self.write_byte(CARRIAGE_RETURN);}
             self.write_byte(*byte);
         } 
     }
@@ -182,37 +663,25 @@ impl Write for Console {
 
 
 pub fn clear() {
-    without_interrupts(|| {
-        STDOUT.lock().clear();
-    })
+    with_output(|console| console.clear())
 }
 
 pub fn home() {
-    without_interrupts(|| {
-        STDOUT.lock().home();
-    })
+    with_output(|console| console.home())
 }
 
 pub fn foreground(color : Color) {
-    without_interrupts(|| {
-        STDOUT.lock().set_foreground_color(color);
-    })
+    with_output(|console| console.set_foreground_color(color))
 }
 
 pub fn background(color : Color) {
-    without_interrupts(|| {
-        STDOUT.lock().set_background_color(color);
-    })
+    with_output(|console| console.set_background_color(color))
 }
 
 pub fn clear_current_row() {
-    without_interrupts(|| {
-        STDOUT.lock().clear_current_row();
-    });
+    with_output(|console| console.clear_current_row())
 }
 
 pub fn carriage_return() {
-    without_interrupts(|| {
-        STDOUT.lock().carriage_return();
-    });
-}
\ No newline at end of file
+    with_output(|console| console.carriage_return())
+}
@@ -1,3 +1,4 @@
+pub mod ansi;
 pub mod console;
 pub mod serial;
 pub mod keyboard;
@@ -6,16 +7,21 @@ pub use console::Console;
 pub use serial::Serial;
 pub use keyboard::KeyBoard;
 // TODO: Move input Items into here.
+/// Prints to the console and serial, and keeps a copy in the kernel log.
 #[macro_export]
 macro_rules! log {
-    () => {
-        $crate::io::devices::console::_print(format_args!(""));
-        $crate::input::serial_print!();
+    () => {};
+
+    ($($args:tt)*) => {
+        $crate::kernel::klog::_log_raw($crate::kernel::klog::Level::Info, $crate::kernel::klog::tag(module_path!()), format_args!($($args)*))
     };
+}
 
+/// Like `log!`, but logged as an error, so it's shown in the error colors on the console.
+#[macro_export]
+macro_rules! elog {
     ($($args:tt)*) => {
-        $crate::io::devices::console::_print(format_args!($($args)*));   
-        $crate::input::serial_print!($($args)*);
+        $crate::kernel::klog::_log_raw($crate::kernel::klog::Level::Error, $crate::kernel::klog::tag(module_path!()), format_args!($($args)*))
     };
 }
 
@@ -1,24 +1,60 @@
 use core::fmt::Write;
 
-use crate::{input::serial_read, io::{IoReader, IoWriter}, kernel::hardware::uart::*};
-pub struct Serial;
+use crate::{io::{IoReader, IoWriter}, kernel::hardware::uart::{self, ComPort, LineConfig}};
+
+pub struct Serial {
+    port : ComPort,
+}
+
+impl Serial {
+    pub fn new(port : ComPort) -> Self {
+        Self { port }
+    }
+
+    pub fn port(&self) -> ComPort {
+        self.port
+    }
+
+    pub fn configure(&mut self, config : LineConfig) {
+        uart::configure(self.port, config)
+    }
+
+    /// Waits for a byte instead of returning `None` when nothing has arrived yet.
+    pub fn read_blocking(&mut self) -> u8 {
+        uart::receive(self.port)
+    }
+
+    pub fn available(&self) -> usize {
+        uart::available(self.port)
+    }
+}
+
+impl Default for Serial {
+    fn default() -> Self {
+        Self::new(ComPort::Com1)
+    }
+}
 
 impl IoReader<u8> for Serial {
     fn read(&mut self) -> Option<u8> {
-        serial_read()
+        uart::try_receive(self.port)
     }
 }
 
 impl IoWriter<u8> for Serial {
     fn write(&mut self, item : u8) {
-        write_u8(item)
+        uart::send(self.port, item)
     }
 }
 
 impl Write for Serial {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        self.write_fmt(format_args!("{}\r\n", s))
+        for byte in s.bytes() {
+            if byte == b'\n' {
+                uart::send(self.port, b'\r');
+            }
+            uart::send(self.port, byte);
+        }
+        Ok(())
     }
 }
-
-
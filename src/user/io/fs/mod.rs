@@ -0,0 +1,6 @@
+pub use crate::kernel::path::Path;
+pub use crate::kernel::pipe::{pipe, pipeline, PipeReader, PipeWriter, Stage, StageIo};
+pub use crate::kernel::vfs::{
+    chdir, getcwd, mkfifo, open, OpenFile,
+    OPEN_READ, OPEN_WRITE, OPEN_CREATE, OPEN_TRUNCATE, OPEN_APPEND,
+};
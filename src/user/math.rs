@@ -43,6 +43,19 @@ pub fn maxu(a : usize, b : usize) -> usize {
     if a < b {b} else {a}
 }
 
+/// The largest integer whose square is at most `n`.
+pub fn isqrt(n : u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+    let mut x = n;
+    let mut y = (x + 1) / 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
 
 pub struct Point2i {
     x : isize,
@@ -2,6 +2,7 @@ pub mod graphics;
 pub mod math;
 pub mod time;
 pub mod input;
+pub mod readline;
 // pub mod fs;
 pub mod sys;
 pub mod io;
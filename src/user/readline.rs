@@ -0,0 +1,264 @@
+use alloc::{boxed::Box, collections::VecDeque, format, string::String, vec::Vec};
+
+use crate::input::{self, KeyCode};
+use crate::io::devices::console::{BACKSPACE, DELETE, WIDTH};
+use crate::kernel::vfs::{self, NodeKind};
+use crate::print;
+
+/// Lines an editor remembers for Up/Down, the oldest are forgotten first.
+pub const HISTORY_SIZE : usize = 64;
+
+const CTRL_A : char = '\u{1}';
+const CTRL_E : char = '\u{5}';
+const CTRL_K : char = '\u{b}';
+const CTRL_U : char = '\u{15}';
+
+/// Suggests how to finish the word before the cursor when Tab is pressed.
+pub trait Completer {
+    /// Returns where the word being completed starts, in characters, and
+    /// every full replacement for it.
+    fn complete(&self, line : &str, cursor : usize) -> (usize, Vec<String>);
+}
+
+/// Completes file and directory names through the VFS, relative to the working
+/// directory. Directories are completed with a trailing `/`.
+pub struct FileCompleter;
+
+impl Completer for FileCompleter {
+    fn complete(&self, line : &str, cursor : usize) -> (usize, Vec<String>) {
+        let before : String = line.chars().take(cursor).collect();
+        let start = before.rfind(' ').map_or(0, |space| space + 1);
+        let word = &before[start..];
+        let (dir, prefix) = match word.rfind('/') {
+            Some(slash) => (&word[..slash + 1], &word[slash + 1..]),
+            None => ("", word),
+        };
+
+        let entries = vfs::read_dir(if dir.is_empty() { "." } else { dir }).unwrap_or_default();
+        let mut candidates : Vec<String> = entries.iter()
+            .filter(|entry| entry.name.starts_with(prefix))
+            .map(|entry| match entry.kind {
+                NodeKind::Directory => format!("{}{}/", dir, entry.name),
+                _ => format!("{}{}", dir, entry.name),
+            })
+            .collect();
+        candidates.sort();
+        (before[..start].chars().count(), candidates)
+    }
+}
+
+/// A readline-style line editor: cursor movement, Home/End, insert and delete,
+/// a history browsed with Up/Down and tab completion.
+pub struct Editor {
+    history   : VecDeque<String>,
+    completer : Option<Box<dyn Completer>>,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self { history : VecDeque::new(), completer : None }
+    }
+
+    pub fn with_completer(completer : Box<dyn Completer>) -> Self {
+        Self { history : VecDeque::new(), completer : Some(completer) }
+    }
+
+    pub fn set_completer(&mut self, completer : Option<Box<dyn Completer>>) {
+        self.completer = completer;
+    }
+
+    /// Remembers `line`, unless it's blank or the same as the last one.
+    pub fn add_history(&mut self, line : &str) {
+        if line.trim().is_empty() || self.history.back().map(String::as_str) == Some(line) {
+            return;
+        }
+        if self.history.len() == HISTORY_SIZE {
+            self.history.pop_front();
+        }
+        self.history.push_back(String::from(line));
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &String> {
+        self.history.iter()
+    }
+
+    pub fn set_history<I : IntoIterator<Item = String>>(&mut self, lines : I) {
+        self.history.clear();
+        for line in lines {
+            self.add_history(&line);
+        }
+    }
+
+    /// Shows `prompt` and edits a line until Enter, adding it to the history.
+    /// Ctrl+C drops the line, showing `^C`, and returns an empty string.
+    pub fn read_line(&mut self, prompt : &str) -> String {
+        let mut line = Line { prompt, chars : Vec::new(), cursor : 0, offset : 0 };
+        // Where Up/Down is in the history, `history.len()` being the line being typed.
+        let mut position = self.history.len();
+        let mut typed = Vec::new();
+
+        print!("{}", prompt);
+        loop {
+            let event = match input::wait_event_or_interrupt() {
+                Some(event) => event,
+                None => {
+                    line.move_to(line.chars.len());
+                    print!("^C\n");
+                    return String::new();
+                },
+            };
+            if !event.pressed {
+                continue;
+            }
+            match (event.unicode, event.code) {
+                (Some('\n'), _) | (Some('\r'), _) => break,
+                (Some(c), _) if c == BACKSPACE as char => line.backspace(),
+                (Some(c), _) if c == DELETE as char => line.delete(),
+                (Some('\t'), _) => self.complete(&mut line),
+                (Some(CTRL_A), _) | (None, KeyCode::Home) => line.move_to(0),
+                (Some(CTRL_E), _) | (None, KeyCode::End) => line.move_to(line.chars.len()),
+                (Some(CTRL_K), _) => line.kill_to_end(),
+                (Some(CTRL_U), _) => line.kill_to_start(),
+                (Some(c), _) if !c.is_control() => line.insert(c),
+                (None, KeyCode::ArrowLeft) => line.move_to(line.cursor.saturating_sub(1)),
+                (None, KeyCode::ArrowRight) => line.move_to(line.cursor + 1),
+                (None, KeyCode::ArrowUp) if position > 0 => {
+                    if position == self.history.len() {
+                        typed = line.chars.clone();
+                    }
+                    position -= 1;
+                    line.replace(self.history[position].chars().collect());
+                },
+                (None, KeyCode::ArrowDown) if position < self.history.len() => {
+                    position += 1;
+                    let next = match self.history.get(position) {
+                        Some(entry) => entry.chars().collect(),
+                        None => typed.clone(),
+                    };
+                    line.replace(next);
+                },
+                _ => {},
+            }
+        }
+        print!("\n");
+
+        let text : String = line.chars.iter().collect();
+        self.add_history(&text);
+        text
+    }
+
+    fn complete(&self, line : &mut Line) {
+        let completer = match &self.completer {
+            Some(completer) => completer,
+            None => return,
+        };
+        let text : String = line.chars.iter().collect();
+        let (start, candidates) = completer.complete(&text, line.cursor);
+        let word_len = line.cursor - start;
+
+        let common = match candidates.first() {
+            Some(first) => candidates.iter().skip(1).fold(first.chars().count(), |common, candidate| {
+                first.chars().zip(candidate.chars()).take(common).take_while(|(a, b)| a == b).count()
+            }),
+            None => return,
+        };
+        if common > word_len {
+            for c in candidates[0].chars().skip(word_len).take(common - word_len) {
+                line.insert(c);
+            }
+            if candidates.len() == 1 && !candidates[0].ends_with('/') {
+                line.insert(' ');
+            }
+        } else if candidates.len() > 1 {
+            print!("\n{}\n", candidates.join("  "));
+            line.redraw();
+        }
+    }
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The line being edited, drawn after the prompt on the current row. A line
+/// too long for the row scrolls sideways to keep the cursor in view.
+struct Line<'a> {
+    prompt : &'a str,
+    chars  : Vec<char>,
+    cursor : usize,
+    offset : usize, // The first character shown.
+}
+
+impl Line<'_> {
+    /// How many characters fit after the prompt, the last column being kept for the cursor.
+    fn room(&self) -> usize {
+        (WIDTH - 1).saturating_sub(self.prompt.chars().count()).max(1)
+    }
+
+    /// Redraws the prompt and the part of the line around the cursor, then
+    /// puts the cursor back.
+    fn redraw(&mut self) {
+        let room = self.room();
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor - self.offset > room {
+            self.offset = self.cursor - room;
+        }
+        let shown : String = self.chars.iter().skip(self.offset).take(room).collect();
+        let column = self.prompt.chars().count() + self.cursor - self.offset;
+        print!("\r\x1b[K{}{}\x1b[{}G", self.prompt, shown, column + 1);
+    }
+
+    fn insert(&mut self, c : char) {
+        self.chars.insert(self.cursor, c);
+        self.cursor += 1;
+        // Typing at the end only needs the new character, unless the line has to scroll.
+        if self.cursor == self.chars.len() && self.cursor - self.offset <= self.room() {
+            print!("{}", c);
+        } else {
+            self.redraw();
+        }
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.chars.remove(self.cursor);
+            self.redraw();
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.chars.len() {
+            self.chars.remove(self.cursor);
+            self.redraw();
+        }
+    }
+
+    fn move_to(&mut self, cursor : usize) {
+        let cursor = cursor.min(self.chars.len());
+        if cursor != self.cursor {
+            self.cursor = cursor;
+            self.redraw();
+        }
+    }
+
+    fn kill_to_end(&mut self) {
+        self.chars.truncate(self.cursor);
+        self.redraw();
+    }
+
+    fn kill_to_start(&mut self) {
+        self.chars.drain(..self.cursor);
+        self.cursor = 0;
+        self.redraw();
+    }
+
+    fn replace(&mut self, chars : Vec<char>) {
+        self.cursor = chars.len();
+        self.chars = chars;
+        self.redraw();
+    }
+}
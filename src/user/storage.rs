@@ -7,7 +7,8 @@ pub fn get_root(disk : DiskID) -> FileTable {
 }
 
 impl FileTable {
-    pub fn open_file(&mut self, name : &str, mut entry_buffer : &mut FileTableEntry) {
-        *entry_buffer = FileTableEntry::empty();
-    } 
+    /// Fills `entry_buffer` with the entry for `name`, or an empty entry if there isn't one.
+    pub fn open_file(&mut self, name : &str, entry_buffer : &mut FileTableEntry) {
+        *entry_buffer = self.search(name).unwrap_or_else(FileTableEntry::empty);
+    }
 }
\ No newline at end of file
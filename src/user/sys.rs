@@ -52,15 +52,40 @@ pub mod mem {
 
 pub mod programs {
 
-    use crate::graphics::Color;
+    use crate::graphics::{self, Color, font::Font};
 
     use crate::heap::MB;
     use crate::input::{self};
+    use crate::kernel::klog;
     use crate::kernel::drivers::file_systems::{Disk, get_disk, get_sector, set_sector, zero_format_disk};
     use crate::kernel::hardware::ata;
-    use crate::{background, clear_console, foreground, log};
+    use crate::{background, clear_console, foreground, log, print};
 
     pub type ProgramStatusCode = u16;
+
+    /// Prints the kernel log buffer to the console.
+    pub fn dmesg() -> ProgramStatusCode {
+        print!("{}", klog::dmesg());
+        0
+    }
+
+    /// Loads a PSF font for graphics text, and for the consoles if it's 8 pixels wide.
+    pub fn setfont(path : &str) -> ProgramStatusCode {
+        let font = match Font::load(path) {
+            Ok(font) => font,
+            Err(error) => {
+                log!("setfont: {}: {}\n", path, error);
+                return 1;
+            },
+        };
+        if font.width() == 8 {
+            if let Err(error) = font.load_text_mode() {
+                log!("setfont: {}\n", error);
+            }
+        }
+        graphics::set_font(font);
+        0
+    }
     pub fn install() -> ProgramStatusCode {
         foreground!(Color::Red);
         background!(Color::Black);